@@ -0,0 +1,497 @@
+use crate::dynamics;
+
+const PRECESSION_QUADRATURE_STEPS: usize = 1024;
+const MOMENT_TOLERANCE: f64 = 1e-9;
+
+// Closed-form orientation of a torque-free rigid body. The orientation is
+// expressed with Euler angles (precession, nutation, spin) relative to a frame
+// whose z axis points along the constant angular momentum.
+pub struct TorqueFreeSolution {
+    pos: nalgebra::Vector3<f64>,
+    lin_vel: nalgebra::Vector3<f64>,
+    rot_mat: nalgebra::Matrix3<f64>,
+    ang_mom: nalgebra::Vector3<f64>,
+    inv_ine: nalgebra::Matrix3<f64>,
+    // Columns: world-frame basis with the angular momentum as the z axis
+    l_frame: nalgebra::Matrix3<f64>,
+    // Columns: body-frame principal axes (a, b, c), where c is the axis which
+    // the angular momentum circles around as seen from the body
+    principal_frame: nalgebra::Matrix3<f64>,
+    moments: nalgebra::Vector3<f64>,
+    precession0: f64,
+    motion: Motion,
+}
+
+enum Motion {
+    // Angular velocity is constant in the world frame
+    Uniform {
+        omega: nalgebra::Vector3<f64>,
+    },
+    // Two equal principal moments
+    Symmetric {
+        nutation: f64,
+        spin0: f64,
+        spin_rate: f64,
+        precession_rate: f64,
+    },
+    Asymmetric {
+        polhode: Polhode,
+        period: f64,
+        precession_per_period: f64,
+    },
+}
+
+// Principal-frame angular momentum (A cn u, B sn u, C dn u), u = rate * t + u0
+struct Polhode {
+    amplitudes: nalgebra::Vector3<f64>,
+    rate: f64,
+    m: f64,
+    u0: f64,
+}
+
+impl Polhode {
+    fn ang_mom_at(&self, t: f64) -> nalgebra::Vector3<f64> {
+        let (sn, cn, dn) = jacobi_elliptic(self.rate * t + self.u0, self.m);
+        nalgebra::Vector3::new(
+            self.amplitudes.x * cn,
+            self.amplitudes.y * sn,
+            self.amplitudes.z * dn,
+        )
+    }
+
+    fn precession_rate_at(&self, moments: &nalgebra::Vector3<f64>, t: f64) -> f64 {
+        let l_p = self.ang_mom_at(t);
+        let l_perp_2 = l_p.x.powi(2) + l_p.y.powi(2);
+        l_p.norm() * (l_p.x.powi(2) / moments.x + l_p.y.powi(2) / moments.y) / l_perp_2
+    }
+}
+
+pub struct ReferenceError {
+    pub orientation: f64,
+    pub position: f64,
+}
+
+impl TorqueFreeSolution {
+    pub fn new(rigid_body: &physsim::RigidBody<f32>) -> Self {
+        let pos = rigid_body.pos.cast::<f64>();
+        let lin_vel = rigid_body.lin_vel.cast::<f64>();
+        let rot_mat = rigid_body.rot_mat.cast::<f64>();
+        let ang_mom = rigid_body.ang_mom.cast::<f64>();
+        let inv_ine = rigid_body.inv_ine.cast::<f64>();
+
+        let mut solution = Self {
+            pos,
+            lin_vel,
+            rot_mat,
+            ang_mom,
+            inv_ine,
+            l_frame: nalgebra::Matrix3::identity(),
+            principal_frame: nalgebra::Matrix3::identity(),
+            moments: nalgebra::Vector3::zeros(),
+            precession0: 0.0,
+            motion: Motion::Uniform {
+                omega: rot_mat * inv_ine * rot_mat.transpose() * ang_mom,
+            },
+        };
+
+        let ang_mom_norm = ang_mom.norm();
+        if ang_mom_norm == 0.0 {
+            return solution;
+        }
+
+        let (moments, axes) = dynamics::principal_axes(&inv_ine);
+        let equal = |a: f64, b: f64| (a - b).abs() <= MOMENT_TOLERANCE * a.abs().max(b.abs());
+        if equal(moments[0], moments[2]) {
+            // Spherical top
+            return solution;
+        }
+
+        let ang_mom_body = rot_mat.transpose() * ang_mom;
+        let ang_mom_principal = axes.transpose() * ang_mom_body;
+        let energy_2 = (0..3)
+            .map(|i| ang_mom_principal[i].powi(2) / moments[i])
+            .sum::<f64>();
+
+        let symmetric = equal(moments[0], moments[1]) || equal(moments[1], moments[2]);
+        let order = if equal(moments[1], moments[2])
+            || (!symmetric && ang_mom_norm.powi(2) < energy_2 * moments[1])
+        {
+            [2, 1, 0]
+        } else {
+            [0, 1, 2]
+        };
+
+        let mut principal_frame = nalgebra::Matrix3::from_columns(&[
+            axes.column(order[0]).into_owned(),
+            axes.column(order[1]).into_owned(),
+            axes.column(order[2]).into_owned(),
+        ]);
+        if principal_frame.determinant() < 0.0 {
+            principal_frame.set_column(0, &(-principal_frame.column(0)));
+        }
+        let moments =
+            nalgebra::Vector3::new(moments[order[0]], moments[order[1]], moments[order[2]]);
+        let l_p = principal_frame.transpose() * ang_mom_body;
+
+        if l_p.x.hypot(l_p.y) <= MOMENT_TOLERANCE * ang_mom_norm {
+            // Steady rotation about a principal axis
+            solution.motion = Motion::Uniform {
+                omega: ang_mom / moments.z,
+            };
+            return solution;
+        }
+
+        let l_frame = frame_along(&(ang_mom / ang_mom_norm));
+        let nutation0 = l_p.x.hypot(l_p.y).atan2(l_p.z);
+        let spin0 = l_p.x.atan2(l_p.y);
+
+        let euler0 = l_frame.transpose() * rot_mat * principal_frame;
+        let precession_mat = euler0 * (rot_x(nutation0) * rot_z(spin0)).transpose();
+        let precession0 = precession_mat[(1, 0)].atan2(precession_mat[(0, 0)]);
+
+        let motion = if symmetric {
+            Motion::Symmetric {
+                nutation: nutation0,
+                spin0,
+                spin_rate: l_p.z * (1.0 / moments.z - 1.0 / moments.x),
+                precession_rate: ang_mom_norm / moments.x,
+            }
+        } else {
+            let (i_a, i_b, i_c) = (moments.x, moments.y, moments.z);
+            let l_2 = ang_mom_norm.powi(2);
+
+            let amp_a = ((energy_2 * i_c - l_2) / (i_a * (i_c - i_a))).sqrt() * i_a;
+            let amp_b = ((energy_2 * i_c - l_2) / (i_b * (i_c - i_b))).sqrt() * i_b;
+            let amp_c = ((l_2 - energy_2 * i_a) / (i_c * (i_c - i_a))).sqrt() * i_c;
+            let rate = ((i_c - i_b) * (l_2 - energy_2 * i_a) / (i_a * i_b * i_c)).sqrt();
+            let m = ((i_b - i_a) * (energy_2 * i_c - l_2) / ((i_c - i_b) * (l_2 - energy_2 * i_a)))
+                .clamp(0.0, 1.0 - 1e-12);
+
+            // Signs follow from Euler's equations
+            let sign_c = l_p.z.signum();
+            let sign_b = sign_c * (i_c - i_b).signum();
+            let amplitudes = nalgebra::Vector3::new(amp_a, sign_b * amp_b, sign_c * amp_c);
+
+            let amplitude0 = (l_p.y / amplitudes.y).atan2(l_p.x / amplitudes.x);
+            let u0 = elliptic_f(amplitude0, m);
+            let period = 2.0 * elliptic_k(m) / rate;
+
+            let polhode = Polhode {
+                amplitudes,
+                rate,
+                m,
+                u0,
+            };
+            let precession_per_period = simpson(
+                |t| polhode.precession_rate_at(&moments, t),
+                0.0,
+                period,
+                PRECESSION_QUADRATURE_STEPS,
+            );
+
+            Motion::Asymmetric {
+                polhode,
+                period,
+                precession_per_period,
+            }
+        };
+
+        solution.l_frame = l_frame;
+        solution.principal_frame = principal_frame;
+        solution.moments = moments;
+        solution.precession0 = precession0;
+        solution.motion = motion;
+        solution
+    }
+
+    pub fn position_at(&self, t: f64) -> nalgebra::Vector3<f64> {
+        self.pos + self.lin_vel * t
+    }
+
+    pub fn orientation_at(&self, t: f64) -> nalgebra::Matrix3<f64> {
+        let (precession, nutation, spin) = match self.motion {
+            Motion::Uniform { omega } => {
+                return nalgebra::Rotation3::new(omega * t).matrix() * self.rot_mat;
+            }
+            Motion::Symmetric {
+                nutation,
+                spin0,
+                spin_rate,
+                precession_rate,
+            } => (
+                self.precession0 + precession_rate * t,
+                nutation,
+                spin0 + spin_rate * t,
+            ),
+            Motion::Asymmetric {
+                ref polhode,
+                period,
+                precession_per_period,
+            } => {
+                let l_p = polhode.ang_mom_at(t);
+
+                let periods = (t / period).floor();
+                let remainder = t - periods * period;
+                let steps = ((remainder / period) * PRECESSION_QUADRATURE_STEPS as f64).ceil();
+                let precession = self.precession0
+                    + periods * precession_per_period
+                    + simpson(
+                        |t| polhode.precession_rate_at(&self.moments, t),
+                        0.0,
+                        remainder,
+                        (steps as usize).max(16),
+                    );
+
+                (
+                    precession,
+                    l_p.x.hypot(l_p.y).atan2(l_p.z),
+                    l_p.x.atan2(l_p.y),
+                )
+            }
+        };
+
+        self.l_frame
+            * rot_z(precession)
+            * rot_x(nutation)
+            * rot_z(spin)
+            * self.principal_frame.transpose()
+    }
+
+    pub fn rigid_body_at(&self, t: f64) -> physsim::RigidBody<f32> {
+        physsim::RigidBody {
+            pos: self.position_at(t).cast::<f32>(),
+            lin_vel: self.lin_vel.cast::<f32>(),
+            rot_mat: self.orientation_at(t).cast::<f32>(),
+            ang_mom: self.ang_mom.cast::<f32>(),
+            inv_ine: self.inv_ine.cast::<f32>(),
+        }
+    }
+
+    pub fn error(&self, rigid_body: &physsim::RigidBody<f32>, t: f64) -> ReferenceError {
+        let rot_diff = self.orientation_at(t).transpose() * rigid_body.rot_mat.cast::<f64>();
+        let cos_angle = ((rot_diff.trace() - 1.0) / 2.0).clamp(-1.0, 1.0);
+
+        ReferenceError {
+            orientation: cos_angle.acos(),
+            position: (self.position_at(t) - rigid_body.pos.cast::<f64>()).norm(),
+        }
+    }
+}
+
+fn frame_along(dir: &nalgebra::Vector3<f64>) -> nalgebra::Matrix3<f64> {
+    let helper = if dir.x.abs() < 0.9 {
+        nalgebra::Vector3::x()
+    } else {
+        nalgebra::Vector3::y()
+    };
+    let x = helper.cross(dir).normalize();
+    let y = dir.cross(&x);
+    nalgebra::Matrix3::from_columns(&[x, y, *dir])
+}
+
+fn rot_x(angle: f64) -> nalgebra::Matrix3<f64> {
+    *nalgebra::Rotation3::from_axis_angle(&nalgebra::Vector3::x_axis(), angle).matrix()
+}
+
+fn rot_z(angle: f64) -> nalgebra::Matrix3<f64> {
+    *nalgebra::Rotation3::from_axis_angle(&nalgebra::Vector3::z_axis(), angle).matrix()
+}
+
+fn simpson(f: impl Fn(f64) -> f64, a: f64, b: f64, steps: usize) -> f64 {
+    let steps = steps + steps % 2;
+    let h = (b - a) / steps as f64;
+    let mut sum = f(a) + f(b);
+    for i in 1..steps {
+        let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+        sum += weight * f(a + h * i as f64);
+    }
+    sum * h / 3.0
+}
+
+// Jacobi elliptic functions sn, cn, dn with parameter m, by descending Landen
+// transformation
+fn jacobi_elliptic(u: f64, m: f64) -> (f64, f64, f64) {
+    if m < 1e-12 {
+        return (u.sin(), u.cos(), 1.0);
+    }
+
+    let mut a = vec![1.0];
+    let mut c = vec![m.sqrt()];
+    let mut b = (1.0 - m).sqrt();
+    while c.last().unwrap().abs() > 1e-15 && a.len() < 32 {
+        let a_n = *a.last().unwrap();
+        c.push((a_n - b) / 2.0);
+        a.push((a_n + b) / 2.0);
+        b = (a_n * b).sqrt();
+    }
+
+    let n = a.len() - 1;
+    let mut phi = 2f64.powi(n as i32) * a[n] * u;
+    for i in (1..=n).rev() {
+        phi = (phi + (c[i] / a[i] * phi.sin()).asin()) / 2.0;
+    }
+
+    let sn = phi.sin();
+    (sn, phi.cos(), (1.0 - m * sn * sn).sqrt())
+}
+
+fn elliptic_k(m: f64) -> f64 {
+    let mut a = 1.0;
+    let mut b = (1.0 - m).sqrt();
+    while (a - b).abs() > 1e-15 * a {
+        (a, b) = ((a + b) / 2.0, (a * b).sqrt());
+    }
+    std::f64::consts::PI / (2.0 * a)
+}
+
+// Incomplete elliptic integral of the first kind, valid for any amplitude
+fn elliptic_f(phi: f64, m: f64) -> f64 {
+    let n = (phi / std::f64::consts::PI).round();
+    let phi = phi - n * std::f64::consts::PI;
+    let (s, c) = phi.sin_cos();
+    2.0 * n * elliptic_k(m) + s * carlson_rf(c * c, 1.0 - m * s * s, 1.0)
+}
+
+fn carlson_rf(x: f64, y: f64, z: f64) -> f64 {
+    let (mut x, mut y, mut z) = (x, y, z);
+    loop {
+        let (sx, sy, sz) = (x.sqrt(), y.sqrt(), z.sqrt());
+        let lambda = sx * (sy + sz) + sy * sz;
+        x = 0.25 * (x + lambda);
+        y = 0.25 * (y + lambda);
+        z = 0.25 * (z + lambda);
+
+        let avg = (x + y + z) / 3.0;
+        let (dx, dy, dz) = ((avg - x) / avg, (avg - y) / avg, (avg - z) / avg);
+        if dx.abs().max(dy.abs()).max(dz.abs()) < 1e-4 {
+            let e2 = dx * dy - dz * dz;
+            let e3 = dx * dy * dz;
+            return (1.0 + (e2 / 24.0 - 0.1 - 3.0 / 44.0 * e3) * e2 + e3 / 14.0) / avg.sqrt();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} differs from {} by more than {}",
+            actual,
+            expected,
+            tolerance
+        );
+    }
+
+    #[test]
+    fn jacobi_elliptic_matches_known_values() {
+        let cases = [
+            (
+                0.5,
+                0.5,
+                0.470750473655657,
+                0.88226639489044,
+                0.942972425777386,
+            ),
+            (
+                1.2,
+                0.9,
+                0.845178268805035,
+                0.534484512347855,
+                0.597583738521851,
+            ),
+            (
+                2.0,
+                0.1,
+                0.93284463274404,
+                -0.360278907460091,
+                0.955499915811647,
+            ),
+        ];
+        for (u, m, sn, cn, dn) in cases {
+            let actual = jacobi_elliptic(u, m);
+            assert_close(actual.0, sn, 1e-12);
+            assert_close(actual.1, cn, 1e-12);
+            assert_close(actual.2, dn, 1e-12);
+        }
+        let (sn, cn, dn) = jacobi_elliptic(1.0, 0.0);
+        assert_eq!((sn, cn, dn), (1f64.sin(), 1f64.cos(), 1.0));
+    }
+
+    #[test]
+    fn elliptic_integrals_match_known_values() {
+        assert_close(elliptic_k(0.0), std::f64::consts::FRAC_PI_2, 1e-15);
+        assert_close(elliptic_k(0.5), 1.85407467730137, 1e-13);
+        assert_close(elliptic_k(0.9), 2.57809211334817, 1e-13);
+        assert_close(elliptic_k(0.99), 3.69563736298987, 1e-13);
+        assert_close(elliptic_f(1.0, 0.5), 1.08321677284517, 1e-12);
+        // Past the first half period
+        assert_close(elliptic_f(4.0, 0.5), 4.61952061625711, 1e-12);
+        // sn reaches 1 at a quarter period
+        assert_close(jacobi_elliptic(elliptic_k(0.7), 0.7).0, 1.0, 1e-12);
+    }
+
+    // Torque-free rotation by classic Runge-Kutta with a fine step
+    fn integrate(rigid_body: &physsim::RigidBody<f32>, t: f64) -> nalgebra::Matrix3<f64> {
+        const STEP: f64 = 1e-3;
+        let ang_mom = rigid_body.ang_mom.cast::<f64>();
+        let inv_ine = rigid_body.inv_ine.cast::<f64>();
+        let derivative = |rot_mat: &nalgebra::Matrix3<f64>| {
+            let omega = rot_mat * inv_ine * rot_mat.transpose() * ang_mom;
+            omega.cross_matrix() * rot_mat
+        };
+
+        let mut rot_mat = rigid_body.rot_mat.cast::<f64>();
+        for _ in 0..(t / STEP).round() as usize {
+            let k1 = derivative(&rot_mat);
+            let k2 = derivative(&(rot_mat + k1 * (STEP / 2.0)));
+            let k3 = derivative(&(rot_mat + k2 * (STEP / 2.0)));
+            let k4 = derivative(&(rot_mat + k3 * STEP));
+            rot_mat += (k1 + k2 * 2.0 + k3 * 2.0 + k4) * (STEP / 6.0);
+        }
+        rot_mat
+    }
+
+    fn assert_follows_integration(moments: [f32; 3], ang_mom: [f32; 3]) {
+        let rigid_body = physsim::RigidBody {
+            pos: nalgebra::Vector3::new(1.0, 2.0, 3.0),
+            lin_vel: nalgebra::Vector3::new(0.5, 0.0, -0.25),
+            rot_mat: *nalgebra::Rotation3::from_euler_angles(0.3, -0.7, 1.1).matrix(),
+            ang_mom: ang_mom.into(),
+            inv_ine: nalgebra::Matrix3::from_diagonal(
+                &nalgebra::Vector3::from(moments).map(|moment| 1.0 / moment),
+            ),
+        };
+        let solution = TorqueFreeSolution::new(&rigid_body);
+        for t in [0.0, 1.0, 2.5, 7.0] {
+            let expected = integrate(&rigid_body, t);
+            let rot_diff = solution.orientation_at(t).transpose() * expected;
+            let angle = ((rot_diff.trace() - 1.0) / 2.0).clamp(-1.0, 1.0).acos();
+            assert!(
+                angle < 1e-6,
+                "moments {:?}, ang_mom {:?}: off by {} rad at t = {}",
+                moments,
+                ang_mom,
+                angle,
+                t
+            );
+        }
+        assert_close(solution.position_at(7.0).x, 4.5, 1e-12);
+    }
+
+    #[test]
+    fn symmetric_tops_follow_integration() {
+        assert_follows_integration([1.0, 1.0, 2.0], [0.3, -0.8, 1.5]);
+        assert_follows_integration([2.0, 0.5, 2.0], [1.0, 0.2, -0.4]);
+    }
+
+    #[test]
+    fn asymmetric_tops_follow_integration() {
+        // Circling the axes of least and of largest inertia
+        assert_follows_integration([1.0, 2.0, 3.0], [1.2, 0.3, 0.2]);
+        assert_follows_integration([1.0, 2.0, 3.0], [0.2, 0.5, 1.4]);
+        assert_follows_integration([3.0, 1.5, 0.8], [-0.4, 0.9, 0.6]);
+    }
+}
@@ -0,0 +1,190 @@
+// Headless runner for batch jobs. Steps a scene exactly like the visualizer's
+// physics tick and writes the recorded trajectory or a conserved-quantity
+// report.
+
+use physsim_viz_rust::{dynamics, recorder, scene};
+
+const USAGE: &str = "\
+usage: physsim-batch <scene> --steps <n> [options]
+
+options:
+    --steps <n>          number of physics ticks to run
+    --decimation <n>     write (and check the drift of) every n-th tick (default 1)
+    --trajectory <fmt>   write body trajectories as csv or jsonl (default csv)
+    --report             write total energy, momentum and angular momentum instead
+    --output <file>      write to a file instead of stdout
+    --help               show this message";
+
+enum Output {
+    Trajectory(recorder::ExportFormat),
+    Report,
+}
+
+struct Args {
+    scene: String,
+    steps: u64,
+    decimation: u32,
+    output: Output,
+    output_path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut scene = None;
+    let mut steps = None;
+    let mut decimation = 1;
+    let mut output = Output::Trajectory(recorder::ExportFormat::Csv);
+    let mut output_path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--steps" => {
+                let text = value("--steps")?;
+                steps = Some(
+                    text.parse::<u64>()
+                        .map_err(|_| format!("invalid step count {:?}", text))?,
+                );
+            }
+            "--decimation" => {
+                let text = value("--decimation")?;
+                decimation = match text.parse::<u32>() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(format!("invalid decimation {:?}", text)),
+                };
+            }
+            "--trajectory" => {
+                let text = value("--trajectory")?;
+                let format = recorder::ExportFormat::from_name(&text)
+                    .ok_or_else(|| format!("unknown trajectory format {:?}", text))?;
+                output = Output::Trajectory(format);
+            }
+            "--report" => output = Output::Report,
+            "--output" | "-o" => output_path = Some(value("--output")?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if scene.is_none() => scene = Some(arg),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+
+    Ok(Args {
+        scene: scene.ok_or("missing scene file")?,
+        steps: steps.ok_or("missing --steps")?,
+        decimation,
+        output,
+        output_path,
+    })
+}
+
+const REPORT_HEADER: &str = "time,energy,momentum_x,momentum_y,momentum_z,\
+    ang_mom_x,ang_mom_y,ang_mom_z";
+
+fn report_row(time: f64, totals: &dynamics::Totals) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{}\n",
+        time,
+        totals.energy,
+        totals.momentum.x,
+        totals.momentum.y,
+        totals.momentum.z,
+        totals.ang_mom.x,
+        totals.ang_mom.y,
+        totals.ang_mom.z,
+    )
+}
+
+// Change of a quantity relative to its initial magnitude, absolute if that is
+// zero
+fn drift(initial: f64, change: f64) -> f64 {
+    if initial > f64::EPSILON {
+        change / initial
+    } else {
+        change
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let text = std::fs::read_to_string(&args.scene)
+        .map_err(|err| format!("couldn't read {}: {}", args.scene, err))?;
+    let scene = scene::Scene::parse(&text).map_err(|err| format!("{}: {}", args.scene, err))?;
+    let mut simulation = scene
+        .build_simulation()
+        .map_err(|err| format!("{}: {}", args.scene, err))?;
+
+    let text = match args.output {
+        Output::Trajectory(format) => {
+            let mut recorder = recorder::Recorder::new();
+            recorder.start(args.decimation, &simulation);
+            for _ in 0..args.steps {
+                simulation.step();
+                recorder.record(&simulation);
+            }
+            recorder.export(format)
+        }
+        Output::Report => {
+            let initial = simulation.totals();
+            let mut max_energy_drift: f64 = 0.0;
+            let mut max_ang_mom_drift: f64 = 0.0;
+            let mut text = String::from(REPORT_HEADER);
+            text.push('\n');
+            text += &report_row(simulation.time(), &initial);
+            for _ in 0..args.steps {
+                simulation.step();
+                // Only the written ticks, the mutual gravity's potential is
+                // quadratic in the number of bodies
+                if !simulation.counter.is_multiple_of(args.decimation as u64) {
+                    continue;
+                }
+                let totals = simulation.totals();
+                max_energy_drift = max_energy_drift.max(drift(
+                    initial.energy.abs(),
+                    (totals.energy - initial.energy).abs(),
+                ));
+                max_ang_mom_drift = max_ang_mom_drift.max(drift(
+                    initial.ang_mom.norm(),
+                    (totals.ang_mom - initial.ang_mom).norm(),
+                ));
+                text += &report_row(simulation.time(), &totals);
+            }
+            eprintln!("max relative energy drift: {:e}", max_energy_drift);
+            eprintln!(
+                "max relative angular momentum drift: {:e}",
+                max_ang_mom_drift
+            );
+            text
+        }
+    };
+
+    match &args.output_path {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|err| format!("couldn't write {}: {}", path, err))?
+        }
+        None => {
+            use std::io::Write;
+            std::io::stdout()
+                .write_all(text.as_bytes())
+                .map_err(|err| err.to_string())?
+        }
+    }
+
+    // Comparable with `Runner.stateHashAt` in the browser
+    eprintln!(
+        "tick {} state hash {:016x}",
+        simulation.counter,
+        simulation.state_hash()
+    );
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let result = parse_args(args.into_iter()).and_then(|args| run(&args));
+    if let Err(err) = result {
+        eprintln!("error: {}\n\n{}", err, USAGE);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,79 @@
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Shape {
+    Cuboid { half_extents: [f32; 3] },
+    Sphere { radius: f32 },
+}
+
+impl Shape {
+    // Body-frame inertia tensor of a homogeneous solid of the given mass
+    pub fn inertia(&self, mass: f32) -> nalgebra::Matrix3<f32> {
+        match *self {
+            Shape::Cuboid {
+                half_extents: [a, b, c],
+            } => nalgebra::Matrix3::from_diagonal(&nalgebra::Vector3::new(
+                mass * (b * b + c * c) / 3.0,
+                mass * (a * a + c * c) / 3.0,
+                mass * (a * a + b * b) / 3.0,
+            )),
+            Shape::Sphere { radius } => {
+                nalgebra::Matrix3::identity() * (0.4 * mass * radius * radius)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Material {
+    // Ratio of separating to approaching normal speed, 0 to 1
+    pub restitution: f32,
+    // Coulomb friction coefficient
+    pub friction: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            restitution: 0.5,
+            friction: 0.5,
+        }
+    }
+}
+
+impl Material {
+    // Coefficients for a contact between two materials
+    pub fn combine(&self, other: &Material) -> Material {
+        Material {
+            restitution: self.restitution.max(other.restitution),
+            friction: (self.friction * other.friction).sqrt(),
+        }
+    }
+}
+
+pub struct Body {
+    pub name: String,
+    pub shape: Shape,
+    pub mass: f32,
+    pub material: Material,
+    pub rigid_body: physsim::RigidBody<f32>,
+}
+
+// `physsim::RigidBody` isn't `Clone`, but all of its fields are plain values
+impl Clone for Body {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            shape: self.shape,
+            mass: self.mass,
+            material: self.material,
+            rigid_body: physsim::RigidBody {
+                pos: self.rigid_body.pos,
+                lin_vel: self.rigid_body.lin_vel,
+                rot_mat: self.rigid_body.rot_mat,
+                ang_mom: self.rigid_body.ang_mom,
+                inv_ine: self.rigid_body.inv_ine,
+            },
+        }
+    }
+}
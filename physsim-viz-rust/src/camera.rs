@@ -0,0 +1,130 @@
+const FOVY: f32 = 75.0 * std::f32::consts::PI / 180.0;
+const Z_NEAR: f32 = 0.01;
+const Z_FAR: f32 = 1000.0;
+
+// Maps world coordinates to clip space for a camera at `pos` looking down its
+// local -z axis
+pub fn view_projection(
+    pos: &nalgebra::Vector3<f32>,
+    rot: &nalgebra::Rotation3<f32>,
+    aspect: f32,
+) -> nalgebra::Matrix4<f32> {
+    //let tan_half_fovy = (fovy / 2.0).tan();
+
+    //let mut proj_mat = nalgebra::Matrix4::<f32>::identity();
+    //proj_mat.data.0[0][0] = 1.0 / (aspect * tan_half_fovy);
+    //proj_mat.data.0[1][1] = 1.0 / tan_half_fovy;
+    //proj_mat.data.0[2][2] = z_far / (z_far - z_near);
+    //proj_mat.data.0[2][3] = 1.0;
+    //proj_mat.data.0[3][2] = -(z_far * z_near) / (z_far - z_near);
+
+    let persp = nalgebra::Perspective3::new(aspect, FOVY, Z_NEAR, Z_FAR);
+    let translation = nalgebra::Translation3::<f32>::from(*pos);
+    persp.as_matrix()
+        * (translation.to_homogeneous() * rot.to_homogeneous())
+            .try_inverse()
+            .unwrap()
+}
+
+// Moves the camera by `delta` given in its own frame
+pub fn move_local(
+    pos: &mut nalgebra::Vector3<f32>,
+    rot: &nalgebra::Rotation3<f32>,
+    delta: &nalgebra::Vector3<f32>,
+) {
+    *pos += rot * delta;
+}
+
+// Turns the camera by the rotation vector `axis_angle` given in its own frame
+pub fn turn_local(rot: &mut nalgebra::Rotation3<f32>, axis_angle: &nalgebra::Vector3<f32>) {
+    *rot *= nalgebra::Rotation3::new(*axis_angle);
+}
+
+// World-space ray from the camera through the point `ndc` of the viewport in
+// normalized device coordinates, as origin and unit direction
+pub fn ray(
+    pos: &nalgebra::Vector3<f32>,
+    rot: &nalgebra::Rotation3<f32>,
+    aspect: f32,
+    ndc: &nalgebra::Vector2<f32>,
+) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+    let inverse = view_projection(pos, rot, aspect).try_inverse().unwrap();
+    let unproject = |z: f32| {
+        let p = inverse * nalgebra::Vector4::new(ndc.x, ndc.y, z, 1.0);
+        p.xyz() / p.w
+    };
+    (*pos, (unproject(1.0) - unproject(-1.0)).normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &nalgebra::Vector3<f32>, b: &nalgebra::Vector3<f32>) {
+        assert!((a - b).norm() < 1e-4, "{} differs from {}", a, b);
+    }
+
+    fn camera() -> (nalgebra::Vector3<f32>, nalgebra::Rotation3<f32>) {
+        (
+            nalgebra::Vector3::new(1.0, 2.0, 3.0),
+            nalgebra::Rotation3::from_euler_angles(0.2, -0.4, 0.1),
+        )
+    }
+
+    #[test]
+    fn projects_what_is_ahead_to_the_center() {
+        let (pos, rot) = camera();
+        let view_projection = view_projection(&pos, &rot, 1.333);
+        let project = |p: nalgebra::Vector3<f32>| {
+            let clip = view_projection * p.push(1.0);
+            clip.xyz() / clip.w
+        };
+
+        let ahead = project(pos + rot * nalgebra::Vector3::new(0.0, 0.0, -5.0));
+        assert!(ahead.xy().norm() < 1e-5, "{}", ahead);
+        assert!(ahead.z > -1.0 && ahead.z < 1.0);
+        // Up and right in the camera's frame are up and right on screen
+        let up_right = project(pos + rot * nalgebra::Vector3::new(1.0, 1.0, -5.0));
+        assert!(up_right.x > 0.0 && up_right.y > 0.0, "{}", up_right);
+        // Nearer points have smaller depth
+        assert!(project(pos + rot * nalgebra::Vector3::new(0.0, 0.0, -1.0)).z < ahead.z);
+    }
+
+    #[test]
+    fn rays_pass_through_what_they_point_at() {
+        let (pos, rot) = camera();
+        let (origin, direction) = ray(&pos, &rot, 1.333, &nalgebra::Vector2::zeros());
+        assert_close(&origin, &pos);
+        assert_close(&direction, &(rot * -nalgebra::Vector3::z()));
+
+        let target = pos + rot * nalgebra::Vector3::new(0.7, -0.3, -4.0);
+        let clip = view_projection(&pos, &rot, 1.333) * target.push(1.0);
+        let (origin, direction) = ray(&pos, &rot, 1.333, &(clip.xy() / clip.w));
+        assert_close(&direction, &(target - origin).normalize());
+    }
+
+    #[test]
+    fn moves_and_turns_in_the_camera_frame() {
+        let rot = nalgebra::Rotation3::from_axis_angle(
+            &nalgebra::Vector3::y_axis(),
+            std::f32::consts::FRAC_PI_2,
+        );
+        let mut pos = nalgebra::Vector3::new(1.0, 0.0, 0.0);
+        // Forward is -x after a quarter turn to the left
+        move_local(&mut pos, &rot, &nalgebra::Vector3::new(0.0, 0.0, -1.0));
+        assert_close(&pos, &nalgebra::Vector3::new(0.0, 0.0, 0.0));
+
+        let mut turned = rot;
+        turn_local(&mut turned, &nalgebra::Vector3::new(0.3, 0.0, 0.0));
+        let expected = rot * nalgebra::Rotation3::new(nalgebra::Vector3::new(0.3, 0.0, 0.0));
+        assert!((turned.matrix() - expected.matrix()).norm() < 1e-6);
+
+        // Turns don't commute, so the order they are applied in matters
+        let (mut a, mut b) = (rot, rot);
+        turn_local(&mut a, &nalgebra::Vector3::new(0.5, 0.0, 0.0));
+        turn_local(&mut a, &nalgebra::Vector3::new(0.0, 0.5, 0.0));
+        turn_local(&mut b, &nalgebra::Vector3::new(0.0, 0.5, 0.0));
+        turn_local(&mut b, &nalgebra::Vector3::new(0.5, 0.0, 0.0));
+        assert!((a.matrix() - b.matrix()).norm() > 1e-2);
+    }
+}
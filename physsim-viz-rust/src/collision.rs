@@ -0,0 +1,504 @@
+use crate::body;
+use crate::contact;
+
+// Edge-edge axes only win over face axes when clearly better, otherwise
+// resting boxes flicker between contact manifolds
+const EDGE_AXIS_BIAS: f32 = 0.95;
+// Slack when clipping the incident face to the reference face
+const FACE_TOLERANCE: f32 = 1e-3;
+
+// World-frame axis aligned bounds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: nalgebra::Vector3<f32>,
+    pub max: nalgebra::Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn of(body: &body::Body) -> Self {
+        let rigid_body = &body.rigid_body;
+        let extent = match body.shape {
+            body::Shape::Cuboid { half_extents } => {
+                rigid_body.rot_mat.abs() * nalgebra::Vector3::from(half_extents)
+            }
+            body::Shape::Sphere { radius } => nalgebra::Vector3::repeat(radius),
+        };
+        Self {
+            min: rigid_body.pos - extent,
+            max: rigid_body.pos + extent,
+        }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+}
+
+// Sweep and prune along x. Pairs come out with the lower index first and in a
+// fixed order, which keeps the solver deterministic.
+pub fn broadphase(bodies: &[body::Body]) -> Vec<(usize, usize)> {
+    let bounds: Vec<Aabb> = bodies.iter().map(Aabb::of).collect();
+    let mut order: Vec<usize> = (0..bodies.len()).collect();
+    order.sort_by(|&i, &j| bounds[i].min.x.total_cmp(&bounds[j].min.x).then(i.cmp(&j)));
+
+    let mut pairs = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+    for &i in order.iter() {
+        active.retain(|&j| bounds[j].max.x >= bounds[i].min.x);
+        for &j in active.iter() {
+            if bounds[i].overlaps(&bounds[j]) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+        active.push(i);
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+pub fn detect(bodies: &[body::Body]) -> Vec<contact::Contact> {
+    let mut contacts = Vec::new();
+    for (a, b) in broadphase(bodies) {
+        let material = bodies[a].material.combine(&bodies[b].material);
+        for (point, normal, depth) in narrowphase(&bodies[a], &bodies[b]) {
+            contacts.push(contact::Contact {
+                body: a,
+                other: Some(b),
+                point,
+                normal,
+                depth,
+                material,
+                impulse: nalgebra::Vector3::zeros(),
+            });
+        }
+    }
+    contacts
+}
+
+// Contact points with normals pointing from `b` to `a` and penetration depths
+type Manifold = Vec<(nalgebra::Vector3<f32>, nalgebra::Vector3<f32>, f32)>;
+
+fn narrowphase(a: &body::Body, b: &body::Body) -> Manifold {
+    let (pa, pb) = (&a.rigid_body, &b.rigid_body);
+    match (a.shape, b.shape) {
+        (body::Shape::Sphere { radius: ra }, body::Shape::Sphere { radius: rb }) => {
+            sphere_sphere(&pa.pos, ra, &pb.pos, rb)
+        }
+        (body::Shape::Sphere { radius }, body::Shape::Cuboid { half_extents }) => {
+            sphere_box(&pa.pos, radius, &Obb::new(pb, &half_extents))
+        }
+        (body::Shape::Cuboid { half_extents }, body::Shape::Sphere { radius }) => {
+            sphere_box(&pb.pos, radius, &Obb::new(pa, &half_extents))
+                .into_iter()
+                .map(|(point, normal, depth)| (point, -normal, depth))
+                .collect()
+        }
+        (body::Shape::Cuboid { half_extents: ha }, body::Shape::Cuboid { half_extents: hb }) => {
+            box_box(&Obb::new(pa, &ha), &Obb::new(pb, &hb))
+        }
+    }
+}
+
+fn sphere_sphere(
+    ca: &nalgebra::Vector3<f32>,
+    ra: f32,
+    cb: &nalgebra::Vector3<f32>,
+    rb: f32,
+) -> Manifold {
+    let d = ca - cb;
+    let distance = d.norm();
+    let depth = ra + rb - distance;
+    if depth <= 0.0 {
+        return Vec::new();
+    }
+    let normal = if distance > f32::EPSILON {
+        d / distance
+    } else {
+        nalgebra::Vector3::y()
+    };
+    vec![(cb + normal * (rb - depth / 2.0), normal, depth)]
+}
+
+struct Obb {
+    center: nalgebra::Vector3<f32>,
+    // World-frame box axes as columns
+    axes: nalgebra::Matrix3<f32>,
+    half: nalgebra::Vector3<f32>,
+}
+
+impl Obb {
+    fn new(rigid_body: &physsim::RigidBody<f32>, half_extents: &[f32; 3]) -> Self {
+        Self {
+            center: rigid_body.pos,
+            axes: rigid_body.rot_mat,
+            half: nalgebra::Vector3::from(*half_extents),
+        }
+    }
+
+    fn axis(&self, i: usize) -> nalgebra::Vector3<f32> {
+        self.axes.column(i).into_owned()
+    }
+
+    // Half the length of the box's projection onto `direction`
+    fn radius_along(&self, direction: &nalgebra::Vector3<f32>) -> f32 {
+        (0..3)
+            .map(|i| self.half[i] * self.axis(i).dot(direction).abs())
+            .sum()
+    }
+
+    fn to_local(&self, p: &nalgebra::Vector3<f32>) -> nalgebra::Vector3<f32> {
+        self.axes.transpose() * (p - self.center)
+    }
+}
+
+// Normal points from the box to the sphere
+fn sphere_box(center: &nalgebra::Vector3<f32>, radius: f32, obb: &Obb) -> Manifold {
+    let local = obb.to_local(center);
+    let clamped = local.zip_map(&obb.half, |x, h| x.clamp(-h, h));
+
+    if clamped != local {
+        let d = local - clamped;
+        let distance = d.norm();
+        if distance >= radius {
+            return Vec::new();
+        }
+        let normal = obb.axes * (d / distance);
+        return vec![(obb.center + obb.axes * clamped, normal, radius - distance)];
+    }
+
+    // Center inside the box, leave through the nearest face
+    let (axis, gap) = (0..3)
+        .map(|i| (i, obb.half[i] - local[i].abs()))
+        .min_by(|x, y| x.1.total_cmp(&y.1))
+        .unwrap();
+    let sign = if local[axis] < 0.0 { -1.0 } else { 1.0 };
+    let normal = obb.axis(axis) * sign;
+    let mut surface = local;
+    surface[axis] = obb.half[axis] * sign;
+    vec![(obb.center + obb.axes * surface, normal, radius + gap)]
+}
+
+enum Axis {
+    FaceA(usize),
+    FaceB(usize),
+    Edge(usize, usize),
+}
+
+// Separating axis test over the 15 candidate axes; the contacts come from the
+// axis of least penetration
+fn box_box(a: &Obb, b: &Obb) -> Manifold {
+    let t = b.center - a.center;
+
+    let mut best: Option<(Axis, nalgebra::Vector3<f32>, f32)> = None;
+    let mut candidates = Vec::with_capacity(15);
+    for i in 0..3 {
+        candidates.push((Axis::FaceA(i), a.axis(i)));
+    }
+    for j in 0..3 {
+        candidates.push((Axis::FaceB(j), b.axis(j)));
+    }
+    for i in 0..3 {
+        for j in 0..3 {
+            let cross = a.axis(i).cross(&b.axis(j));
+            // Parallel edges are covered by the face axes
+            if cross.norm_squared() > 1e-6 {
+                candidates.push((Axis::Edge(i, j), cross.normalize()));
+            }
+        }
+    }
+
+    for (axis, direction) in candidates {
+        let overlap =
+            a.radius_along(&direction) + b.radius_along(&direction) - t.dot(&direction).abs();
+        if overlap <= 0.0 {
+            return Vec::new();
+        }
+        let better = match (&best, &axis) {
+            (None, _) => true,
+            (Some((_, _, best_overlap)), Axis::Edge(..)) => overlap < best_overlap * EDGE_AXIS_BIAS,
+            (Some((_, _, best_overlap)), _) => overlap < *best_overlap,
+        };
+        if better {
+            // Pointing from a to b
+            let direction = if t.dot(&direction) < 0.0 {
+                -direction
+            } else {
+                direction
+            };
+            best = Some((axis, direction, overlap));
+        }
+    }
+
+    let (axis, n, depth) = best.unwrap();
+    match axis {
+        Axis::FaceA(i) => face_contacts(a, i, &n, b)
+            .into_iter()
+            .map(|(point, depth)| (point, -n, depth))
+            .collect(),
+        Axis::FaceB(j) => face_contacts(b, j, &-n, a)
+            .into_iter()
+            .map(|(point, depth)| (point, -n, depth))
+            .collect(),
+        Axis::Edge(i, j) => {
+            // The edges of both boxes closest to the other box
+            let support = |obb: &Obb, skip: usize, direction: &nalgebra::Vector3<f32>| {
+                let mut p = obb.center;
+                for k in (0..3).filter(|k| *k != skip) {
+                    let axis = obb.axis(k);
+                    p += axis * obb.half[k] * axis.dot(direction).signum();
+                }
+                p
+            };
+            let pa = support(a, i, &n);
+            let pb = support(b, j, &-n);
+            let (da, db) = (a.axis(i), b.axis(j));
+
+            // Closest points of the two edge lines
+            let r = pa - pb;
+            let (d1, d2, e) = (da.dot(&db), da.dot(&r), db.dot(&r));
+            let denominator = 1.0 - d1 * d1;
+            let (s, u) = if denominator > 1e-6 {
+                let s = (d1 * e - d2) / denominator;
+                (s, d1 * s + e)
+            } else {
+                (0.0, e)
+            };
+            let s = s.clamp(-a.half[i], a.half[i]);
+            let u = u.clamp(-b.half[j], b.half[j]);
+            let point = (pa + da * s + pb + db * u) / 2.0;
+            vec![(point, -n, depth)]
+        }
+    }
+}
+
+// Clips the face of `incident` that faces the reference face against the
+// reference face's side planes, keeping the points behind the reference face
+fn face_contacts(
+    reference: &Obb,
+    axis: usize,
+    normal: &nalgebra::Vector3<f32>,
+    incident: &Obb,
+) -> Vec<(nalgebra::Vector3<f32>, f32)> {
+    // The incident face is the one most opposed to the reference normal
+    let k = (0..3)
+        .max_by(|&x, &y| {
+            let dx = incident.axis(x).dot(normal).abs();
+            let dy = incident.axis(y).dot(normal).abs();
+            dx.total_cmp(&dy)
+        })
+        .unwrap();
+    let face_normal = incident.axis(k) * -incident.axis(k).dot(normal).signum();
+    let face_center = incident.center + face_normal * incident.half[k];
+    let (u, v) = {
+        let others: Vec<usize> = (0..3).filter(|x| *x != k).collect();
+        (
+            incident.axis(others[0]) * incident.half[others[0]],
+            incident.axis(others[1]) * incident.half[others[1]],
+        )
+    };
+    let mut polygon = vec![
+        face_center + u + v,
+        face_center - u + v,
+        face_center - u - v,
+        face_center + u - v,
+    ];
+
+    for side in (0..3).filter(|x| *x != axis) {
+        let side_axis = reference.axis(side);
+        let limit = reference.half[side] + FACE_TOLERANCE;
+        for sign in [1.0, -1.0] {
+            // Inside where the distance is non-negative
+            let distance =
+                |p: &nalgebra::Vector3<f32>| limit - sign * side_axis.dot(&(p - reference.center));
+            polygon = clip(&polygon, distance);
+        }
+    }
+
+    let face = normal.dot(&reference.center) + reference.half[axis];
+    polygon
+        .into_iter()
+        .filter_map(|p| {
+            let depth = face - normal.dot(&p);
+            if depth > 0.0 {
+                Some((p + normal * (depth / 2.0), depth))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Sutherland-Hodgman against a single plane
+fn clip(
+    polygon: &[nalgebra::Vector3<f32>],
+    distance: impl Fn(&nalgebra::Vector3<f32>) -> f32,
+) -> Vec<nalgebra::Vector3<f32>> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, p) in polygon.iter().enumerate() {
+        let q = &polygon[(i + 1) % polygon.len()];
+        let (dp, dq) = (distance(p), distance(q));
+        if dp >= 0.0 {
+            clipped.push(*p);
+        }
+        if (dp >= 0.0) != (dq >= 0.0) {
+            clipped.push(p + (q - p) * (dp / (dp - dq)));
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(shape: body::Shape, pos: [f32; 3], rot: nalgebra::Rotation3<f32>) -> body::Body {
+        body::Body {
+            name: String::new(),
+            shape,
+            mass: 1.0,
+            material: body::Material::default(),
+            rigid_body: physsim::RigidBody {
+                pos: pos.into(),
+                lin_vel: nalgebra::Vector3::zeros(),
+                rot_mat: *rot.matrix(),
+                ang_mom: nalgebra::Vector3::zeros(),
+                inv_ine: nalgebra::Matrix3::identity(),
+            },
+        }
+    }
+
+    fn sphere(radius: f32, pos: [f32; 3]) -> body::Body {
+        body(
+            body::Shape::Sphere { radius },
+            pos,
+            nalgebra::Rotation3::identity(),
+        )
+    }
+
+    fn cuboid(pos: [f32; 3], rot: nalgebra::Rotation3<f32>) -> body::Body {
+        body(
+            body::Shape::Cuboid {
+                half_extents: [1.0, 1.0, 1.0],
+            },
+            pos,
+            rot,
+        )
+    }
+
+    fn assert_manifold(
+        manifold: &Manifold,
+        points: usize,
+        normal: nalgebra::Vector3<f32>,
+        depth: f32,
+    ) {
+        assert_eq!(manifold.len(), points, "{:?}", manifold);
+        for (_, n, d) in manifold {
+            assert!(
+                (n - normal).norm() < 1e-4,
+                "normal {} instead of {}",
+                n,
+                normal
+            );
+            assert!((d - depth).abs() < 1e-4, "depth {} instead of {}", d, depth);
+        }
+    }
+
+    #[test]
+    fn sphere_sphere_contacts() {
+        let manifold = narrowphase(&sphere(1.0, [0.0; 3]), &sphere(1.0, [1.5, 0.0, 0.0]));
+        assert_manifold(&manifold, 1, -nalgebra::Vector3::x(), 0.5);
+        assert!((manifold[0].0 - nalgebra::Vector3::new(0.75, 0.0, 0.0)).norm() < 1e-5);
+
+        assert!(narrowphase(&sphere(1.0, [0.0; 3]), &sphere(0.5, [0.0, 1.6, 0.0])).is_empty());
+    }
+
+    #[test]
+    fn sphere_box_contacts() {
+        let (ball, block) = (
+            sphere(0.5, [0.2, 1.4, -0.3]),
+            cuboid([0.0; 3], nalgebra::Rotation3::identity()),
+        );
+        let manifold = narrowphase(&ball, &block);
+        assert_manifold(&manifold, 1, nalgebra::Vector3::y(), 0.1);
+        assert!((manifold[0].0 - nalgebra::Vector3::new(0.2, 1.0, -0.3)).norm() < 1e-5);
+        // Either order, the normal points towards the first body
+        assert_manifold(&narrowphase(&block, &ball), 1, -nalgebra::Vector3::y(), 0.1);
+
+        // Center just inside, pushed out through the nearest face
+        let ball = sphere(0.5, [0.0, 0.0, -0.9]);
+        assert_manifold(&narrowphase(&ball, &block), 1, -nalgebra::Vector3::z(), 0.6);
+
+        // Off a corner of a turned box
+        let turned = cuboid(
+            [0.0; 3],
+            nalgebra::Rotation3::from_axis_angle(
+                &nalgebra::Vector3::y_axis(),
+                std::f32::consts::FRAC_PI_4,
+            ),
+        );
+        let ball = sphere(0.5, [1.5, 0.0, 0.0]);
+        let corner = std::f32::consts::SQRT_2;
+        assert_manifold(
+            &narrowphase(&ball, &turned),
+            1,
+            nalgebra::Vector3::x(),
+            corner - 1.0,
+        );
+
+        assert!(narrowphase(&sphere(0.5, [1.4, 1.4, 0.0]), &block).is_empty());
+    }
+
+    #[test]
+    fn box_box_contacts() {
+        let below = cuboid([0.0; 3], nalgebra::Rotation3::identity());
+
+        // Face on face, one point per corner of the overlap
+        let above = cuboid([0.3, 1.9, 0.2], nalgebra::Rotation3::identity());
+        let manifold = narrowphase(&above, &below);
+        assert_manifold(&manifold, 4, nalgebra::Vector3::y(), 0.1);
+        assert!(manifold.iter().all(|(p, _, _)| (p.y - 0.95).abs() < 1e-4));
+        assert_manifold(
+            &narrowphase(&below, &above),
+            4,
+            -nalgebra::Vector3::y(),
+            0.1,
+        );
+
+        // Turned about the normal, the incident face gets clipped to an octagon
+        let turned = cuboid(
+            [0.0, 1.9, 0.0],
+            nalgebra::Rotation3::from_axis_angle(
+                &nalgebra::Vector3::y_axis(),
+                std::f32::consts::FRAC_PI_4,
+            ),
+        );
+        assert_manifold(
+            &narrowphase(&turned, &below),
+            8,
+            nalgebra::Vector3::y(),
+            0.1,
+        );
+
+        // Crossed edges meet in a single point
+        let quarter = std::f32::consts::FRAC_PI_4;
+        let ridge = cuboid(
+            [0.0; 3],
+            nalgebra::Rotation3::from_axis_angle(&nalgebra::Vector3::z_axis(), quarter),
+        );
+        let wedge = cuboid(
+            [0.0, 2.0 * std::f32::consts::SQRT_2 - 0.1, 0.0],
+            nalgebra::Rotation3::from_axis_angle(&nalgebra::Vector3::x_axis(), quarter),
+        );
+        let manifold = narrowphase(&wedge, &ridge);
+        assert_manifold(&manifold, 1, nalgebra::Vector3::y(), 0.1);
+        let expected = nalgebra::Vector3::new(0.0, std::f32::consts::SQRT_2 - 0.05, 0.0);
+        assert!(
+            (manifold[0].0 - expected).norm() < 1e-4,
+            "{}",
+            manifold[0].0
+        );
+
+        let apart = cuboid([2.1, 0.0, 0.0], nalgebra::Rotation3::identity());
+        assert!(narrowphase(&apart, &below).is_empty());
+    }
+}
@@ -0,0 +1,452 @@
+use crate::body;
+use crate::dynamics;
+use crate::geometry;
+use crate::overlays;
+use crate::trace;
+
+// Sequential impulse passes over all contacts of a substep
+const SOLVER_ITERATIONS: usize = 8;
+// Approach speeds below this don't bounce, so resting bodies settle
+const BOUNCE_THRESHOLD: f32 = 0.3;
+// Penetration tolerated without position correction, avoids jitter
+const PENETRATION_SLOP: f32 = 0.002;
+// Fraction of the remaining penetration removed per substep
+const CORRECTION_FACTOR: f32 = 0.8;
+
+const GROUND_GRID_SPACING: f32 = 0.5;
+const GROUND_GRID_DIVISIONS: usize = 40;
+const GROUND_COLOR: (f32, f32, f32) = (0.35, 0.35, 0.35);
+
+// Physics ticks a contact stays visible while fading out
+const CONTACT_HISTORY_LEN: usize = 30;
+const CONTACT_MARKER_SIZE: f32 = 0.04;
+const CONTACT_NORMAL_LENGTH: f32 = 0.25;
+const CONTACT_POINT_COLOR: (f32, f32, f32) = (1.0, 1.0, 1.0);
+const CONTACT_NORMAL_COLOR: (f32, f32, f32) = (0.2, 0.9, 0.9);
+const CONTACT_DEPTH_COLOR: (f32, f32, f32) = (1.0, 0.2, 0.2);
+
+// Static infinite plane; points x with normal . x < offset are inside
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plane {
+    // Unit length
+    pub normal: nalgebra::Vector3<f32>,
+    pub offset: f32,
+    pub material: body::Material,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub body: usize,
+    // The other body, or `None` for the ground
+    pub other: Option<usize>,
+    // World-frame contact point
+    pub point: nalgebra::Vector3<f32>,
+    // Unit normal pointing from the other body (or the ground) to `body`
+    pub normal: nalgebra::Vector3<f32>,
+    pub depth: f32,
+    pub material: body::Material,
+    // Total impulse applied to `body` while resolving, the other body gets
+    // the opposite
+    pub impulse: nalgebra::Vector3<f32>,
+}
+
+impl Contact {
+    fn new(
+        body: usize,
+        other: Option<usize>,
+        point: nalgebra::Vector3<f32>,
+        normal: nalgebra::Vector3<f32>,
+        depth: f32,
+        material: body::Material,
+    ) -> Self {
+        Self {
+            body,
+            other,
+            point,
+            normal,
+            depth,
+            material,
+            impulse: nalgebra::Vector3::zeros(),
+        }
+    }
+}
+
+pub fn cuboid_corners(
+    half_extents: &[f32; 3],
+    rigid_body: &physsim::RigidBody<f32>,
+) -> [nalgebra::Vector3<f32>; 8] {
+    let h = nalgebra::Vector3::from(*half_extents);
+    let mut corners = [nalgebra::Vector3::zeros(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+        let local = nalgebra::Vector3::new(sign(1) * h.x, sign(2) * h.y, sign(4) * h.z);
+        *corner = rigid_body.pos + rigid_body.rot_mat * local;
+    }
+    corners
+}
+
+// Cuboids touch the plane with their penetrating corners, spheres with their
+// lowest point
+pub fn plane_contacts(plane: &Plane, index: usize, body: &body::Body) -> Vec<Contact> {
+    let rigid_body = &body.rigid_body;
+    let material = body.material.combine(&plane.material);
+    match body.shape {
+        body::Shape::Cuboid { half_extents } => cuboid_corners(&half_extents, rigid_body)
+            .iter()
+            .filter_map(|corner| {
+                let depth = plane.offset - plane.normal.dot(corner);
+                if depth > 0.0 {
+                    Some(Contact::new(
+                        index,
+                        None,
+                        *corner,
+                        plane.normal,
+                        depth,
+                        material,
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        body::Shape::Sphere { radius } => {
+            let depth = radius - (plane.normal.dot(&rigid_body.pos) - plane.offset);
+            if depth > 0.0 {
+                vec![Contact::new(
+                    index,
+                    None,
+                    rigid_body.pos - plane.normal * radius,
+                    plane.normal,
+                    depth,
+                    material,
+                )]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn point_velocity(body: &body::Body, point: &nalgebra::Vector3<f32>) -> nalgebra::Vector3<f32> {
+    let rigid_body = &body.rigid_body;
+    rigid_body.lin_vel + dynamics::angular_velocity(rigid_body).cross(&(point - rigid_body.pos))
+}
+
+// Change of the point's velocity along `direction` per unit impulse along it
+fn inverse_effective_mass(
+    body: &body::Body,
+    point: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+) -> f32 {
+    let r = point - body.rigid_body.pos;
+    let inv_inertia = dynamics::world_inv_inertia(&body.rigid_body);
+    1.0 / body.mass + direction.dot(&(inv_inertia * r.cross(direction)).cross(&r))
+}
+
+fn apply_impulse(
+    body: &mut body::Body,
+    point: &nalgebra::Vector3<f32>,
+    impulse: &nalgebra::Vector3<f32>,
+) {
+    let r = point - body.rigid_body.pos;
+    body.rigid_body.lin_vel += impulse / body.mass;
+    body.rigid_body.ang_mom += r.cross(impulse);
+}
+
+struct Pair<'a> {
+    body: &'a mut body::Body,
+    other: Option<&'a mut body::Body>,
+}
+
+fn pair<'a>(bodies: &'a mut [body::Body], contact: &Contact) -> Pair<'a> {
+    match contact.other {
+        None => Pair {
+            body: &mut bodies[contact.body],
+            other: None,
+        },
+        Some(other) => {
+            // Two disjoint mutable borrows out of the same slice
+            let (low, high) = (contact.body.min(other), contact.body.max(other));
+            let (head, tail) = bodies.split_at_mut(high);
+            let (low, high) = (&mut head[low], &mut tail[0]);
+            if contact.body < other {
+                Pair {
+                    body: low,
+                    other: Some(high),
+                }
+            } else {
+                Pair {
+                    body: high,
+                    other: Some(low),
+                }
+            }
+        }
+    }
+}
+
+impl Pair<'_> {
+    fn relative_velocity(&self, point: &nalgebra::Vector3<f32>) -> nalgebra::Vector3<f32> {
+        let v = point_velocity(self.body, point);
+        match &self.other {
+            Some(other) => v - point_velocity(other, point),
+            None => v,
+        }
+    }
+
+    fn inverse_effective_mass(
+        &self,
+        point: &nalgebra::Vector3<f32>,
+        direction: &nalgebra::Vector3<f32>,
+    ) -> f32 {
+        let k = inverse_effective_mass(self.body, point, direction);
+        match &self.other {
+            Some(other) => k + inverse_effective_mass(other, point, direction),
+            None => k,
+        }
+    }
+
+    fn apply_impulse(&mut self, point: &nalgebra::Vector3<f32>, impulse: &nalgebra::Vector3<f32>) {
+        apply_impulse(self.body, point, impulse);
+        if let Some(other) = self.other.as_mut() {
+            apply_impulse(other, point, &-impulse);
+        }
+    }
+}
+
+// Sequential impulses with accumulated clamping: normal impulses never pull,
+// friction stays inside the Coulomb cone. Afterwards bodies are pushed apart
+// along the normals, split by inverse mass.
+pub fn resolve(contacts: &mut [Contact], bodies: &mut [body::Body]) {
+    let bounces: Vec<f32> = contacts
+        .iter()
+        .map(|contact| {
+            let pair = pair(bodies, contact);
+            let approach = pair.relative_velocity(&contact.point).dot(&contact.normal);
+            if approach < -BOUNCE_THRESHOLD {
+                -contact.material.restitution * approach
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let mut normal_impulses = vec![0.0f32; contacts.len()];
+    let mut friction_impulses = vec![nalgebra::Vector3::<f32>::zeros(); contacts.len()];
+
+    for _ in 0..SOLVER_ITERATIONS {
+        for (i, contact) in contacts.iter().enumerate() {
+            let mut pair = pair(bodies, contact);
+            let (point, normal) = (&contact.point, &contact.normal);
+
+            let normal_speed = pair.relative_velocity(point).dot(normal);
+            let k = pair.inverse_effective_mass(point, normal);
+            let total = (normal_impulses[i] + (bounces[i] - normal_speed) / k).max(0.0);
+            let delta = total - normal_impulses[i];
+            normal_impulses[i] = total;
+            pair.apply_impulse(point, &(normal * delta));
+
+            let v = pair.relative_velocity(point);
+            let tangential = v - normal * v.dot(normal);
+            let speed = tangential.norm();
+            if speed <= f32::EPSILON {
+                continue;
+            }
+            let direction = tangential / speed;
+            let k = pair.inverse_effective_mass(point, &direction);
+            let mut total = friction_impulses[i] - direction * (speed / k);
+            let limit = contact.material.friction * normal_impulses[i];
+            if total.norm() > limit {
+                total *= limit / total.norm();
+            }
+            pair.apply_impulse(point, &(total - friction_impulses[i]));
+            friction_impulses[i] = total;
+        }
+    }
+
+    for (i, contact) in contacts.iter_mut().enumerate() {
+        contact.impulse = contact.normal * normal_impulses[i] + friction_impulses[i];
+    }
+
+    // Corrections already made count towards the other contacts, so a face
+    // resting on four corners isn't pushed out four times
+    let mut corrections = vec![nalgebra::Vector3::<f32>::zeros(); bodies.len()];
+    for contact in contacts.iter() {
+        let moved = match contact.other {
+            Some(other) => corrections[contact.body] - corrections[other],
+            None => corrections[contact.body],
+        };
+        let remaining = contact.depth - PENETRATION_SLOP - moved.dot(&contact.normal);
+        if remaining <= 0.0 {
+            continue;
+        }
+        let push = contact.normal * (remaining * CORRECTION_FACTOR);
+        match contact.other {
+            Some(other) => {
+                let inv_a = 1.0 / bodies[contact.body].mass;
+                let inv_b = 1.0 / bodies[other].mass;
+                corrections[contact.body] += push * (inv_a / (inv_a + inv_b));
+                corrections[other] -= push * (inv_b / (inv_a + inv_b));
+            }
+            None => corrections[contact.body] += push,
+        }
+    }
+    for (body, correction) in bodies.iter_mut().zip(corrections.iter()) {
+        body.rigid_body.pos += correction;
+    }
+}
+
+// The plane is infinite, so the grid follows the viewer, snapped to whole
+// cells so that it doesn't appear to slide
+pub fn ground_to_vertices(vertices: &mut Vec<f32>, plane: &Plane, viewer: &nalgebra::Vector3<f32>) {
+    let (u, v) = geometry::perpendicular_basis(&plane.normal);
+    let snap = |x: f32| (x / GROUND_GRID_SPACING).round() * GROUND_GRID_SPACING;
+    let center = plane.normal * plane.offset + u * snap(u.dot(viewer)) + v * snap(v.dot(viewer));
+    let half_size = GROUND_GRID_SPACING * GROUND_GRID_DIVISIONS as f32 / 2.0;
+    geometry::grid_to_vertices(
+        vertices,
+        &center,
+        &u,
+        &v,
+        half_size,
+        GROUND_GRID_DIVISIONS,
+        GROUND_COLOR,
+    );
+}
+
+// Contacts of the recent physics ticks, oldest first
+pub struct ContactHistory {
+    ticks: trace::RingBuffer<Vec<Contact>>,
+}
+
+impl Default for ContactHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContactHistory {
+    pub fn new() -> Self {
+        Self {
+            ticks: trace::RingBuffer::new(CONTACT_HISTORY_LEN),
+        }
+    }
+
+    // Call after every physics tick, also without contacts so old ones age
+    pub fn record(&mut self, contacts: &[Contact]) {
+        self.ticks.push(contacts.to_vec());
+    }
+
+    pub fn clear(&mut self) {
+        self.ticks.clear();
+    }
+}
+
+// Marker at each contact point, its normal, the penetration depth as a red
+// segment into the body and the impulse as an arrow. Older ticks fade out.
+pub fn contacts_to_vertices(
+    vertices: &mut Vec<f32>,
+    history: &ContactHistory,
+    overlay: &overlays::VectorOverlay,
+) {
+    if !overlay.enabled {
+        return;
+    }
+
+    let len = history.ticks.capacity();
+    let skipped = len - history.ticks.len();
+    for (age, contacts) in history.ticks.iter().enumerate() {
+        let fade = (skipped + age + 1) as f32 / len as f32;
+        let faded = |color: (f32, f32, f32)| (color.0 * fade, color.1 * fade, color.2 * fade);
+
+        for contact in contacts {
+            for axis in [
+                nalgebra::Vector3::x(),
+                nalgebra::Vector3::y(),
+                nalgebra::Vector3::z(),
+            ] {
+                geometry::segment_to_vertices(
+                    vertices,
+                    &(contact.point - axis * CONTACT_MARKER_SIZE),
+                    &(contact.point + axis * CONTACT_MARKER_SIZE),
+                    faded(CONTACT_POINT_COLOR),
+                );
+            }
+            geometry::vector_to_vertices(
+                vertices,
+                &contact.point,
+                &(contact.normal * CONTACT_NORMAL_LENGTH),
+                Some(faded(CONTACT_NORMAL_COLOR)),
+                0.02,
+            );
+            geometry::segment_to_vertices(
+                vertices,
+                &contact.point,
+                &(contact.point - contact.normal * contact.depth),
+                faded(CONTACT_DEPTH_COLOR),
+            );
+            geometry::vector_to_vertices(
+                vertices,
+                &contact.point,
+                &(contact.impulse * overlay.scale),
+                Some(faded(overlay.color)),
+                0.03,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene;
+
+    #[test]
+    fn dropped_box_settles_on_the_ground() {
+        let mut simulation = scene::Scene::parse(
+            r#"{
+                "version": 1,
+                "bodies": [{
+                    "name": "box",
+                    "shape": { "type": "cuboid", "half_extents": [0.5, 0.25, 0.4] },
+                    "mass": 2.0,
+                    "material": { "restitution": 0.5, "friction": 0.6 },
+                    "pos": [0.0, 1.0, 0.0],
+                    "lin_vel": [1.5, 0.0, 0.0]
+                }],
+                "forces": [{ "type": "gravity", "acceleration": [0.0, -9.81, 0.0] }],
+                "ground": { "normal": [0.0, 1.0, 0.0], "material": { "restitution": 0.5, "friction": 0.6 } },
+                "integrator": { "timestep": 0.005, "substeps": 2 }
+            }"#,
+        )
+        .unwrap()
+        .build_simulation().unwrap();
+
+        let mut bounced = false;
+        let mut landed = false;
+        for _ in 0..600 {
+            simulation.step();
+            let lin_vel = simulation.bodies[0].rigid_body.lin_vel;
+            landed |= !simulation.contacts.is_empty();
+            bounced |= landed && lin_vel.y > 0.5;
+        }
+        assert!(bounced, "restitution should make the first impact bounce");
+
+        let rigid_body = &simulation.bodies[0].rigid_body;
+        // At rest, resting on its face and having slid some way before
+        // friction stopped it
+        assert!(rigid_body.lin_vel.norm() < 0.05, "{}", rigid_body.lin_vel);
+        assert!(rigid_body.ang_mom.norm() < 0.05, "{}", rigid_body.ang_mom);
+        assert!((rigid_body.pos.y - 0.25).abs() < 0.02, "{}", rigid_body.pos);
+        assert!(
+            rigid_body.pos.x > 0.1 && rigid_body.pos.x < 3.0,
+            "{}",
+            rigid_body.pos
+        );
+        assert!(rigid_body.rot_mat[(1, 1)] > 0.999);
+        assert!(simulation
+            .contacts
+            .iter()
+            .all(|contact| contact.other.is_none()
+                && contact.normal == nalgebra::Vector3::y()
+                && contact.depth < 0.02));
+        assert!(simulation.contacts.len() >= 4);
+    }
+}
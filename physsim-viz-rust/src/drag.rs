@@ -0,0 +1,214 @@
+use crate::body;
+use crate::dynamics;
+use crate::geometry;
+
+// Pull per unit mass and distance, 1/s^2
+const PULL_STIFFNESS: f32 = 40.0;
+const PULL_DAMPING_RATIO: f32 = 0.5;
+// Velocity change of the grabbed point per unit of drag distance, 1/s
+const IMPULSE_GAIN: f32 = 4.0;
+const ARROW_COLOR: (f32, f32, f32) = (1.0, 1.0, 1.0);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DragMode {
+    // Damped spring pulling the grabbed point towards the cursor while held
+    #[default]
+    Pull,
+    // Single impulse at the grabbed point on release, along the drag vector
+    Impulse,
+}
+
+impl DragMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pull" => Some(DragMode::Pull),
+            "impulse" => Some(DragMode::Impulse),
+            _ => None,
+        }
+    }
+}
+
+// A body grabbed at a body-fixed point. The cursor moves the target within
+// the plane through the grab point that faces the camera.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Drag {
+    pub body: usize,
+    pub point: nalgebra::Vector3<f32>,
+    pub mode: DragMode,
+    pub target: nalgebra::Vector3<f32>,
+    plane_normal: nalgebra::Vector3<f32>,
+}
+
+impl Drag {
+    pub fn new(
+        index: usize,
+        body: &body::Body,
+        hit: &nalgebra::Vector3<f32>,
+        view_direction: &nalgebra::Vector3<f32>,
+        mode: DragMode,
+    ) -> Self {
+        let rigid_body = &body.rigid_body;
+        Self {
+            body: index,
+            point: rigid_body.rot_mat.transpose() * (hit - rigid_body.pos),
+            mode,
+            target: *hit,
+            plane_normal: view_direction.normalize(),
+        }
+    }
+
+    pub fn grabbed_point(&self, body: &body::Body) -> nalgebra::Vector3<f32> {
+        body.rigid_body.pos + body.rigid_body.rot_mat * self.point
+    }
+
+    // Moves the target to where the ray crosses the drag plane, if it does
+    pub fn move_to(&mut self, origin: &nalgebra::Vector3<f32>, direction: &nalgebra::Vector3<f32>) {
+        let denominator = direction.dot(&self.plane_normal);
+        if denominator.abs() < f32::EPSILON {
+            return;
+        }
+        let t = (self.target - origin).dot(&self.plane_normal) / denominator;
+        if t > 0.0 {
+            self.target = origin + direction * t;
+        }
+    }
+
+    // Applies the pull over `dt`, does nothing in impulse mode
+    pub fn apply(&self, bodies: &mut [body::Body], dt: f32) {
+        if self.mode != DragMode::Pull {
+            return;
+        }
+        let body = match bodies.get_mut(self.body) {
+            Some(body) => body,
+            None => return,
+        };
+        let point = self.grabbed_point(body);
+        let r = point - body.rigid_body.pos;
+        let velocity =
+            body.rigid_body.lin_vel + dynamics::angular_velocity(&body.rigid_body).cross(&r);
+        let stiffness = PULL_STIFFNESS * body.mass;
+        let damping = 2.0 * PULL_DAMPING_RATIO * (stiffness * body.mass).sqrt();
+        let force = (self.target - point) * stiffness - velocity * damping;
+        kick(body, &r, &(force * dt));
+    }
+
+    // Ends the drag, applying the impulse in impulse mode
+    pub fn release(&self, bodies: &mut [body::Body]) {
+        if self.mode != DragMode::Impulse {
+            return;
+        }
+        let body = match bodies.get_mut(self.body) {
+            Some(body) => body,
+            None => return,
+        };
+        let point = self.grabbed_point(body);
+        let impulse = (self.target - point) * (IMPULSE_GAIN * body.mass);
+        kick(body, &(point - body.rigid_body.pos), &impulse);
+    }
+}
+
+// Impulse at the offset `r` from the center of mass
+fn kick(body: &mut body::Body, r: &nalgebra::Vector3<f32>, impulse: &nalgebra::Vector3<f32>) {
+    body.rigid_body.lin_vel += impulse / body.mass;
+    body.rigid_body.ang_mom += r.cross(impulse);
+}
+
+// Arrow from the grabbed point to the target
+pub fn drag_to_vertices(vertices: &mut Vec<f32>, drag: &Drag, bodies: &[body::Body]) {
+    if let Some(body) = bodies.get(drag.body) {
+        let point = drag.grabbed_point(body);
+        geometry::vector_to_vertices(
+            vertices,
+            &point,
+            &(drag.target - point),
+            Some(ARROW_COLOR),
+            0.05,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bodies() -> Vec<body::Body> {
+        vec![body::Body {
+            name: String::new(),
+            shape: body::Shape::Cuboid {
+                half_extents: [0.5, 0.5, 0.5],
+            },
+            mass: 2.0,
+            material: body::Material::default(),
+            rigid_body: physsim::RigidBody {
+                pos: nalgebra::Vector3::new(1.0, 0.0, 0.0),
+                lin_vel: nalgebra::Vector3::new(0.0, 0.0, 0.5),
+                rot_mat: *nalgebra::Rotation3::from_axis_angle(&nalgebra::Vector3::y_axis(), 0.7)
+                    .matrix(),
+                ang_mom: nalgebra::Vector3::new(0.1, 0.0, 0.0),
+                inv_ine: nalgebra::Matrix3::identity() * 6.0,
+            },
+        }]
+    }
+
+    fn unchanged(body: &body::Body, before: &body::Body) -> bool {
+        let (a, b) = (&body.rigid_body, &before.rigid_body);
+        (a.pos, a.lin_vel, a.rot_mat, a.ang_mom) == (b.pos, b.lin_vel, b.rot_mat, b.ang_mom)
+    }
+
+    // Grabbed off center, seen from a camera that looks down -z
+    fn drag(bodies: &[body::Body], mode: DragMode) -> Drag {
+        let hit = bodies[0].rigid_body.pos + nalgebra::Vector3::new(0.3, 0.4, 0.5);
+        Drag::new(0, &bodies[0], &hit, &-nalgebra::Vector3::z(), mode)
+    }
+
+    #[test]
+    fn keeps_the_target_on_the_drag_plane() {
+        let bodies = bodies();
+        let mut drag = drag(&bodies, DragMode::Pull);
+        let camera = nalgebra::Vector3::new(0.0, 0.0, 10.0);
+
+        let direction = nalgebra::Vector3::new(0.2, -0.1, -1.0).normalize();
+        drag.move_to(&camera, &direction);
+        assert!((drag.target.z - 0.5).abs() < 1e-5);
+        let along = (drag.target - camera).normalize();
+        assert!((along - direction).norm() < 1e-5);
+
+        // Rays that miss the plane leave the target where it was
+        let target = drag.target;
+        drag.move_to(&camera, &nalgebra::Vector3::x());
+        drag.move_to(&camera, &nalgebra::Vector3::z());
+        assert_eq!(drag.target, target);
+    }
+
+    #[test]
+    fn releases_an_impulse_at_the_grabbed_point() {
+        let mut bodies = bodies();
+        let mut drag = drag(&bodies, DragMode::Impulse);
+        drag.target += nalgebra::Vector3::new(0.5, -0.25, 0.0);
+        let before = bodies[0].clone();
+
+        drag.apply(&mut bodies, 0.01);
+        assert!(unchanged(&bodies[0], &before));
+
+        drag.release(&mut bodies);
+        let impulse = nalgebra::Vector3::new(0.5, -0.25, 0.0) * (IMPULSE_GAIN * 2.0);
+        let r = nalgebra::Vector3::new(0.3, 0.4, 0.5);
+        let (rigid_body, before) = (&bodies[0].rigid_body, &before.rigid_body);
+        assert!((rigid_body.lin_vel - (before.lin_vel + impulse / 2.0)).norm() < 1e-5);
+        assert!((rigid_body.ang_mom - (before.ang_mom + r.cross(&impulse))).norm() < 1e-5);
+        assert_eq!(rigid_body.pos, before.pos);
+    }
+
+    #[test]
+    fn pulls_only_while_held() {
+        let mut bodies = bodies();
+        let mut drag = drag(&bodies, DragMode::Pull);
+        drag.target += nalgebra::Vector3::new(1.0, 0.0, 0.0);
+        let before = bodies[0].clone();
+
+        drag.release(&mut bodies);
+        assert!(unchanged(&bodies[0], &before));
+        drag.apply(&mut bodies, 0.01);
+        assert!(bodies[0].rigid_body.lin_vel.x > 0.0);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::body;
+
+pub fn world_inv_inertia(rigid_body: &physsim::RigidBody<f32>) -> nalgebra::Matrix3<f32> {
+    rigid_body.rot_mat * rigid_body.inv_ine * rigid_body.rot_mat.transpose()
+}
+
+pub fn angular_velocity(rigid_body: &physsim::RigidBody<f32>) -> nalgebra::Vector3<f32> {
+    world_inv_inertia(rigid_body) * rigid_body.ang_mom
+}
+
+pub fn kinetic_energy(rigid_body: &physsim::RigidBody<f32>, mass: f32) -> f32 {
+    0.5 * mass * rigid_body.lin_vel.norm_squared()
+        + 0.5 * rigid_body.ang_mom.dot(&angular_velocity(rigid_body))
+}
+
+// Principal moments of inertia and the body-frame principal axes (as columns),
+// sorted by ascending moment. The axes always form a right-handed frame.
+pub fn principal_axes(
+    inv_ine: &nalgebra::Matrix3<f64>,
+) -> (nalgebra::Vector3<f64>, nalgebra::Matrix3<f64>) {
+    let eigen = nalgebra::SymmetricEigen::new(*inv_ine);
+
+    // Largest inverse moment is the smallest moment
+    let mut order = [0, 1, 2];
+    order.sort_by(|&i, &j| eigen.eigenvalues[j].total_cmp(&eigen.eigenvalues[i]));
+
+    let moments = nalgebra::Vector3::new(
+        1.0 / eigen.eigenvalues[order[0]],
+        1.0 / eigen.eigenvalues[order[1]],
+        1.0 / eigen.eigenvalues[order[2]],
+    );
+    let mut axes = nalgebra::Matrix3::from_columns(&[
+        eigen.eigenvectors.column(order[0]).into_owned(),
+        eigen.eigenvectors.column(order[1]).into_owned(),
+        eigen.eigenvectors.column(order[2]).into_owned(),
+    ]);
+    if axes.determinant() < 0.0 {
+        axes.set_column(2, &(-axes.column(2)));
+    }
+
+    (moments, axes)
+}
+
+// Quantities conserved by a closed, force-free system of bodies. Angular
+// momentum is taken about the world origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Totals {
+    pub energy: f64,
+    pub momentum: nalgebra::Vector3<f64>,
+    pub ang_mom: nalgebra::Vector3<f64>,
+}
+
+pub fn totals(bodies: &[body::Body]) -> Totals {
+    let mut totals = Totals {
+        energy: 0.0,
+        momentum: nalgebra::Vector3::zeros(),
+        ang_mom: nalgebra::Vector3::zeros(),
+    };
+    for body in bodies {
+        let rigid_body = &body.rigid_body;
+        let momentum = (rigid_body.lin_vel * body.mass).cast::<f64>();
+        totals.energy += kinetic_energy(rigid_body, body.mass) as f64;
+        totals.momentum += momentum;
+        totals.ang_mom +=
+            rigid_body.pos.cast::<f64>().cross(&momentum) + rigid_body.ang_mom.cast::<f64>();
+    }
+    totals
+}
@@ -0,0 +1,342 @@
+use crate::body;
+use crate::dynamics;
+use crate::geometry;
+use crate::overlays;
+use crate::scene;
+
+// Stored as a plain string, RON can't read unit variants back from inside an
+// internally tagged enum
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Frame {
+    #[default]
+    World,
+    // Rotates with the body
+    Body,
+}
+
+impl From<Frame> for String {
+    fn from(frame: Frame) -> Self {
+        match frame {
+            Frame::World => String::from("world"),
+            Frame::Body => String::from("body"),
+        }
+    }
+}
+
+impl std::convert::TryFrom<String> for Frame {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match name.as_str() {
+            "world" => Ok(Frame::World),
+            "body" => Ok(Frame::Body),
+            _ => Err(format!(
+                "unknown frame `{}`, expected `world` or `body`",
+                name
+            )),
+        }
+    }
+}
+
+// Forces and torques are applied to the momenta before every `step_sim` call,
+// so they are constant over a substep
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ForceGenerator {
+    // Acts on every body
+    Gravity {
+        acceleration: [f32; 3],
+    },
+    ConstantForce {
+        body: usize,
+        force: [f32; 3],
+        #[serde(default)]
+        frame: Frame,
+    },
+    ConstantTorque {
+        body: usize,
+        torque: [f32; 3],
+        #[serde(default)]
+        frame: Frame,
+    },
+    // Force applied at a body-fixed point, so it also exerts a torque
+    PointForce {
+        body: usize,
+        point: [f32; 3],
+        force: [f32; 3],
+        #[serde(default)]
+        frame: Frame,
+    },
+    // Force -c v; acts on every body unless `body` is given
+    LinearDamping {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<usize>,
+        coefficient: f32,
+    },
+    // Torque -c w; acts on every body unless `body` is given
+    AngularDamping {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<usize>,
+        coefficient: f32,
+    },
+}
+
+// World-frame force acting at `point` plus a pure torque
+pub struct Applied {
+    pub point: nalgebra::Vector3<f32>,
+    pub force: nalgebra::Vector3<f32>,
+    pub torque: nalgebra::Vector3<f32>,
+}
+
+impl ForceGenerator {
+    pub fn validate(&self, path: &str, body_count: usize) -> Result<(), scene::SceneError> {
+        let field = |name: &str| format!("{}.{}", path, name);
+        let check_body = |body: usize| scene::check_body(&field("body"), body, body_count);
+        let check_vector = |name: &str, v: &[f32; 3]| scene::check_vector(&field(name), v);
+
+        match self {
+            ForceGenerator::Gravity { acceleration } => check_vector("acceleration", acceleration),
+            ForceGenerator::ConstantForce { body, force, .. } => {
+                check_body(*body)?;
+                check_vector("force", force)
+            }
+            ForceGenerator::ConstantTorque { body, torque, .. } => {
+                check_body(*body)?;
+                check_vector("torque", torque)
+            }
+            ForceGenerator::PointForce {
+                body, point, force, ..
+            } => {
+                check_body(*body)?;
+                check_vector("point", point)?;
+                check_vector("force", force)
+            }
+            ForceGenerator::LinearDamping { body, coefficient }
+            | ForceGenerator::AngularDamping { body, coefficient } => {
+                if let Some(body) = body {
+                    check_body(*body)?;
+                }
+                scene::check_non_negative(&field("coefficient"), *coefficient)
+            }
+        }
+    }
+
+    // Indices of the bodies the generator is tied to
+    pub fn body_indices_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            ForceGenerator::Gravity { .. } => Vec::new(),
+            ForceGenerator::ConstantForce { body, .. }
+            | ForceGenerator::ConstantTorque { body, .. }
+            | ForceGenerator::PointForce { body, .. } => vec![body],
+            ForceGenerator::LinearDamping { body, .. }
+            | ForceGenerator::AngularDamping { body, .. } => body.iter_mut().collect(),
+        }
+    }
+
+    // What the generator does to the body with the given index, if it acts on
+    // it at all
+    pub fn apply_to(&self, index: usize, body: &body::Body) -> Option<Applied> {
+        let rigid_body = &body.rigid_body;
+        let to_world = |v: &[f32; 3], frame: Frame| match frame {
+            Frame::World => nalgebra::Vector3::from(*v),
+            Frame::Body => rigid_body.rot_mat * nalgebra::Vector3::from(*v),
+        };
+        let at_center = |force, torque| Applied {
+            point: rigid_body.pos,
+            force,
+            torque,
+        };
+
+        match self {
+            ForceGenerator::Gravity { acceleration } => Some(at_center(
+                nalgebra::Vector3::from(*acceleration) * body.mass,
+                nalgebra::Vector3::zeros(),
+            )),
+            ForceGenerator::ConstantForce {
+                body: target,
+                force,
+                frame,
+            } if *target == index => Some(at_center(
+                to_world(force, *frame),
+                nalgebra::Vector3::zeros(),
+            )),
+            ForceGenerator::ConstantTorque {
+                body: target,
+                torque,
+                frame,
+            } if *target == index => Some(at_center(
+                nalgebra::Vector3::zeros(),
+                to_world(torque, *frame),
+            )),
+            ForceGenerator::PointForce {
+                body: target,
+                point,
+                force,
+                frame,
+            } if *target == index => Some(Applied {
+                point: rigid_body.pos + rigid_body.rot_mat * nalgebra::Vector3::from(*point),
+                force: to_world(force, *frame),
+                torque: nalgebra::Vector3::zeros(),
+            }),
+            ForceGenerator::LinearDamping {
+                body: target,
+                coefficient,
+            } if target.unwrap_or(index) == index => Some(at_center(
+                -rigid_body.lin_vel * *coefficient,
+                nalgebra::Vector3::zeros(),
+            )),
+            ForceGenerator::AngularDamping {
+                body: target,
+                coefficient,
+            } if target.unwrap_or(index) == index => Some(at_center(
+                nalgebra::Vector3::zeros(),
+                -dynamics::angular_velocity(rigid_body) * *coefficient,
+            )),
+            _ => None,
+        }
+    }
+}
+
+// Kicks the momenta of a body by the impulse of all generators over `dt`
+pub fn apply(generators: &[ForceGenerator], index: usize, body: &mut body::Body, dt: f32) {
+    let mut force = nalgebra::Vector3::zeros();
+    let mut torque = nalgebra::Vector3::zeros();
+    let mut acted = false;
+    for applied in generators.iter().filter_map(|g| g.apply_to(index, body)) {
+        force += applied.force;
+        torque += applied.torque + (applied.point - body.rigid_body.pos).cross(&applied.force);
+        acted = true;
+    }
+
+    // Leave untouched bodies bit-identical to a force-free run
+    if acted {
+        body.rigid_body.lin_vel += force * (dt / body.mass);
+        body.rigid_body.ang_mom += torque * dt;
+    }
+}
+
+pub fn forces_to_vertices(
+    vertices: &mut Vec<f32>,
+    generators: &[ForceGenerator],
+    bodies: &[body::Body],
+    overlays: &overlays::Overlays,
+) {
+    for generator in generators {
+        for (index, body) in bodies.iter().enumerate() {
+            let applied = match generator.apply_to(index, body) {
+                Some(applied) => applied,
+                None => continue,
+            };
+            if overlays.forces.enabled && applied.force != nalgebra::Vector3::zeros() {
+                geometry::vector_to_vertices(
+                    vertices,
+                    &applied.point,
+                    &(applied.force * overlays.forces.scale),
+                    Some(overlays.forces.color),
+                    0.05,
+                );
+            }
+            if overlays.torques.enabled && applied.torque != nalgebra::Vector3::zeros() {
+                geometry::vector_to_vertices(
+                    vertices,
+                    &applied.point,
+                    &(applied.torque * overlays.torques.scale),
+                    Some(overlays.torques.color),
+                    0.05,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(forces: &str) -> crate::simulation::Simulation {
+        let text = format!(
+            r#"{{
+                "version": 1,
+                "bodies": [
+                    {{
+                        "name": "light",
+                        "shape": {{ "type": "sphere", "radius": 0.1 }},
+                        "mass": 0.5,
+                        "pos": [0.0, 10.0, 0.0]
+                    }},
+                    {{
+                        "name": "heavy",
+                        "shape": {{ "type": "cuboid", "half_extents": [0.1, 0.2, 0.3] }},
+                        "mass": 8.0,
+                        "pos": [2.0, 10.0, 0.0],
+                        "orientation": [0.8660254, 0.5, 0.0, 0.0]
+                    }}
+                ],
+                "forces": {},
+                "integrator": {{ "timestep": 0.01, "substeps": 2 }}
+            }}"#,
+            forces
+        );
+        scene::Scene::parse(&text)
+            .unwrap()
+            .build_simulation()
+            .unwrap()
+    }
+
+    #[test]
+    fn bodies_fall_freely_regardless_of_mass() {
+        let mut simulation =
+            simulation(r#"[{ "type": "gravity", "acceleration": [0.0, -9.81, 0.0] }]"#);
+        for _ in 0..100 {
+            simulation.step();
+        }
+        let t = simulation.time() as f32;
+        for (body, x) in simulation.bodies.iter().zip([0.0, 2.0]) {
+            let rigid_body = &body.rigid_body;
+            assert!(
+                (rigid_body.lin_vel - nalgebra::Vector3::new(0.0, -9.81 * t, 0.0)).norm() < 1e-4
+            );
+            // Kicked before every drift, so ahead by at most half a substep
+            let fallen = 10.0 - rigid_body.pos.y;
+            assert!(
+                (fallen - 0.5 * 9.81 * t * t).abs() < 9.81 * t * 0.005 + 1e-4,
+                "{}",
+                fallen
+            );
+            assert_eq!((rigid_body.pos.x, rigid_body.pos.z), (x, 0.0));
+            assert_eq!(rigid_body.ang_mom, nalgebra::Vector3::zeros());
+        }
+    }
+
+    #[test]
+    fn point_forces_exert_torque_about_the_center() {
+        let dt = 0.01;
+        for frame in [Frame::World, Frame::Body] {
+            let generators = [ForceGenerator::PointForce {
+                body: 1,
+                point: [0.0, 0.3, 0.1],
+                force: [2.0, 0.0, -1.0],
+                frame,
+            }];
+            let mut simulation = simulation("[]");
+            let rot_mat = simulation.bodies[1].rigid_body.rot_mat;
+            let force = match frame {
+                Frame::World => nalgebra::Vector3::new(2.0, 0.0, -1.0),
+                Frame::Body => rot_mat * nalgebra::Vector3::new(2.0, 0.0, -1.0),
+            };
+            let lever = rot_mat * nalgebra::Vector3::new(0.0, 0.3, 0.1);
+
+            apply(&generators, 0, &mut simulation.bodies[0], dt);
+            apply(&generators, 1, &mut simulation.bodies[1], dt);
+            assert_eq!(
+                simulation.bodies[0].rigid_body.lin_vel,
+                nalgebra::Vector3::zeros()
+            );
+            let rigid_body = &simulation.bodies[1].rigid_body;
+            assert!((rigid_body.lin_vel - force * (dt / 8.0)).norm() < 1e-6);
+            assert!((rigid_body.ang_mom - lever.cross(&force) * dt).norm() < 1e-6);
+            assert!(rigid_body.ang_mom.norm() > 1e-3);
+        }
+    }
+}
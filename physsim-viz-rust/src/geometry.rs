@@ -0,0 +1,476 @@
+use crate::body;
+
+fn add_colored_vert(vertices: &mut Vec<f32>, v: &nalgebra::Vector3<f32>, color: (f32, f32, f32)) {
+    vertices.push(v.x);
+    vertices.push(v.y);
+    vertices.push(v.z);
+    vertices.push(color.0);
+    vertices.push(color.1);
+    vertices.push(color.2);
+}
+
+pub fn segment_to_vertices(
+    vertices: &mut Vec<f32>,
+    a: &nalgebra::Vector3<f32>,
+    b: &nalgebra::Vector3<f32>,
+    color: (f32, f32, f32),
+) {
+    add_colored_vert(vertices, a, color);
+    add_colored_vert(vertices, b, color);
+}
+
+// Closed loop given as a list of points, emitted as separate line segments
+pub fn loop_to_vertices(
+    vertices: &mut Vec<f32>,
+    points: &[nalgebra::Vector3<f32>],
+    color: (f32, f32, f32),
+) {
+    for (i, p) in points.iter().enumerate() {
+        segment_to_vertices(vertices, p, &points[(i + 1) % points.len()], color);
+    }
+}
+
+// Wireframe of an ellipsoid whose semi-axes are the columns of `semi_axes`,
+// made of latitude rings and meridians
+pub fn ellipsoid_to_vertices(
+    vertices: &mut Vec<f32>,
+    center: &nalgebra::Vector3<f32>,
+    semi_axes: &nalgebra::Matrix3<f32>,
+    color: (f32, f32, f32),
+    segments: usize,
+) {
+    let point = |theta: f32, phi: f32| {
+        center
+            + semi_axes
+                * nalgebra::Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+    };
+
+    let rings = segments / 2;
+    for i in 1..rings {
+        let theta = std::f32::consts::PI * i as f32 / rings as f32;
+        let ring = (0..segments)
+            .map(|j| {
+                point(
+                    theta,
+                    2.0 * std::f32::consts::PI * j as f32 / segments as f32,
+                )
+            })
+            .collect::<Vec<_>>();
+        loop_to_vertices(vertices, &ring, color);
+    }
+
+    for j in 0..segments {
+        let phi = 2.0 * std::f32::consts::PI * j as f32 / segments as f32;
+        for i in 0..rings {
+            let theta_1 = std::f32::consts::PI * i as f32 / rings as f32;
+            let theta_2 = std::f32::consts::PI * (i + 1) as f32 / rings as f32;
+            segment_to_vertices(vertices, &point(theta_1, phi), &point(theta_2, phi), color);
+        }
+    }
+}
+
+// Square grid spanned by `u` and `v` (expected orthonormal)
+pub fn grid_to_vertices(
+    vertices: &mut Vec<f32>,
+    center: &nalgebra::Vector3<f32>,
+    u: &nalgebra::Vector3<f32>,
+    v: &nalgebra::Vector3<f32>,
+    half_size: f32,
+    divisions: usize,
+    color: (f32, f32, f32),
+) {
+    for i in 0..=divisions {
+        let offset = -half_size + 2.0 * half_size * i as f32 / divisions as f32;
+        segment_to_vertices(
+            vertices,
+            &(center + u * offset - v * half_size),
+            &(center + u * offset + v * half_size),
+            color,
+        );
+        segment_to_vertices(
+            vertices,
+            &(center + v * offset - u * half_size),
+            &(center + v * offset + u * half_size),
+            color,
+        );
+    }
+}
+
+pub fn perpendicular_basis(
+    n: &nalgebra::Vector3<f32>,
+) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+    let helper = if n.x.abs() < 0.9 {
+        nalgebra::Vector3::x()
+    } else {
+        nalgebra::Vector3::y()
+    };
+    let u = helper.cross(n).normalize();
+    let v = n.cross(&u).normalize();
+    (u, v)
+}
+
+pub fn shape_to_vertices(
+    vertices: &mut Vec<f32>,
+    shape: &body::Shape,
+    rigid_body: &physsim::RigidBody<f32>,
+    wireframe: bool,
+) {
+    match *shape {
+        body::Shape::Cuboid { half_extents } => cuboid_to_vertices(
+            vertices,
+            rigid_body,
+            &(nalgebra::Vector3::from(half_extents) * 2.0),
+            wireframe,
+        ),
+        body::Shape::Sphere { radius } => {
+            sphere_to_vertices(vertices, rigid_body, radius, wireframe)
+        }
+    }
+}
+
+pub fn cuboid_to_vertices(
+    vertices: &mut Vec<f32>,
+    rigid_body: &physsim::RigidBody<f32>,
+    size: &nalgebra::Vector3<f32>,
+    wireframe: bool,
+) {
+    fn add_vert(vertices: &mut Vec<f32>, v: nalgebra::Vector3<f32>) {
+        vertices.push(v.x);
+        vertices.push(v.y);
+        vertices.push(v.z);
+    }
+
+    let v1 = rigid_body.rot_mat * nalgebra::Vector3::new(-0.5, -0.5, -0.5).component_mul(size)
+        + rigid_body.pos;
+    let v2 = rigid_body.rot_mat * nalgebra::Vector3::new(-0.5, -0.5, 0.5).component_mul(size)
+        + rigid_body.pos;
+    let v3 = rigid_body.rot_mat * nalgebra::Vector3::new(-0.5, 0.5, -0.5).component_mul(size)
+        + rigid_body.pos;
+    let v4 = rigid_body.rot_mat * nalgebra::Vector3::new(-0.5, 0.5, 0.5).component_mul(size)
+        + rigid_body.pos;
+    let v5 = rigid_body.rot_mat * nalgebra::Vector3::new(0.5, -0.5, -0.5).component_mul(size)
+        + rigid_body.pos;
+    let v6 = rigid_body.rot_mat * nalgebra::Vector3::new(0.5, -0.5, 0.5).component_mul(size)
+        + rigid_body.pos;
+    let v7 = rigid_body.rot_mat * nalgebra::Vector3::new(0.5, 0.5, -0.5).component_mul(size)
+        + rigid_body.pos;
+    let v8 = rigid_body.rot_mat * nalgebra::Vector3::new(0.5, 0.5, 0.5).component_mul(size)
+        + rigid_body.pos;
+
+    if wireframe {
+        //E1
+        add_vert(vertices, v1);
+        add_vert(vertices, v2);
+
+        //E2
+        add_vert(vertices, v1);
+        add_vert(vertices, v3);
+
+        //E3
+        add_vert(vertices, v3);
+        add_vert(vertices, v4);
+
+        //E4
+        add_vert(vertices, v2);
+        add_vert(vertices, v4);
+
+        //E5
+        add_vert(vertices, v5);
+        add_vert(vertices, v6);
+
+        //E6
+        add_vert(vertices, v5);
+        add_vert(vertices, v7);
+
+        //E7
+        add_vert(vertices, v7);
+        add_vert(vertices, v8);
+
+        //E8
+        add_vert(vertices, v6);
+        add_vert(vertices, v8);
+
+        //E9
+        add_vert(vertices, v1);
+        add_vert(vertices, v5);
+
+        //E10
+        add_vert(vertices, v3);
+        add_vert(vertices, v7);
+
+        //E11
+        add_vert(vertices, v4);
+        add_vert(vertices, v8);
+
+        //E12
+        add_vert(vertices, v2);
+        add_vert(vertices, v6);
+    } else {
+        //F1
+        add_vert(vertices, v1);
+        add_vert(vertices, v2);
+        add_vert(vertices, v3);
+
+        //F2
+        add_vert(vertices, v2);
+        add_vert(vertices, v3);
+        add_vert(vertices, v4);
+
+        //F3
+        add_vert(vertices, v1);
+        add_vert(vertices, v3);
+        add_vert(vertices, v7);
+
+        //F4
+        add_vert(vertices, v1);
+        add_vert(vertices, v5);
+        add_vert(vertices, v7);
+
+        //F5
+        add_vert(vertices, v1);
+        add_vert(vertices, v2);
+        add_vert(vertices, v6);
+
+        //F6
+        add_vert(vertices, v1);
+        add_vert(vertices, v5);
+        add_vert(vertices, v6);
+
+        //F7
+        add_vert(vertices, v5);
+        add_vert(vertices, v6);
+        add_vert(vertices, v7);
+
+        //F8
+        add_vert(vertices, v6);
+        add_vert(vertices, v7);
+        add_vert(vertices, v8);
+
+        //F9
+        add_vert(vertices, v2);
+        add_vert(vertices, v4);
+        add_vert(vertices, v8);
+
+        //F10
+        add_vert(vertices, v2);
+        add_vert(vertices, v6);
+        add_vert(vertices, v8);
+
+        //F11
+        add_vert(vertices, v3);
+        add_vert(vertices, v4);
+        add_vert(vertices, v8);
+
+        //F12
+        add_vert(vertices, v3);
+        add_vert(vertices, v7);
+        add_vert(vertices, v8);
+    }
+}
+
+pub fn sphere_to_vertices(
+    vertices: &mut Vec<f32>,
+    rigid_body: &physsim::RigidBody<f32>,
+    radius: f32,
+    wireframe: bool,
+) {
+    fn add_vert(vertices: &mut Vec<f32>, v: nalgebra::Vector3<f32>) {
+        vertices.push(v.x);
+        vertices.push(v.y);
+        vertices.push(v.z);
+    }
+
+    let rings = 8;
+    let segments = 16;
+    let point = |i: usize, j: usize| {
+        let theta = std::f32::consts::PI * i as f32 / rings as f32;
+        let phi = 2.0 * std::f32::consts::PI * j as f32 / segments as f32;
+        rigid_body.rot_mat
+            * nalgebra::Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            )
+            * radius
+            + rigid_body.pos
+    };
+
+    for i in 0..rings {
+        for j in 0..segments {
+            let v1 = point(i, j);
+            let v2 = point(i + 1, j);
+            let v3 = point(i, j + 1);
+            let v4 = point(i + 1, j + 1);
+
+            if wireframe {
+                add_vert(vertices, v1);
+                add_vert(vertices, v2);
+                add_vert(vertices, v1);
+                add_vert(vertices, v3);
+            } else {
+                add_vert(vertices, v1);
+                add_vert(vertices, v2);
+                add_vert(vertices, v4);
+
+                add_vert(vertices, v1);
+                add_vert(vertices, v4);
+                add_vert(vertices, v3);
+            }
+        }
+    }
+}
+
+pub fn vector_to_vertices(
+    vertices: &mut Vec<f32>,
+    pos: &nalgebra::Vector3<f32>,
+    v: &nalgebra::Vector3<f32>,
+    color: Option<(f32, f32, f32)>,
+    tip_size: f32,
+) {
+    fn add_vert(
+        vertices: &mut Vec<f32>,
+        v: &nalgebra::Vector3<f32>,
+        color: Option<(f32, f32, f32)>,
+    ) {
+        vertices.push(v.x);
+        vertices.push(v.y);
+        vertices.push(v.z);
+        if let Some(color) = color {
+            vertices.push(color.0);
+            vertices.push(color.1);
+            vertices.push(color.2);
+        }
+    }
+
+    // Main vector line
+    add_vert(vertices, pos, color);
+    add_vert(vertices, &(pos + v), color);
+
+    // Vector tip
+    add_vert(vertices, &(pos + v), color);
+    add_vert(
+        vertices,
+        &(pos + v + nalgebra::Vector3::new(tip_size, 0.0, 0.0)),
+        color,
+    );
+    add_vert(vertices, &(pos + v), color);
+    add_vert(
+        vertices,
+        &(pos + v + nalgebra::Vector3::new(-tip_size, 0.0, 0.0)),
+        color,
+    );
+    add_vert(vertices, &(pos + v), color);
+    add_vert(
+        vertices,
+        &(pos + v + nalgebra::Vector3::new(0.0, tip_size, 0.0)),
+        color,
+    );
+    add_vert(vertices, &(pos + v), color);
+    add_vert(
+        vertices,
+        &(pos + v + nalgebra::Vector3::new(0.0, -tip_size, 0.0)),
+        color,
+    );
+    add_vert(vertices, &(pos + v), color);
+    add_vert(
+        vertices,
+        &(pos + v + nalgebra::Vector3::new(0.0, 0.0, tip_size)),
+        color,
+    );
+    add_vert(vertices, &(pos + v), color);
+    add_vert(
+        vertices,
+        &(pos + v + nalgebra::Vector3::new(0.0, 0.0, -tip_size)),
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rigid_body() -> physsim::RigidBody<f32> {
+        physsim::RigidBody {
+            pos: nalgebra::Vector3::new(1.0, -2.0, 0.5),
+            lin_vel: nalgebra::Vector3::zeros(),
+            rot_mat: *nalgebra::Rotation3::from_euler_angles(0.3, 0.6, -0.9).matrix(),
+            ang_mom: nalgebra::Vector3::zeros(),
+            inv_ine: nalgebra::Matrix3::identity(),
+        }
+    }
+
+    // Positions of the vertices, relative to the body and in its frame
+    fn local_points(
+        vertices: &[f32],
+        rigid_body: &physsim::RigidBody<f32>,
+    ) -> Vec<nalgebra::Vector3<f32>> {
+        vertices
+            .chunks(3)
+            .map(|v| {
+                rigid_body.rot_mat.transpose()
+                    * (nalgebra::Vector3::new(v[0], v[1], v[2]) - rigid_body.pos)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cuboid_vertices_are_its_corners() {
+        let rigid_body = rigid_body();
+        let size = nalgebra::Vector3::new(0.4, 1.0, 2.0);
+        for (wireframe, count) in [(true, 24), (false, 36)] {
+            let mut vertices = Vec::new();
+            cuboid_to_vertices(&mut vertices, &rigid_body, &size, wireframe);
+            let points = local_points(&vertices, &rigid_body);
+            assert_eq!(points.len(), count);
+            for p in points {
+                let corner = p.component_div(&size);
+                assert!(corner.iter().all(|x| (x.abs() - 0.5).abs() < 1e-5), "{}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_vertices_lie_on_the_surface() {
+        let rigid_body = rigid_body();
+        for wireframe in [true, false] {
+            let mut vertices = Vec::new();
+            sphere_to_vertices(&mut vertices, &rigid_body, 0.75, wireframe);
+            assert!(!vertices.is_empty());
+            for p in local_points(&vertices, &rigid_body) {
+                assert!((p.norm() - 0.75).abs() < 1e-5, "{}", p);
+            }
+        }
+    }
+
+    #[test]
+    fn perpendicular_bases_are_orthonormal() {
+        for n in [
+            nalgebra::Vector3::x(),
+            nalgebra::Vector3::y(),
+            -nalgebra::Vector3::z(),
+            nalgebra::Vector3::new(1.0, 2.0, -3.0).normalize(),
+        ] {
+            let (u, v) = perpendicular_basis(&n);
+            assert!((u.norm() - 1.0).abs() < 1e-6 && (v.norm() - 1.0).abs() < 1e-6);
+            assert!(u.dot(&v).abs() < 1e-6 && u.dot(&n).abs() < 1e-6 && v.dot(&n).abs() < 1e-6);
+            // Right-handed
+            assert!((u.cross(&v) - n).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn vectors_end_at_their_tip() {
+        let mut vertices = Vec::new();
+        let pos = nalgebra::Vector3::new(1.0, 2.0, 3.0);
+        let v = nalgebra::Vector3::new(0.0, 0.0, 2.0);
+        vector_to_vertices(&mut vertices, &pos, &v, Some((1.0, 0.0, 0.0)), 0.1);
+        // Position and color per vertex
+        assert_eq!(vertices.len() % 6, 0);
+        assert_eq!(&vertices[0..6], &[1.0, 2.0, 3.0, 1.0, 0.0, 0.0]);
+        assert_eq!(&vertices[6..9], &[1.0, 2.0, 5.0]);
+    }
+}
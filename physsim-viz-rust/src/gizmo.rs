@@ -0,0 +1,408 @@
+use crate::body;
+use crate::geometry;
+use crate::history;
+
+// Handle length and ring radius relative to the body's bounding radius
+const GIZMO_SCALE: f32 = 1.6;
+const MIN_GIZMO_SIZE: f32 = 0.3;
+// How close the cursor ray has to pass a handle, relative to the gizmo size
+const HANDLE_TOLERANCE: f32 = 0.08;
+const RING_SEGMENTS: usize = 64;
+const AXIS_COLORS: [(f32, f32, f32); 3] = [(1.0, 0.2, 0.2), (0.2, 1.0, 0.2), (0.3, 0.4, 1.0)];
+const ACTIVE_COLOR: (f32, f32, f32) = (1.0, 1.0, 0.3);
+
+// Along or about one of the world axes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handle {
+    Translate(usize),
+    Rotate(usize),
+}
+
+// Increments edits snap to, zero for none
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapping {
+    pub grid: f32,
+    // Radians
+    pub angle: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self {
+            grid: 0.1,
+            angle: 15.0_f32.to_radians(),
+        }
+    }
+}
+
+fn size(body: &body::Body) -> f32 {
+    let radius = match body.shape {
+        body::Shape::Sphere { radius } => radius,
+        body::Shape::Cuboid { half_extents } => nalgebra::Vector3::from(half_extents).norm(),
+    };
+    (radius * GIZMO_SCALE).max(MIN_GIZMO_SIZE)
+}
+
+fn axis(i: usize) -> nalgebra::Vector3<f32> {
+    nalgebra::Vector3::ith(i, 1.0)
+}
+
+fn snap(x: f32, increment: f32) -> f32 {
+    if increment > 0.0 {
+        (x / increment).round() * increment
+    } else {
+        x
+    }
+}
+
+// Parameter of the point on the line center + s axis closest to the ray
+fn closest_on_axis(
+    center: &nalgebra::Vector3<f32>,
+    axis: &nalgebra::Vector3<f32>,
+    origin: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+) -> Option<(f32, f32)> {
+    let w = center - origin;
+    let b = axis.dot(direction);
+    let denominator = 1.0 - b * b;
+    if denominator < 1e-6 {
+        return None;
+    }
+    let (d, e) = (axis.dot(&w), direction.dot(&w));
+    let s = (b * e - d) / denominator;
+    let t = (e - b * d) / denominator;
+    if t < 0.0 {
+        return None;
+    }
+    let gap = ((center + axis * s) - (origin + direction * t)).norm();
+    Some((s, gap))
+}
+
+// Angle of the point where the ray crosses the ring's plane, and its distance
+// from the center
+fn angle_on_ring(
+    center: &nalgebra::Vector3<f32>,
+    normal: &nalgebra::Vector3<f32>,
+    origin: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+) -> Option<(f32, f32)> {
+    let denominator = direction.dot(normal);
+    if denominator.abs() < 1e-6 {
+        return None;
+    }
+    let t = (center - origin).dot(normal) / denominator;
+    if t < 0.0 {
+        return None;
+    }
+    let offset = origin + direction * t - center;
+    let (u, v) = geometry::perpendicular_basis(normal);
+    Some((offset.dot(&v).atan2(offset.dot(&u)), offset.norm()))
+}
+
+// Handle of the body's gizmo under the cursor ray, arrows before rings
+pub fn hit_handle(
+    body: &body::Body,
+    origin: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+) -> Option<Handle> {
+    let center = body.rigid_body.pos;
+    let size = size(body);
+    let tolerance = size * HANDLE_TOLERANCE;
+
+    let arrow = (0..3).find(
+        |&i| match closest_on_axis(&center, &axis(i), origin, direction) {
+            Some((s, gap)) => s >= 0.0 && s <= size && gap < tolerance,
+            None => false,
+        },
+    );
+    if let Some(i) = arrow {
+        return Some(Handle::Translate(i));
+    }
+    (0..3)
+        .find(
+            |&i| match angle_on_ring(&center, &axis(i), origin, direction) {
+                Some((_, distance)) => (distance - size).abs() < tolerance,
+                None => false,
+            },
+        )
+        .map(Handle::Rotate)
+}
+
+// An ongoing edit with a gizmo handle, relative to where it was grabbed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manipulation {
+    pub body: usize,
+    pub handle: Handle,
+    start: history::Pose,
+    // Grabbed axis parameter or ring angle
+    grabbed: f32,
+}
+
+impl Manipulation {
+    pub fn begin(
+        index: usize,
+        body: &body::Body,
+        handle: Handle,
+        origin: &nalgebra::Vector3<f32>,
+        direction: &nalgebra::Vector3<f32>,
+    ) -> Option<Self> {
+        let center = body.rigid_body.pos;
+        let grabbed = match handle {
+            Handle::Translate(i) => closest_on_axis(&center, &axis(i), origin, direction)?.0,
+            Handle::Rotate(i) => angle_on_ring(&center, &axis(i), origin, direction)?.0,
+        };
+        Some(Self {
+            body: index,
+            handle,
+            start: history::Pose::of(body),
+            grabbed,
+        })
+    }
+
+    // Moves or turns the body to follow the cursor ray. Translations snap the
+    // edited coordinate to the grid, rotations the angle turned.
+    pub fn update(
+        &self,
+        body: &mut body::Body,
+        origin: &nalgebra::Vector3<f32>,
+        direction: &nalgebra::Vector3<f32>,
+        snapping: &Snapping,
+    ) {
+        let center = self.start.pos;
+        match self.handle {
+            Handle::Translate(i) => {
+                if let Some((s, _)) = closest_on_axis(&center, &axis(i), origin, direction) {
+                    let mut pos = self.start.pos;
+                    pos[i] = snap(self.start.pos[i] + s - self.grabbed, snapping.grid);
+                    body.rigid_body.pos = pos;
+                }
+            }
+            Handle::Rotate(i) => {
+                if let Some((angle, _)) = angle_on_ring(&center, &axis(i), origin, direction) {
+                    let turned = snap(angle - self.grabbed, snapping.angle);
+                    let rotation = nalgebra::Rotation3::new(axis(i) * turned);
+                    body.rigid_body.rot_mat = rotation.matrix() * self.start.rot_mat;
+                }
+            }
+        }
+    }
+
+    // The edit made so far, if the body moved at all
+    pub fn finish(&self, body: &body::Body) -> Option<history::Edit> {
+        let after = history::Pose::of(body);
+        if after == self.start {
+            return None;
+        }
+        Some(history::Edit::Pose {
+            body: self.body,
+            before: self.start,
+            after,
+        })
+    }
+}
+
+// Translation arrows and rotation rings along the world axes around the body
+pub fn gizmo_to_vertices(vertices: &mut Vec<f32>, body: &body::Body, active: Option<Handle>) {
+    let center = body.rigid_body.pos;
+    let size = size(body);
+    for (i, color) in AXIS_COLORS.iter().enumerate() {
+        let color_of = |handle| {
+            if active == Some(handle) {
+                ACTIVE_COLOR
+            } else {
+                *color
+            }
+        };
+        geometry::vector_to_vertices(
+            vertices,
+            &center,
+            &(axis(i) * size),
+            Some(color_of(Handle::Translate(i))),
+            size * 0.1,
+        );
+
+        let (u, v) = geometry::perpendicular_basis(&axis(i));
+        let ring: Vec<nalgebra::Vector3<f32>> = (0..RING_SEGMENTS)
+            .map(|k| {
+                let angle = k as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + (u * angle.cos() + v * angle.sin()) * size
+            })
+            .collect();
+        geometry::loop_to_vertices(vertices, &ring, color_of(Handle::Rotate(i)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene;
+    use crate::simulation;
+
+    const CENTER: [f32; 3] = [1.0, 2.0, 3.0];
+    const NO_SNAPPING: Snapping = Snapping {
+        grid: 0.0,
+        angle: 0.0,
+    };
+
+    // A sphere of radius 0.5 at `CENTER`, whose gizmo has size 0.8
+    fn simulation() -> simulation::Simulation {
+        let text = format!(
+            r#"{{
+                "version": 1,
+                "bodies": [{{
+                    "name": "ball",
+                    "shape": {{ "type": "sphere", "radius": 0.5 }},
+                    "mass": 1.0,
+                    "pos": {:?}
+                }}]
+            }}"#,
+            CENTER
+        );
+        scene::Scene::parse(&text)
+            .unwrap()
+            .build_simulation()
+            .unwrap()
+    }
+
+    // Straight down onto the point, along the axis, from above it
+    fn ray_onto(
+        point: nalgebra::Vector3<f32>,
+        axis: nalgebra::Vector3<f32>,
+    ) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+        (point + axis * 5.0, -axis)
+    }
+
+    fn center() -> nalgebra::Vector3<f32> {
+        nalgebra::Vector3::from(CENTER)
+    }
+
+    #[test]
+    fn snaps_to_increments() {
+        assert!((snap(0.26, 0.1) - 0.3).abs() < 1e-6);
+        assert!((snap(-0.26, 0.1) + 0.3).abs() < 1e-6);
+        assert!((snap(0.4, 0.25) - 0.5).abs() < 1e-6);
+        assert_eq!(snap(0.26, 0.0), 0.26);
+        assert_eq!(snap(0.26, -1.0), 0.26);
+    }
+
+    #[test]
+    fn prefers_arrows_over_rings() {
+        let simulation = simulation();
+        let body = &simulation.bodies[0];
+        let hit = |point: nalgebra::Vector3<f32>| {
+            let (origin, direction) = ray_onto(center() + point, nalgebra::Vector3::z());
+            hit_handle(body, &origin, &direction)
+        };
+
+        // The tip of the x arrow lies on the z ring
+        assert_eq!(
+            hit(nalgebra::Vector3::new(0.8, 0.0, 0.0)),
+            Some(Handle::Translate(0))
+        );
+        assert_eq!(
+            hit(nalgebra::Vector3::new(0.4, 0.0, 0.0)),
+            Some(Handle::Translate(0))
+        );
+        let on_ring = nalgebra::Vector3::new(1.0, 1.0, 0.0).normalize() * 0.8;
+        assert_eq!(hit(on_ring), Some(Handle::Rotate(2)));
+        assert_eq!(hit(on_ring * 0.5), None);
+        assert_eq!(hit(on_ring * 2.0), None);
+    }
+
+    #[test]
+    fn translates_along_the_axis() {
+        let mut simulation = simulation();
+        let body = &mut simulation.bodies[0];
+        let along = |s: f32| {
+            ray_onto(
+                center() + nalgebra::Vector3::x() * s,
+                nalgebra::Vector3::z(),
+            )
+        };
+
+        let (origin, direction) = along(0.3);
+        let manipulation =
+            Manipulation::begin(0, body, Handle::Translate(0), &origin, &direction).unwrap();
+
+        let (origin, direction) = along(0.74);
+        manipulation.update(body, &origin, &direction, &NO_SNAPPING);
+        assert!((body.rigid_body.pos - nalgebra::Vector3::new(1.44, 2.0, 3.0)).norm() < 1e-5);
+
+        // The coordinate snaps, not the distance moved
+        manipulation.update(body, &origin, &direction, &Snapping::default());
+        assert!((body.rigid_body.pos - nalgebra::Vector3::new(1.4, 2.0, 3.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn rotates_counterclockwise_about_each_axis() {
+        for i in 0..3 {
+            let mut simulation = simulation();
+            let body = &mut simulation.bodies[0];
+            let (u, _) = geometry::perpendicular_basis(&axis(i));
+            let at = |degrees: f32| {
+                let turn = nalgebra::Rotation3::new(axis(i) * degrees.to_radians());
+                ray_onto(center() + turn * u * 0.8, axis(i))
+            };
+
+            let (origin, direction) = at(10.0);
+            let manipulation =
+                Manipulation::begin(0, body, Handle::Rotate(i), &origin, &direction).unwrap();
+            let turned = |body: &body::Body, degrees: f32| {
+                let expected = nalgebra::Rotation3::new(axis(i) * degrees.to_radians());
+                (body.rigid_body.rot_mat - expected.matrix()).norm() < 1e-5
+            };
+
+            let (origin, direction) = at(47.0);
+            manipulation.update(body, &origin, &direction, &NO_SNAPPING);
+            assert!(turned(body, 37.0), "axis {}", i);
+            manipulation.update(body, &origin, &direction, &Snapping::default());
+            assert!(turned(body, 30.0), "axis {}", i);
+            assert_eq!(body.rigid_body.pos, center());
+        }
+    }
+
+    #[test]
+    fn finishes_with_an_undoable_edit() {
+        let mut simulation = simulation();
+        let along = |s: f32| {
+            ray_onto(
+                center() + nalgebra::Vector3::y() * s,
+                nalgebra::Vector3::x(),
+            )
+        };
+        let (origin, direction) = along(0.2);
+        let manipulation = Manipulation::begin(
+            0,
+            &simulation.bodies[0],
+            Handle::Translate(1),
+            &origin,
+            &direction,
+        )
+        .unwrap();
+        let start = history::Pose::of(&simulation.bodies[0]);
+
+        manipulation.update(&mut simulation.bodies[0], &origin, &direction, &NO_SNAPPING);
+        assert!(manipulation.finish(&simulation.bodies[0]).is_none());
+
+        let (origin, direction) = along(0.7);
+        manipulation.update(&mut simulation.bodies[0], &origin, &direction, &NO_SNAPPING);
+        let moved = history::Pose::of(&simulation.bodies[0]);
+        let edit = manipulation.finish(&simulation.bodies[0]).unwrap();
+        match &edit {
+            history::Edit::Pose {
+                body,
+                before,
+                after,
+            } => assert_eq!((*body, before, after), (0, &start, &moved)),
+            _ => panic!("expected a pose edit"),
+        }
+        assert!((moved.pos - nalgebra::Vector3::new(1.0, 2.5, 3.0)).norm() < 1e-5);
+
+        let mut history = history::History::new();
+        history.record(edit);
+        history.undo(&mut simulation).unwrap();
+        assert_eq!(history::Pose::of(&simulation.bodies[0]), start);
+        history.redo(&mut simulation).unwrap();
+        assert_eq!(history::Pose::of(&simulation.bodies[0]), moved);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::body;
+use crate::scene;
+
+// Up to this many bodies the pairwise sum is both exact and cheaper than
+// building the tree
+const DIRECT_SUMMATION_LIMIT: usize = 64;
+// Guards against endless splitting of coincident bodies
+const MAX_TREE_DEPTH: usize = 24;
+
+// Newtonian attraction between every pair of bodies. The potential is
+// softened to -G m1 m2 / sqrt(r^2 + softening^2), which keeps close
+// encounters finite.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MutualGravity {
+    pub constant: f32,
+    #[serde(default)]
+    pub softening: f32,
+    // Barnes-Hut opening angle, zero for the exact pairwise sum
+    #[serde(default = "default_opening_angle")]
+    pub opening_angle: f32,
+}
+
+fn default_opening_angle() -> f32 {
+    0.5
+}
+
+impl MutualGravity {
+    pub fn validate(&self, path: &str) -> Result<(), scene::SceneError> {
+        let field = |name: &str| format!("{}.{}", path, name);
+        scene::check_non_negative(&field("constant"), self.constant)?;
+        scene::check_non_negative(&field("softening"), self.softening)?;
+        scene::check_non_negative(&field("opening_angle"), self.opening_angle)
+    }
+
+    // Kicks all velocities by the gravitational accelerations over `dt`
+    pub fn apply(&self, bodies: &mut [body::Body], dt: f32) {
+        let accelerations = self.accelerations(bodies);
+        for (body, acceleration) in bodies.iter_mut().zip(accelerations) {
+            body.rigid_body.lin_vel += acceleration * dt;
+        }
+    }
+
+    pub fn accelerations(&self, bodies: &[body::Body]) -> Vec<nalgebra::Vector3<f32>> {
+        if self.opening_angle == 0.0 || bodies.len() <= DIRECT_SUMMATION_LIMIT {
+            self.direct(bodies)
+        } else {
+            let tree = Octree::new(bodies);
+            bodies
+                .iter()
+                .enumerate()
+                .map(|(i, body)| {
+                    tree.acceleration(0, i, &body.rigid_body.pos, self, bodies) * self.constant
+                })
+                .collect()
+        }
+    }
+
+    pub fn potential_energy(&self, bodies: &[body::Body]) -> f64 {
+        let softening = (self.softening as f64).powi(2);
+        let mut energy = 0.0;
+        for (i, a) in bodies.iter().enumerate() {
+            for b in bodies[i + 1..].iter() {
+                let r = (b.rigid_body.pos - a.rigid_body.pos).cast::<f64>();
+                energy -= (a.mass as f64) * (b.mass as f64) / (r.norm_squared() + softening).sqrt();
+            }
+        }
+        energy * self.constant as f64
+    }
+
+    // Acceleration towards a point mass per unit G
+    fn pull(
+        &self,
+        pos: &nalgebra::Vector3<f32>,
+        source: &nalgebra::Vector3<f32>,
+        mass: f32,
+    ) -> nalgebra::Vector3<f32> {
+        let d = source - pos;
+        let r2 = d.norm_squared() + self.softening * self.softening;
+        if r2 <= f32::MIN_POSITIVE {
+            return nalgebra::Vector3::zeros();
+        }
+        d * (mass / (r2 * r2.sqrt()))
+    }
+
+    // Pairwise, so that momentum is conserved up to rounding
+    fn direct(&self, bodies: &[body::Body]) -> Vec<nalgebra::Vector3<f32>> {
+        let mut accelerations = vec![nalgebra::Vector3::zeros(); bodies.len()];
+        for (i, a) in bodies.iter().enumerate() {
+            for (j, b) in bodies.iter().enumerate().skip(i + 1) {
+                let pull = self.pull(&a.rigid_body.pos, &b.rigid_body.pos, 1.0) * self.constant;
+                accelerations[i] += pull * b.mass;
+                accelerations[j] -= pull * a.mass;
+            }
+        }
+        accelerations
+    }
+}
+
+struct Node {
+    center: nalgebra::Vector3<f32>,
+    half_size: f32,
+    mass: f32,
+    center_of_mass: nalgebra::Vector3<f32>,
+    // Leaves list their bodies, inner nodes their 8 children
+    bodies: Vec<usize>,
+    children: Option<[usize; 8]>,
+}
+
+// Barnes-Hut tree over the body positions, node 0 is the root
+struct Octree {
+    nodes: Vec<Node>,
+}
+
+impl Octree {
+    fn new(bodies: &[body::Body]) -> Self {
+        let (mut min, mut max) = (
+            nalgebra::Vector3::repeat(f32::INFINITY),
+            nalgebra::Vector3::repeat(f32::NEG_INFINITY),
+        );
+        for body in bodies {
+            min = min.inf(&body.rigid_body.pos);
+            max = max.sup(&body.rigid_body.pos);
+        }
+        let mut tree = Self {
+            nodes: vec![Node {
+                center: (min + max) / 2.0,
+                half_size: ((max - min).max() / 2.0).max(f32::EPSILON),
+                mass: 0.0,
+                center_of_mass: nalgebra::Vector3::zeros(),
+                bodies: Vec::new(),
+                children: None,
+            }],
+        };
+        for i in 0..bodies.len() {
+            tree.insert(0, i, bodies, 0);
+        }
+        tree.summarize(0, bodies);
+        tree
+    }
+
+    fn insert(&mut self, node: usize, body: usize, bodies: &[body::Body], depth: usize) {
+        if let Some(children) = self.nodes[node].children {
+            let octant = self.octant(node, &bodies[body].rigid_body.pos);
+            self.insert(children[octant], body, bodies, depth + 1);
+            return;
+        }
+
+        self.nodes[node].bodies.push(body);
+        if self.nodes[node].bodies.len() == 1 || depth >= MAX_TREE_DEPTH {
+            return;
+        }
+
+        let (center, half_size) = (self.nodes[node].center, self.nodes[node].half_size / 2.0);
+        let mut children = [0; 8];
+        for (octant, child) in children.iter_mut().enumerate() {
+            let offset = nalgebra::Vector3::new(
+                if octant & 1 != 0 { 1.0 } else { -1.0 },
+                if octant & 2 != 0 { 1.0 } else { -1.0 },
+                if octant & 4 != 0 { 1.0 } else { -1.0 },
+            );
+            *child = self.nodes.len();
+            self.nodes.push(Node {
+                center: center + offset * half_size,
+                half_size,
+                mass: 0.0,
+                center_of_mass: nalgebra::Vector3::zeros(),
+                bodies: Vec::new(),
+                children: None,
+            });
+        }
+        self.nodes[node].children = Some(children);
+        for moved in std::mem::take(&mut self.nodes[node].bodies) {
+            let octant = self.octant(node, &bodies[moved].rigid_body.pos);
+            self.insert(children[octant], moved, bodies, depth + 1);
+        }
+    }
+
+    fn octant(&self, node: usize, pos: &nalgebra::Vector3<f32>) -> usize {
+        let center = &self.nodes[node].center;
+        (pos.x >= center.x) as usize
+            | ((pos.y >= center.y) as usize) << 1
+            | ((pos.z >= center.z) as usize) << 2
+    }
+
+    fn summarize(&mut self, node: usize, bodies: &[body::Body]) {
+        let mut mass = 0.0;
+        let mut moment = nalgebra::Vector3::zeros();
+        match self.nodes[node].children {
+            Some(children) => {
+                for child in children {
+                    self.summarize(child, bodies);
+                    mass += self.nodes[child].mass;
+                    moment += self.nodes[child].center_of_mass * self.nodes[child].mass;
+                }
+            }
+            None => {
+                for &i in self.nodes[node].bodies.iter() {
+                    mass += bodies[i].mass;
+                    moment += bodies[i].rigid_body.pos * bodies[i].mass;
+                }
+            }
+        }
+        let node = &mut self.nodes[node];
+        node.mass = mass;
+        if mass > 0.0 {
+            node.center_of_mass = moment / mass;
+        }
+    }
+
+    // Per unit G, on body `index` at `pos`
+    fn acceleration(
+        &self,
+        node: usize,
+        index: usize,
+        pos: &nalgebra::Vector3<f32>,
+        gravity: &MutualGravity,
+        bodies: &[body::Body],
+    ) -> nalgebra::Vector3<f32> {
+        let current = &self.nodes[node];
+        if current.mass == 0.0 {
+            return nalgebra::Vector3::zeros();
+        }
+        match current.children {
+            None => current
+                .bodies
+                .iter()
+                .filter(|&&i| i != index)
+                .map(|&i| gravity.pull(pos, &bodies[i].rigid_body.pos, bodies[i].mass))
+                .sum(),
+            Some(children) => {
+                // Cells around the body itself are always opened, they would
+                // include its own mass
+                let outside = (pos - current.center).amax() > current.half_size;
+                let distance = (current.center_of_mass - pos).norm();
+                if outside && 2.0 * current.half_size < gravity.opening_angle * distance {
+                    gravity.pull(pos, &current.center_of_mass, current.mass)
+                } else {
+                    children
+                        .iter()
+                        .map(|&child| self.acceleration(child, index, pos, gravity, bodies))
+                        .sum()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics;
+
+    fn point_mass(pos: [f32; 3], lin_vel: [f32; 3], mass: f32) -> body::Body {
+        body::Body {
+            name: String::new(),
+            shape: body::Shape::Sphere { radius: 0.01 },
+            mass,
+            material: body::Material::default(),
+            rigid_body: physsim::RigidBody {
+                pos: pos.into(),
+                lin_vel: lin_vel.into(),
+                rot_mat: nalgebra::Matrix3::identity(),
+                ang_mom: nalgebra::Vector3::zeros(),
+                inv_ine: nalgebra::Matrix3::identity(),
+            },
+        }
+    }
+
+    // A fixed pseudo-random cluster, more bodies than are summed directly
+    fn cluster() -> Vec<body::Body> {
+        let mut state = 12345u32;
+        let mut next = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0
+        };
+        (0..4 * DIRECT_SUMMATION_LIMIT)
+            .map(|_| point_mass([next(), next(), next()], [0.0; 3], 1.5 + next()))
+            .collect()
+    }
+
+    #[test]
+    fn octree_approaches_direct_summation() {
+        let bodies = cluster();
+        let gravity = |opening_angle| MutualGravity {
+            constant: 1.0,
+            softening: 0.05,
+            opening_angle,
+        };
+        let exact = gravity(0.0).direct(&bodies);
+        let error = |opening_angle| {
+            gravity(opening_angle)
+                .accelerations(&bodies)
+                .iter()
+                .zip(exact.iter())
+                .map(|(a, b)| (a - b).norm() / b.norm())
+                .fold(0.0, f32::max)
+        };
+
+        let errors = [1.0, 0.5, 0.2, 0.05].map(error);
+        assert!(errors[1] < 0.05, "{:?}", errors);
+        assert!(
+            errors.windows(2).all(|pair| pair[1] < pair[0]),
+            "{:?}",
+            errors
+        );
+        assert!(errors[3] < 1e-4, "{:?}", errors);
+    }
+
+    #[test]
+    fn softened_energy_is_conserved() {
+        let gravity = MutualGravity {
+            constant: 1.0,
+            softening: 0.2,
+            opening_angle: 0.0,
+        };
+        // Passing right through each other, only finite with softening
+        let mut bodies = vec![
+            point_mass([-1.0, 0.0, 0.0], [0.0, 0.0, 0.0], 1.0),
+            point_mass([1.0, 0.0, 0.0], [0.0, 0.0, 0.0], 1.0),
+            point_mass([0.0, 3.0, 0.0], [0.4, 0.0, 0.0], 0.1),
+        ];
+        let energy = |bodies: &[body::Body]| {
+            dynamics::totals(bodies).energy + gravity.potential_energy(bodies)
+        };
+        let initial = energy(&bodies);
+
+        // Semi-implicit Euler only keeps the energy on average, it is off by
+        // O(dt) while the bodies pass each other
+        let dt = 2e-4;
+        let mut closest = f32::INFINITY;
+        for _ in 0..20_000 {
+            gravity.apply(&mut bodies, dt);
+            for body in bodies.iter_mut() {
+                body.rigid_body.step_sim(dt);
+            }
+            let distance = (bodies[0].rigid_body.pos - bodies[1].rigid_body.pos).norm();
+            closest = closest.min(distance);
+            let drift = ((energy(&bodies) - initial) / initial).abs();
+            let tolerance = if distance > 1.5 { 1e-4 } else { 1e-2 };
+            assert!(
+                drift < tolerance,
+                "drift {} at distance {}",
+                drift,
+                distance
+            );
+        }
+        // Through the softened core and back out
+        assert!(closest < 0.05, "{}", closest);
+        let distance = (bodies[0].rigid_body.pos - bodies[1].rigid_body.pos).norm();
+        assert!(distance > 1.5, "{}", distance);
+    }
+}
@@ -0,0 +1,642 @@
+use crate::body;
+use crate::forces;
+use crate::joints;
+use crate::simulation;
+use crate::springs;
+use crate::trails;
+
+// Oldest edits are forgotten beyond this
+const HISTORY_LIMIT: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub pos: nalgebra::Vector3<f32>,
+    pub rot_mat: nalgebra::Matrix3<f32>,
+}
+
+impl Pose {
+    pub fn of(body: &body::Body) -> Self {
+        Self {
+            pos: body.rigid_body.pos,
+            rot_mat: body.rigid_body.rot_mat,
+        }
+    }
+
+    fn apply_to(&self, body: &mut body::Body) {
+        body.rigid_body.pos = self.pos;
+        body.rigid_body.rot_mat = self.rot_mat;
+    }
+}
+
+// Angular momentum rather than velocity, so that it survives changes to the
+// mass properties unchanged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity {
+    pub lin_vel: nalgebra::Vector3<f32>,
+    pub ang_mom: nalgebra::Vector3<f32>,
+}
+
+impl Velocity {
+    pub fn of(body: &body::Body) -> Self {
+        Self {
+            lin_vel: body.rigid_body.lin_vel,
+            ang_mom: body.rigid_body.ang_mom,
+        }
+    }
+
+    fn apply_to(&self, body: &mut body::Body) {
+        body.rigid_body.lin_vel = self.lin_vel;
+        body.rigid_body.ang_mom = self.ang_mom;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MassProperties {
+    pub mass: f32,
+    // Body frame
+    pub inv_ine: nalgebra::Matrix3<f32>,
+}
+
+impl MassProperties {
+    pub fn of(body: &body::Body) -> Self {
+        Self {
+            mass: body.mass,
+            inv_ine: body.rigid_body.inv_ine,
+        }
+    }
+
+    fn apply_to(&self, body: &mut body::Body) {
+        body.mass = self.mass;
+        body.rigid_body.inv_ine = self.inv_ine;
+    }
+}
+
+// A body together with everything that refers to it, each at its index in
+// the simulation's lists, or the list of trails, before the removal
+#[derive(Clone)]
+pub struct Removed {
+    body: body::Body,
+    forces: Vec<(usize, forces::ForceGenerator)>,
+    joints: Vec<(usize, joints::Joint)>,
+    springs: Vec<(usize, springs::Spring)>,
+    trails: Vec<(usize, trails::Trail)>,
+}
+
+impl Removed {
+    fn of(simulation: &simulation::Simulation, trails: &[trails::Trail], index: usize) -> Self {
+        fn referring<T: Clone>(
+            items: &[T],
+            index: usize,
+            indices: impl Fn(&mut T) -> Vec<&mut usize>,
+        ) -> Vec<(usize, T)> {
+            items
+                .iter()
+                .cloned()
+                .enumerate()
+                .filter(|(_, item)| involves(indices(&mut item.clone()), index))
+                .collect()
+        }
+
+        Self {
+            body: simulation.bodies[index].clone(),
+            forces: referring(&simulation.forces, index, |force| force.body_indices_mut()),
+            joints: referring(&simulation.joints, index, |joint| joint.body_indices_mut()),
+            springs: referring(&simulation.springs, index, |spring| {
+                spring.body_indices_mut()
+            }),
+            trails: trails
+                .iter()
+                .enumerate()
+                .filter(|(_, trail)| trail.body == index)
+                .map(|(i, trail)| (i, trail.clone()))
+                .collect(),
+        }
+    }
+}
+
+// How the list of bodies changed, for state kept per body elsewhere
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyChange {
+    Inserted(usize),
+    Removed(usize),
+    // Pose, velocity or mass properties set, rather than simulated
+    Changed(usize),
+}
+
+// A change to the simulation that knows how to revert itself
+#[derive(Clone)]
+pub enum Edit {
+    Pose {
+        body: usize,
+        before: Pose,
+        after: Pose,
+    },
+    Velocity {
+        body: usize,
+        before: Velocity,
+        after: Velocity,
+    },
+    MassProperties {
+        body: usize,
+        before: MassProperties,
+        after: MassProperties,
+    },
+    Material {
+        body: usize,
+        before: body::Material,
+        after: body::Material,
+    },
+    Forces {
+        before: Vec<forces::ForceGenerator>,
+        after: Vec<forces::ForceGenerator>,
+    },
+    // Appended after the existing bodies
+    AddBody {
+        body: body::Body,
+    },
+    RemoveBody {
+        index: usize,
+        removed: Removed,
+    },
+}
+
+impl Edit {
+    pub fn set_pose(simulation: &simulation::Simulation, body: usize, after: Pose) -> Self {
+        Edit::Pose {
+            body,
+            before: Pose::of(&simulation.bodies[body]),
+            after,
+        }
+    }
+
+    pub fn set_velocity(simulation: &simulation::Simulation, body: usize, after: Velocity) -> Self {
+        Edit::Velocity {
+            body,
+            before: Velocity::of(&simulation.bodies[body]),
+            after,
+        }
+    }
+
+    pub fn set_mass_properties(
+        simulation: &simulation::Simulation,
+        body: usize,
+        after: MassProperties,
+    ) -> Self {
+        Edit::MassProperties {
+            body,
+            before: MassProperties::of(&simulation.bodies[body]),
+            after,
+        }
+    }
+
+    pub fn set_material(
+        simulation: &simulation::Simulation,
+        body: usize,
+        after: body::Material,
+    ) -> Self {
+        Edit::Material {
+            body,
+            before: simulation.bodies[body].material,
+            after,
+        }
+    }
+
+    pub fn set_forces(
+        simulation: &simulation::Simulation,
+        after: Vec<forces::ForceGenerator>,
+    ) -> Self {
+        Edit::Forces {
+            before: simulation.forces.clone(),
+            after,
+        }
+    }
+
+    // Also removes the forces, joints, springs and trails that involve the body
+    pub fn remove_body(
+        simulation: &simulation::Simulation,
+        trails: &[trails::Trail],
+        index: usize,
+    ) -> Self {
+        Edit::RemoveBody {
+            index,
+            removed: Removed::of(simulation, trails, index),
+        }
+    }
+
+    // How applying or reverting the edit changes the list of bodies
+    pub fn body_change(
+        &self,
+        simulation: &simulation::Simulation,
+        reverted: bool,
+    ) -> Option<BodyChange> {
+        match (self, reverted) {
+            // Applied edits have already happened
+            (Edit::AddBody { .. }, false) => {
+                Some(BodyChange::Inserted(simulation.bodies.len() - 1))
+            }
+            (Edit::AddBody { .. }, true) => Some(BodyChange::Removed(simulation.bodies.len())),
+            (Edit::RemoveBody { index, .. }, false) => Some(BodyChange::Removed(*index)),
+            (Edit::RemoveBody { index, .. }, true) => Some(BodyChange::Inserted(*index)),
+            (Edit::Pose { body, .. }, _)
+            | (Edit::Velocity { body, .. }, _)
+            | (Edit::MassProperties { body, .. }, _) => Some(BodyChange::Changed(*body)),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, simulation: &mut simulation::Simulation) {
+        match self {
+            Edit::Pose { body, after, .. } => after.apply_to(&mut simulation.bodies[*body]),
+            Edit::Velocity { body, after, .. } => after.apply_to(&mut simulation.bodies[*body]),
+            Edit::MassProperties { body, after, .. } => {
+                after.apply_to(&mut simulation.bodies[*body])
+            }
+            Edit::Material { body, after, .. } => simulation.bodies[*body].material = *after,
+            Edit::Forces { after, .. } => simulation.forces = after.clone(),
+            Edit::AddBody { body } => {
+                simulation.bodies.push(body.clone());
+                simulation.contacts.clear();
+            }
+            Edit::RemoveBody { index, .. } => remove_body(simulation, *index),
+        }
+    }
+
+    pub fn revert(&self, simulation: &mut simulation::Simulation) {
+        match self {
+            Edit::Pose { body, before, .. } => before.apply_to(&mut simulation.bodies[*body]),
+            Edit::Velocity { body, before, .. } => before.apply_to(&mut simulation.bodies[*body]),
+            Edit::MassProperties { body, before, .. } => {
+                before.apply_to(&mut simulation.bodies[*body])
+            }
+            Edit::Material { body, before, .. } => simulation.bodies[*body].material = *before,
+            Edit::Forces { before, .. } => simulation.forces = before.clone(),
+            Edit::AddBody { .. } => {
+                simulation.bodies.pop();
+                simulation.contacts.clear();
+            }
+            Edit::RemoveBody { index, removed } => insert_body(simulation, *index, removed),
+        }
+    }
+
+    // Trails are kept apart from the simulation, but follow its bodies. These
+    // go along with `apply` and `revert`, after them.
+    pub fn apply_to_trails(&self, trails: &mut Vec<trails::Trail>) {
+        if let Edit::RemoveBody { index, .. } = self {
+            remove_trails(trails, *index);
+        }
+    }
+
+    pub fn revert_trails(
+        &self,
+        simulation: &simulation::Simulation,
+        trails: &mut Vec<trails::Trail>,
+    ) {
+        match self {
+            Edit::AddBody { .. } => remove_trails(trails, simulation.bodies.len()),
+            Edit::RemoveBody { index, removed } => {
+                for trail in trails.iter_mut() {
+                    if trail.body >= *index {
+                        trail.body += 1;
+                    }
+                }
+                for (i, trail) in removed.trails.iter() {
+                    trails.insert(*i, trail.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn involves(indices: Vec<&mut usize>, body: usize) -> bool {
+    indices.iter().any(|i| **i == body)
+}
+
+// Renumbers the body references of every force, joint and spring
+fn remap_bodies(simulation: &mut simulation::Simulation, remap: impl Fn(usize) -> usize) {
+    let indices = simulation
+        .forces
+        .iter_mut()
+        .flat_map(|force| force.body_indices_mut())
+        .chain(
+            simulation
+                .joints
+                .iter_mut()
+                .flat_map(|joint| joint.body_indices_mut()),
+        )
+        .chain(
+            simulation
+                .springs
+                .iter_mut()
+                .flat_map(|spring| spring.body_indices_mut()),
+        );
+    for index in indices {
+        *index = remap(*index);
+    }
+}
+
+fn remove_body(simulation: &mut simulation::Simulation, index: usize) {
+    simulation.bodies.remove(index);
+    simulation
+        .forces
+        .retain_mut(|force| !involves(force.body_indices_mut(), index));
+    simulation
+        .joints
+        .retain_mut(|joint| !involves(joint.body_indices_mut(), index));
+    simulation
+        .springs
+        .retain_mut(|spring| !involves(spring.body_indices_mut(), index));
+    remap_bodies(simulation, |i| if i > index { i - 1 } else { i });
+    simulation.contacts.clear();
+}
+
+fn remove_trails(trails: &mut Vec<trails::Trail>, index: usize) {
+    trails.retain(|trail| trail.body != index);
+    for trail in trails.iter_mut() {
+        if trail.body > index {
+            trail.body -= 1;
+        }
+    }
+}
+
+fn insert_body(simulation: &mut simulation::Simulation, index: usize, removed: &Removed) {
+    simulation.bodies.insert(index, removed.body.clone());
+    remap_bodies(simulation, |i| if i >= index { i + 1 } else { i });
+    // Ascending, so each lands where it was
+    for (i, force) in removed.forces.iter() {
+        simulation.forces.insert(*i, force.clone());
+    }
+    for (i, joint) in removed.joints.iter() {
+        simulation.joints.insert(*i, joint.clone());
+    }
+    for (i, spring) in removed.springs.iter() {
+        simulation.springs.insert(*i, spring.clone());
+    }
+    simulation.contacts.clear();
+}
+
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    // Applies the edit and adds it
+    pub fn execute(&mut self, edit: Edit, simulation: &mut simulation::Simulation) -> &Edit {
+        edit.apply(simulation);
+        self.record(edit);
+        self.undo.last().unwrap()
+    }
+
+    // Adds an edit that has already been applied
+    pub fn record(&mut self, edit: Edit) {
+        if self.undo.len() == HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    // The edit that was reverted, if there was anything to undo
+    pub fn undo(&mut self, simulation: &mut simulation::Simulation) -> Option<&Edit> {
+        let edit = self.undo.pop()?;
+        edit.revert(simulation);
+        self.redo.push(edit);
+        self.redo.last()
+    }
+
+    pub fn redo(&mut self, simulation: &mut simulation::Simulation) -> Option<&Edit> {
+        let edit = self.redo.pop()?;
+        edit.apply(simulation);
+        self.undo.push(edit);
+        self.undo.last()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene;
+
+    const SCENE: &str = r#"{
+        "version": 1,
+        "bodies": [
+            { "name": "a", "shape": { "type": "sphere", "radius": 0.2 }, "mass": 1.0 },
+            {
+                "name": "b",
+                "shape": { "type": "sphere", "radius": 0.2 },
+                "mass": 1.0,
+                "pos": [1.0, 0.0, 0.0]
+            },
+            {
+                "name": "c",
+                "shape": { "type": "sphere", "radius": 0.2 },
+                "mass": 1.0,
+                "pos": [2.0, 0.0, 0.0]
+            }
+        ],
+        "forces": [
+            { "type": "gravity", "acceleration": [0.0, -9.81, 0.0] },
+            { "type": "constant_force", "body": 1, "force": [1.0, 0.0, 0.0] },
+            { "type": "constant_torque", "body": 2, "torque": [0.0, 1.0, 0.0] }
+        ],
+        "joints": [
+            { "body": 0, "other": 1, "kind": { "type": "ball" } },
+            { "body": 2, "kind": { "type": "distance" } }
+        ],
+        "springs": [
+            { "body": 1, "other": 2, "rest_length": 1.0, "stiffness": 10.0 },
+            { "body": 0, "other": 2, "rest_length": 2.0, "stiffness": 5.0 }
+        ]
+    }"#;
+
+    fn simulation() -> simulation::Simulation {
+        scene::Scene::parse(SCENE)
+            .unwrap()
+            .build_simulation()
+            .unwrap()
+    }
+
+    fn names(simulation: &simulation::Simulation) -> Vec<&str> {
+        simulation
+            .bodies
+            .iter()
+            .map(|body| body.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn removing_a_body_round_trips() {
+        let original = simulation();
+        for index in 0..original.bodies.len() {
+            let mut simulation = simulation();
+            let mut history = History::new();
+
+            let edit = Edit::remove_body(&simulation, &[], index);
+            let change = history
+                .execute(edit, &mut simulation)
+                .body_change(&simulation, false);
+            assert_eq!(change, Some(BodyChange::Removed(index)));
+            assert_eq!(simulation.bodies.len(), 2);
+            assert!(simulation
+                .forces
+                .iter_mut()
+                .all(|force| force.body_indices_mut().iter().all(|i| **i < 2)));
+            assert!(simulation
+                .joints
+                .iter_mut()
+                .all(|joint| joint.body_indices_mut().iter().all(|i| **i < 2)));
+            assert!(simulation
+                .springs
+                .iter_mut()
+                .all(|spring| spring.body_indices_mut().iter().all(|i| **i < 2)));
+            let removed = (
+                names(&simulation).join(","),
+                simulation.forces.clone(),
+                simulation.joints.clone(),
+                simulation.springs.clone(),
+            );
+
+            let change = history
+                .undo(&mut simulation)
+                .unwrap()
+                .body_change(&simulation, true);
+            assert_eq!(change, Some(BodyChange::Inserted(index)));
+            assert_eq!(names(&simulation), names(&original));
+            assert_eq!(simulation.forces, original.forces);
+            assert_eq!(simulation.joints, original.joints);
+            assert_eq!(simulation.springs, original.springs);
+
+            history.redo(&mut simulation).unwrap();
+            let redone = (
+                names(&simulation).join(","),
+                simulation.forces.clone(),
+                simulation.joints.clone(),
+                simulation.springs.clone(),
+            );
+            assert!(redone == removed);
+        }
+    }
+
+    #[test]
+    fn removing_a_body_keeps_its_trails_for_undo() {
+        let mut simulation = simulation();
+        let mut trails: Vec<trails::Trail> = [0, 1, 2, 1]
+            .iter()
+            .map(|&body| {
+                trails::Trail::new(body, nalgebra::Vector3::zeros(), 10, 1, (1.0, 1.0, 1.0))
+            })
+            .collect();
+        for step in 0..3 {
+            trails[1].record(step, &simulation.bodies[1].rigid_body);
+        }
+        let bodies = |trails: &[trails::Trail]| -> Vec<usize> {
+            trails.iter().map(|trail| trail.body).collect()
+        };
+        let mut history = History::new();
+
+        let edit = Edit::remove_body(&simulation, &trails, 1);
+        history
+            .execute(edit, &mut simulation)
+            .apply_to_trails(&mut trails);
+        assert_eq!(bodies(&trails), [0, 1]);
+
+        history
+            .undo(&mut simulation)
+            .unwrap()
+            .revert_trails(&simulation, &mut trails);
+        assert_eq!(bodies(&trails), [0, 1, 2, 1]);
+        let mut vertices = Vec::new();
+        let ranges = trails::trails_to_vertices(&mut vertices, &trails);
+        assert_eq!(ranges[1].1, 3);
+
+        history
+            .redo(&mut simulation)
+            .unwrap()
+            .apply_to_trails(&mut trails);
+        assert_eq!(bodies(&trails), [0, 1]);
+    }
+
+    #[test]
+    fn undoing_an_added_body_drops_its_trails() {
+        let mut simulation = simulation();
+        let mut history = History::new();
+        let body = simulation.bodies[0].clone();
+        history.execute(Edit::AddBody { body }, &mut simulation);
+        let mut trails = vec![
+            trails::Trail::new(3, nalgebra::Vector3::zeros(), 10, 1, (1.0, 1.0, 1.0)),
+            trails::Trail::new(2, nalgebra::Vector3::zeros(), 10, 1, (1.0, 1.0, 1.0)),
+        ];
+
+        history
+            .undo(&mut simulation)
+            .unwrap()
+            .revert_trails(&simulation, &mut trails);
+        assert_eq!(trails.len(), 1);
+        assert_eq!(trails[0].body, 2);
+    }
+
+    #[test]
+    fn removing_the_middle_body_renumbers_the_rest() {
+        let mut simulation = simulation();
+        Edit::remove_body(&simulation, &[], 1).apply(&mut simulation);
+        assert_eq!(names(&simulation), ["a", "c"]);
+        // Only gravity and the torque on c are left, the torque now on body 1
+        assert_eq!(simulation.forces.len(), 2);
+        assert_eq!(simulation.forces[1].body_indices_mut(), [&mut 1]);
+        assert_eq!(simulation.joints.len(), 1);
+        assert_eq!(simulation.joints[0].body, 1);
+        assert_eq!(simulation.springs.len(), 1);
+        assert_eq!(
+            (simulation.springs[0].body, simulation.springs[0].other),
+            (0, Some(1))
+        );
+    }
+
+    #[test]
+    fn body_edits_report_the_changed_body() {
+        let mut simulation = simulation();
+        let mut history = History::new();
+        let mut pose = Pose::of(&simulation.bodies[2]);
+        pose.pos.y = 1.0;
+        let edit = Edit::set_pose(&simulation, 2, pose);
+        let change = history
+            .execute(edit, &mut simulation)
+            .body_change(&simulation, false);
+        assert_eq!(change, Some(BodyChange::Changed(2)));
+        assert_eq!(simulation.bodies[2].rigid_body.pos.y, 1.0);
+
+        let change = history
+            .undo(&mut simulation)
+            .unwrap()
+            .body_change(&simulation, true);
+        assert_eq!(change, Some(BodyChange::Changed(2)));
+        assert_eq!(simulation.bodies[2].rigid_body.pos.y, 0.0);
+    }
+}
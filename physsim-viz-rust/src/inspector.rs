@@ -0,0 +1,305 @@
+use crate::body;
+use crate::dynamics;
+use crate::scene;
+
+use wasm_bindgen::prelude::*;
+
+const PANEL_ID: &str = "physsim-viz-inspector";
+const PANEL_STYLE: &str = "font-family: monospace; font-size: 12px; padding: 4px; \
+                           border: 1px solid #888; display: inline-block";
+const INPUT_STYLE: &str = "width: 7em; font-family: monospace";
+
+// Quantities of the selected body the panel lets the user edit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Pos,
+    LinVel,
+    // Degrees, R = Rz(yaw) Ry(pitch) Rx(roll) given as (roll, pitch, yaw)
+    Euler,
+    // [w, x, y, z]
+    Quaternion,
+    AngMom,
+    AngVel,
+    // Body frame, row-major
+    InvIne,
+}
+
+impl Field {
+    const ALL: [Field; 7] = [
+        Field::Pos,
+        Field::LinVel,
+        Field::Euler,
+        Field::Quaternion,
+        Field::AngMom,
+        Field::AngVel,
+        Field::InvIne,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Pos => "pos",
+            Field::LinVel => "lin_vel",
+            Field::Euler => "euler",
+            Field::Quaternion => "quaternion",
+            Field::AngMom => "ang_mom",
+            Field::AngVel => "ang_vel",
+            Field::InvIne => "inv_ine",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Field::ALL
+            .iter()
+            .copied()
+            .find(|field| field.name() == name)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Pos => "pos",
+            Field::LinVel => "lin_vel",
+            Field::Euler => "euler (xyz, °)",
+            Field::Quaternion => "quaternion (w, x, y, z)",
+            Field::AngMom => "ang_mom",
+            Field::AngVel => "ω",
+            Field::InvIne => "inv_ine",
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Field::Quaternion => 4,
+            Field::InvIne => 9,
+            _ => 3,
+        }
+    }
+
+    pub fn values(self, body: &body::Body) -> Vec<f32> {
+        let rigid_body = &body.rigid_body;
+        match self {
+            Field::Pos => rigid_body.pos.iter().copied().collect(),
+            Field::LinVel => rigid_body.lin_vel.iter().copied().collect(),
+            Field::Euler => {
+                let (roll, pitch, yaw) =
+                    nalgebra::Rotation3::from_matrix_unchecked(rigid_body.rot_mat).euler_angles();
+                vec![roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees()]
+            }
+            Field::Quaternion => {
+                let q = nalgebra::UnitQuaternion::from_matrix(&rigid_body.rot_mat);
+                vec![q.w, q.i, q.j, q.k]
+            }
+            Field::AngMom => rigid_body.ang_mom.iter().copied().collect(),
+            Field::AngVel => dynamics::angular_velocity(rigid_body)
+                .iter()
+                .copied()
+                .collect(),
+            Field::InvIne => rigid_body.inv_ine.transpose().iter().copied().collect(),
+        }
+    }
+
+    // Writes the entered values into the body's description. Angular velocity
+    // is turned into momentum and the inverse inertia into inertia, the
+    // description's checks then apply as usual.
+    pub fn write(
+        self,
+        values: &[f32],
+        body: &body::Body,
+        spec: &mut scene::BodySpec,
+    ) -> Result<(), String> {
+        if values.len() != self.len() {
+            return Err(format!("{} needs {} numbers", self.name(), self.len()));
+        }
+        let vector = || nalgebra::Vector3::new(values[0], values[1], values[2]);
+        match self {
+            Field::Pos => spec.pos = vector().into(),
+            Field::LinVel => spec.lin_vel = vector().into(),
+            Field::Euler => {
+                let angles = vector().map(f32::to_radians);
+                let q = nalgebra::UnitQuaternion::from_euler_angles(angles.x, angles.y, angles.z);
+                spec.orientation = [q.w, q.i, q.j, q.k];
+            }
+            Field::Quaternion => spec.orientation = [values[0], values[1], values[2], values[3]],
+            Field::AngMom => spec.ang_mom = vector().into(),
+            Field::AngVel => {
+                let rot_mat = &body.rigid_body.rot_mat;
+                let inertia = body
+                    .rigid_body
+                    .inv_ine
+                    .try_inverse()
+                    .ok_or("inv_ine isn't invertible")?;
+                spec.ang_mom = (rot_mat * inertia * rot_mat.transpose() * vector()).into();
+            }
+            Field::InvIne => {
+                let inertia = nalgebra::Matrix3::from_row_slice(values)
+                    .try_inverse()
+                    .ok_or("inv_ine must be invertible")?;
+                spec.inertia = Some([
+                    [inertia[(0, 0)], inertia[(0, 1)], inertia[(0, 2)]],
+                    [inertia[(1, 0)], inertia[(1, 1)], inertia[(1, 2)]],
+                    [inertia[(2, 0)], inertia[(2, 1)], inertia[(2, 2)]],
+                ]);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Panel next to the canvas showing the selected body's state. Edits are
+// committed by the inputs' change events, which bubble up to the panel.
+pub struct Inspector {
+    document: web_sys::Document,
+    panel: web_sys::HtmlElement,
+    title: web_sys::Element,
+    table: web_sys::HtmlElement,
+    inputs: Vec<(Field, Vec<web_sys::HtmlInputElement>)>,
+    rot_mat: Vec<web_sys::Element>,
+    kinetic_energy: web_sys::Element,
+    status: web_sys::Element,
+}
+
+impl Inspector {
+    // Adds the hidden panel after the canvas, in place of one left behind by
+    // an earlier runner
+    pub fn new(
+        document: &web_sys::Document,
+        canvas: &web_sys::HtmlCanvasElement,
+    ) -> Result<Self, JsValue> {
+        let element = |tag: &str| document.create_element(tag);
+        let row = |table: &web_sys::Element, label: &str| -> Result<web_sys::Element, JsValue> {
+            let row = element("tr")?;
+            let header = element("th")?;
+            header.set_text_content(Some(label));
+            header.set_attribute("style", "text-align: left")?;
+            row.append_child(&header)?;
+            table.append_child(&row)?;
+            Ok(row)
+        };
+
+        if let Some(stale) = document.get_element_by_id(PANEL_ID) {
+            stale.remove();
+        }
+        let panel = element("div")?.dyn_into::<web_sys::HtmlElement>()?;
+        panel.set_id(PANEL_ID);
+        panel.set_attribute("style", PANEL_STYLE)?;
+        panel.set_hidden(true);
+        let title = element("div")?;
+        panel.append_child(&title)?;
+        let table = element("table")?.dyn_into::<web_sys::HtmlElement>()?;
+        panel.append_child(&table)?;
+        let status = element("div")?;
+        status.set_attribute("style", "color: #c00")?;
+        panel.append_child(&status)?;
+
+        let mut inputs = Vec::new();
+        for field in Field::ALL {
+            let mut field_inputs = Vec::new();
+            // Matrices get a row each
+            for component in 0..field.len() {
+                if component % 3 == 0 && (field == Field::InvIne || component == 0) {
+                    let label = if component == 0 { field.label() } else { "" };
+                    row(&table, label)?;
+                }
+                let input = element("input")?.dyn_into::<web_sys::HtmlInputElement>()?;
+                input.set_type("number");
+                input.set_step("any");
+                input.set_attribute("style", INPUT_STYLE)?;
+                input.set_attribute("data-field", field.name())?;
+                let cell = element("td")?;
+                cell.append_child(&input)?;
+                table.last_element_child().unwrap().append_child(&cell)?;
+                field_inputs.push(input);
+            }
+            inputs.push((field, field_inputs));
+        }
+
+        let mut rot_mat = Vec::new();
+        for i in 0..3 {
+            let row = row(&table, if i == 0 { "rot_mat" } else { "" })?;
+            for _ in 0..3 {
+                let cell = element("td")?;
+                row.append_child(&cell)?;
+                rot_mat.push(cell);
+            }
+        }
+        let kinetic_energy = element("td")?;
+        row(&table, "kinetic energy")?.append_child(&kinetic_energy)?;
+
+        canvas.after_with_node_1(&panel)?;
+
+        Ok(Self {
+            document: document.clone(),
+            panel,
+            title,
+            table,
+            inputs,
+            rot_mat,
+            kinetic_energy,
+            status,
+        })
+    }
+
+    pub fn panel(&self) -> &web_sys::HtmlElement {
+        &self.panel
+    }
+
+    // Refreshes the shown values. The input being typed into is left alone,
+    // and inputs are disabled unless the body can be edited.
+    pub fn update(&self, shown: bool, selected: Option<(usize, &body::Body)>, editable: bool) {
+        self.panel.set_hidden(!shown);
+        if !shown {
+            return;
+        }
+
+        let (index, body) = match selected {
+            Some(selected) => selected,
+            None => {
+                self.title.set_text_content(Some("No body selected"));
+                self.table.set_hidden(true);
+                return;
+            }
+        };
+        self.title
+            .set_text_content(Some(&format!("{}: {}", index, body.name)));
+        self.table.set_hidden(false);
+
+        let focused = self.document.active_element();
+        for (field, inputs) in self.inputs.iter() {
+            for (input, value) in inputs.iter().zip(field.values(body)) {
+                input.set_disabled(!editable);
+                if focused.as_ref() != Some(AsRef::<web_sys::Element>::as_ref(input)) {
+                    input.set_value(&format!("{:.6}", value));
+                }
+            }
+        }
+        for (cell, value) in self
+            .rot_mat
+            .iter()
+            .zip(body.rigid_body.rot_mat.transpose().iter())
+        {
+            cell.set_text_content(Some(&format!("{:.6}", value)));
+        }
+        self.kinetic_energy.set_text_content(Some(&format!(
+            "{:.6e}",
+            dynamics::kinetic_energy(&body.rigid_body, body.mass)
+        )));
+    }
+
+    // Shows why the last edit was rejected, or clears it
+    pub fn set_status(&self, message: &str) {
+        self.status.set_text_content(Some(message));
+    }
+
+    // The field an input belongs to and the numbers entered into it
+    pub fn entered(&self, target: &web_sys::EventTarget) -> Option<(Field, Vec<f32>)> {
+        let field = target
+            .dyn_ref::<web_sys::Element>()?
+            .get_attribute("data-field")
+            .and_then(|name| Field::from_name(&name))?;
+        let (_, inputs) = self.inputs.iter().find(|(f, _)| *f == field)?;
+        let values = inputs
+            .iter()
+            .map(|input| input.value().trim().parse::<f32>().ok())
+            .collect::<Option<Vec<f32>>>()?;
+        Some((field, values))
+    }
+}
@@ -0,0 +1,676 @@
+use crate::body;
+use crate::dynamics;
+use crate::geometry;
+use crate::overlays;
+use crate::scene;
+
+// Sequential impulse passes over all joint rows per substep
+const JOINT_ITERATIONS: usize = 10;
+// Fraction of the position error fed back into the velocity targets per
+// substep
+const BAUMGARTE: f32 = 0.2;
+
+const GIZMO_MARKER_SIZE: f32 = 0.06;
+
+// Connects `body` to `other` (or the world). Anchors and axes are given in the
+// frame of the body they belong to, world coordinates for the world. Missing
+// values of the other side are filled in from the initial poses, so that the
+// joint starts out satisfied.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Joint {
+    pub body: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other: Option<usize>,
+    #[serde(default)]
+    pub anchor: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other_anchor: Option<[f32; 3]>,
+    pub kind: JointKind,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum JointKind {
+    // Anchors coincide, any relative rotation
+    Ball {},
+    // Anchors coincide, rotation only about the axis
+    Hinge {
+        axis: [f32; 3],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        other_axis: Option<[f32; 3]>,
+    },
+    // Translation only along the axis, no relative rotation
+    Slider {
+        axis: [f32; 3],
+        // Orientation [w, x, y, z] of the other side in the body's frame
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rotation: Option<[f32; 4]>,
+    },
+    // No relative motion at all
+    Fixed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rotation: Option<[f32; 4]>,
+    },
+    // Anchors keep their distance, the initial one unless given
+    Distance {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        length: Option<f32>,
+    },
+}
+
+// World-frame pose of the other side of a joint
+struct Side<'a> {
+    body: Option<&'a body::Body>,
+}
+
+impl Side<'_> {
+    fn rot_mat(&self) -> nalgebra::Matrix3<f32> {
+        match self.body {
+            Some(body) => body.rigid_body.rot_mat,
+            None => nalgebra::Matrix3::identity(),
+        }
+    }
+
+    fn to_world(&self, local: &[f32; 3]) -> nalgebra::Vector3<f32> {
+        match self.body {
+            Some(body) => {
+                body.rigid_body.pos + body.rigid_body.rot_mat * nalgebra::Vector3::from(*local)
+            }
+            None => nalgebra::Vector3::from(*local),
+        }
+    }
+
+    fn to_local(&self, world: &nalgebra::Vector3<f32>) -> [f32; 3] {
+        match self.body {
+            Some(body) => {
+                (body.rigid_body.rot_mat.transpose() * (world - body.rigid_body.pos)).into()
+            }
+            None => (*world).into(),
+        }
+    }
+}
+
+impl Joint {
+    pub fn validate(&self, path: &str, body_count: usize) -> Result<(), scene::SceneError> {
+        let field = |name: &str| format!("{}.{}", path, name);
+
+        scene::check_body(&field("body"), self.body, body_count)?;
+        if let Some(other) = self.other {
+            scene::check_body(&field("other"), other, body_count)?;
+            if other == self.body {
+                return Err(scene::SceneError::new(
+                    field("other"),
+                    "must differ from body",
+                ));
+            }
+        }
+        scene::check_vector(&field("anchor"), &self.anchor)?;
+        if let Some(other_anchor) = &self.other_anchor {
+            scene::check_vector(&field("other_anchor"), other_anchor)?;
+        }
+
+        match &self.kind {
+            JointKind::Ball {} => Ok(()),
+            JointKind::Hinge { axis, other_axis } => {
+                scene::check_non_zero(&field("kind.axis"), axis)?;
+                if let Some(other_axis) = other_axis {
+                    scene::check_non_zero(&field("kind.other_axis"), other_axis)?;
+                }
+                Ok(())
+            }
+            JointKind::Slider { axis, rotation } => {
+                scene::check_non_zero(&field("kind.axis"), axis)?;
+                if let Some(rotation) = rotation {
+                    scene::check_non_zero(&field("kind.rotation"), rotation)?;
+                }
+                Ok(())
+            }
+            JointKind::Fixed { rotation } => {
+                if let Some(rotation) = rotation {
+                    scene::check_non_zero(&field("kind.rotation"), rotation)?;
+                }
+                Ok(())
+            }
+            JointKind::Distance { length } => match length {
+                Some(length) => scene::check_non_negative(&field("kind.length"), *length),
+                None => Ok(()),
+            },
+        }
+    }
+
+    pub fn body_indices_mut(&mut self) -> Vec<&mut usize> {
+        std::iter::once(&mut self.body)
+            .chain(self.other.iter_mut())
+            .collect()
+    }
+
+    // Fills in the values left to the initial poses and normalizes axes
+    pub fn resolve(&self, bodies: &[body::Body]) -> Joint {
+        let a = &bodies[self.body];
+        let b = Side {
+            body: self.other.map(|other| &bodies[other]),
+        };
+        let anchor_world =
+            a.rigid_body.pos + a.rigid_body.rot_mat * nalgebra::Vector3::from(self.anchor);
+        let rotation = || {
+            let relative = a.rigid_body.rot_mat.transpose() * b.rot_mat();
+            let q = nalgebra::UnitQuaternion::from_matrix(&relative);
+            [q.w, q.i, q.j, q.k]
+        };
+        let normalized =
+            |v: &[f32; 3]| -> [f32; 3] { nalgebra::Vector3::from(*v).normalize().into() };
+
+        let kind = match &self.kind {
+            JointKind::Ball {} => JointKind::Ball {},
+            JointKind::Hinge { axis, other_axis } => {
+                let axis = normalized(axis);
+                let other_axis = match other_axis {
+                    Some(other_axis) => normalized(other_axis),
+                    None => (b.rot_mat().transpose()
+                        * a.rigid_body.rot_mat
+                        * nalgebra::Vector3::from(axis))
+                    .into(),
+                };
+                JointKind::Hinge {
+                    axis,
+                    other_axis: Some(other_axis),
+                }
+            }
+            JointKind::Slider { axis, rotation: r } => JointKind::Slider {
+                axis: normalized(axis),
+                rotation: Some(r.unwrap_or_else(rotation)),
+            },
+            JointKind::Fixed { rotation: r } => JointKind::Fixed {
+                rotation: Some(r.unwrap_or_else(rotation)),
+            },
+            JointKind::Distance { length } => {
+                let other_anchor = match &self.other_anchor {
+                    Some(other_anchor) => b.to_world(other_anchor),
+                    None => anchor_world,
+                };
+                JointKind::Distance {
+                    length: Some(length.unwrap_or((other_anchor - anchor_world).norm())),
+                }
+            }
+        };
+
+        Joint {
+            body: self.body,
+            other: self.other,
+            anchor: self.anchor,
+            other_anchor: Some(
+                self.other_anchor
+                    .unwrap_or_else(|| b.to_local(&anchor_world)),
+            ),
+            kind,
+        }
+    }
+}
+
+// A single scalar velocity constraint: the relative velocity of the other side
+// with respect to the body along `direction` should equal `target`
+enum Row {
+    // Of the anchor points
+    Linear {
+        direction: nalgebra::Vector3<f32>,
+        target: f32,
+    },
+    // Of the angular velocities
+    Angular {
+        direction: nalgebra::Vector3<f32>,
+        target: f32,
+    },
+}
+
+fn rotation_from_array(q: &[f32; 4]) -> nalgebra::Matrix3<f32> {
+    nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(q[0], q[1], q[2], q[3]))
+        .to_rotation_matrix()
+        .into_inner()
+}
+
+// Rotation vector turning the other side's orientation into the desired one
+fn orientation_error(a: &body::Body, b: &Side, rotation: &[f32; 4]) -> nalgebra::Vector3<f32> {
+    let desired = a.rigid_body.rot_mat * rotation_from_array(rotation);
+    let error = desired * b.rot_mat().transpose();
+    nalgebra::Rotation3::from_matrix(&error).scaled_axis()
+}
+
+fn rows(joint: &Joint, a: &body::Body, b: &Side, dt: f32) -> Vec<Row> {
+    let anchor_a = a.rigid_body.pos + a.rigid_body.rot_mat * nalgebra::Vector3::from(joint.anchor);
+    let anchor_b = b.to_world(&joint.other_anchor.unwrap_or_default());
+    let separation = anchor_b - anchor_a;
+    let feedback = BAUMGARTE / dt;
+
+    let point_rows = |directions: &[nalgebra::Vector3<f32>]| -> Vec<Row> {
+        directions
+            .iter()
+            .map(|direction| Row::Linear {
+                direction: *direction,
+                target: -feedback * separation.dot(direction),
+            })
+            .collect()
+    };
+    let lock_rotation = |rotation: &[f32; 4]| -> Vec<Row> {
+        let error = orientation_error(a, b, rotation);
+        [
+            nalgebra::Vector3::x(),
+            nalgebra::Vector3::y(),
+            nalgebra::Vector3::z(),
+        ]
+        .iter()
+        .map(|direction| Row::Angular {
+            direction: *direction,
+            target: feedback * error.dot(direction),
+        })
+        .collect()
+    };
+    let world_axes = [
+        nalgebra::Vector3::x(),
+        nalgebra::Vector3::y(),
+        nalgebra::Vector3::z(),
+    ];
+
+    match &joint.kind {
+        JointKind::Ball {} => point_rows(&world_axes),
+        JointKind::Hinge { axis, other_axis } => {
+            let axis_a = a.rigid_body.rot_mat * nalgebra::Vector3::from(*axis);
+            let axis_b = b.rot_mat() * nalgebra::Vector3::from(other_axis.unwrap_or(*axis));
+            let error = axis_b.cross(&axis_a);
+            let (u, v) = geometry::perpendicular_basis(&axis_a);
+            let mut rows = point_rows(&world_axes);
+            for direction in [u, v] {
+                rows.push(Row::Angular {
+                    direction,
+                    target: feedback * error.dot(&direction),
+                });
+            }
+            rows
+        }
+        JointKind::Slider { axis, rotation } => {
+            let axis_a = a.rigid_body.rot_mat * nalgebra::Vector3::from(*axis);
+            let (u, v) = geometry::perpendicular_basis(&axis_a);
+            let mut rows = point_rows(&[u, v]);
+            rows.extend(lock_rotation(&rotation.unwrap_or([1.0, 0.0, 0.0, 0.0])));
+            rows
+        }
+        JointKind::Fixed { rotation } => {
+            let mut rows = point_rows(&world_axes);
+            rows.extend(lock_rotation(&rotation.unwrap_or([1.0, 0.0, 0.0, 0.0])));
+            rows
+        }
+        JointKind::Distance { length } => {
+            let distance = separation.norm();
+            if distance <= f32::EPSILON {
+                return Vec::new();
+            }
+            let direction = separation / distance;
+            vec![Row::Linear {
+                direction,
+                target: -feedback * (distance - length.unwrap_or(0.0)),
+            }]
+        }
+    }
+}
+
+fn anchor_velocity(body: &body::Body, r: &nalgebra::Vector3<f32>) -> nalgebra::Vector3<f32> {
+    body.rigid_body.lin_vel + dynamics::angular_velocity(&body.rigid_body).cross(r)
+}
+
+// Velocity change along `direction` per unit impulse along it
+fn linear_response(
+    body: &body::Body,
+    r: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+) -> f32 {
+    let rn = r.cross(direction);
+    1.0 / body.mass + rn.dot(&(dynamics::world_inv_inertia(&body.rigid_body) * rn))
+}
+
+fn angular_response(body: &body::Body, direction: &nalgebra::Vector3<f32>) -> f32 {
+    direction.dot(&(dynamics::world_inv_inertia(&body.rigid_body) * direction))
+}
+
+// Corrects the momenta so that the joints hold at the end of the substep.
+// Call after applying forces and before `step_sim`.
+pub fn solve(joints: &[Joint], bodies: &mut [body::Body], dt: f32) {
+    let prepared: Vec<(Vec<Row>, nalgebra::Vector3<f32>, nalgebra::Vector3<f32>)> = joints
+        .iter()
+        .map(|joint| {
+            let a = &bodies[joint.body];
+            let b = Side {
+                body: joint.other.map(|other| &bodies[other]),
+            };
+            let anchor_a =
+                a.rigid_body.pos + a.rigid_body.rot_mat * nalgebra::Vector3::from(joint.anchor);
+            let anchor_b = b.to_world(&joint.other_anchor.unwrap_or_default());
+            let r_a = anchor_a - a.rigid_body.pos;
+            let r_b = match b.body {
+                Some(body) => anchor_b - body.rigid_body.pos,
+                None => nalgebra::Vector3::zeros(),
+            };
+            (rows(joint, a, &b, dt), r_a, r_b)
+        })
+        .collect();
+
+    for _ in 0..JOINT_ITERATIONS {
+        for (joint, (rows, r_a, r_b)) in joints.iter().zip(prepared.iter()) {
+            for row in rows {
+                solve_row(joint, row, r_a, r_b, bodies);
+            }
+        }
+    }
+}
+
+fn solve_row(
+    joint: &Joint,
+    row: &Row,
+    r_a: &nalgebra::Vector3<f32>,
+    r_b: &nalgebra::Vector3<f32>,
+    bodies: &mut [body::Body],
+) {
+    match row {
+        Row::Linear { direction, target } => {
+            let a = &bodies[joint.body];
+            let mut velocity = -anchor_velocity(a, r_a).dot(direction);
+            let mut response = linear_response(a, r_a, direction);
+            if let Some(other) = joint.other {
+                let b = &bodies[other];
+                velocity += anchor_velocity(b, r_b).dot(direction);
+                response += linear_response(b, r_b, direction);
+            }
+
+            let impulse = direction * ((target - velocity) / response);
+            let a = &mut bodies[joint.body].rigid_body;
+            a.lin_vel -= impulse / bodies[joint.body].mass;
+            a.ang_mom -= r_a.cross(&impulse);
+            if let Some(other) = joint.other {
+                let mass = bodies[other].mass;
+                let b = &mut bodies[other].rigid_body;
+                b.lin_vel += impulse / mass;
+                b.ang_mom += r_b.cross(&impulse);
+            }
+        }
+        Row::Angular { direction, target } => {
+            let a = &bodies[joint.body];
+            let mut velocity = -dynamics::angular_velocity(&a.rigid_body).dot(direction);
+            let mut response = angular_response(a, direction);
+            if let Some(other) = joint.other {
+                let b = &bodies[other];
+                velocity += dynamics::angular_velocity(&b.rigid_body).dot(direction);
+                response += angular_response(b, direction);
+            }
+
+            let impulse = direction * ((target - velocity) / response);
+            bodies[joint.body].rigid_body.ang_mom -= impulse;
+            if let Some(other) = joint.other {
+                bodies[other].rigid_body.ang_mom += impulse;
+            }
+        }
+    }
+}
+
+// Both anchors with lines to their bodies' centers, plus the hinge or slider
+// axis and the distance constraint
+pub fn joints_to_vertices(
+    vertices: &mut Vec<f32>,
+    joints: &[Joint],
+    bodies: &[body::Body],
+    overlay: &overlays::VectorOverlay,
+) {
+    if !overlay.enabled {
+        return;
+    }
+
+    let marker = |vertices: &mut Vec<f32>, p: &nalgebra::Vector3<f32>| {
+        for axis in [
+            nalgebra::Vector3::x(),
+            nalgebra::Vector3::y(),
+            nalgebra::Vector3::z(),
+        ] {
+            geometry::segment_to_vertices(
+                vertices,
+                &(p - axis * GIZMO_MARKER_SIZE),
+                &(p + axis * GIZMO_MARKER_SIZE),
+                overlay.color,
+            );
+        }
+    };
+
+    for joint in joints {
+        // Joints may refer to bodies missing from an imported replay
+        let a = match bodies.get(joint.body) {
+            Some(a) => a,
+            None => continue,
+        };
+        let b = Side {
+            body: match joint.other {
+                Some(other) => match bodies.get(other) {
+                    Some(b) => Some(b),
+                    None => continue,
+                },
+                None => None,
+            },
+        };
+        let anchor_a =
+            a.rigid_body.pos + a.rigid_body.rot_mat * nalgebra::Vector3::from(joint.anchor);
+        let anchor_b = b.to_world(&joint.other_anchor.unwrap_or_default());
+
+        marker(vertices, &anchor_a);
+        marker(vertices, &anchor_b);
+        geometry::segment_to_vertices(vertices, &a.rigid_body.pos, &anchor_a, overlay.color);
+        if let Some(b) = b.body {
+            geometry::segment_to_vertices(vertices, &b.rigid_body.pos, &anchor_b, overlay.color);
+        }
+
+        match &joint.kind {
+            JointKind::Hinge { axis, .. } | JointKind::Slider { axis, .. } => {
+                let axis = a.rigid_body.rot_mat * nalgebra::Vector3::from(*axis) * overlay.scale;
+                geometry::segment_to_vertices(
+                    vertices,
+                    &(anchor_a - axis),
+                    &(anchor_a + axis),
+                    overlay.color,
+                );
+            }
+            JointKind::Distance { .. } => {
+                geometry::segment_to_vertices(vertices, &anchor_a, &anchor_b, overlay.color);
+            }
+            JointKind::Ball {} | JointKind::Fixed { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation;
+
+    fn simulation(bodies: &str, joints: &str) -> simulation::Simulation {
+        let text = format!(
+            r#"{{
+                "version": 1,
+                "bodies": {},
+                "forces": [{{ "type": "gravity", "acceleration": [0.0, -9.81, 0.0] }}],
+                "joints": {},
+                "integrator": {{ "timestep": 0.005, "substeps": 2 }}
+            }}"#,
+            bodies, joints
+        );
+        scene::Scene::parse(&text)
+            .unwrap()
+            .build_simulation()
+            .unwrap()
+    }
+
+    #[test]
+    fn ball_pendulum_keeps_to_its_anchor() {
+        let mut simulation = simulation(
+            r#"[{
+                "name": "bob",
+                "shape": { "type": "sphere", "radius": 0.1 },
+                "mass": 1.0,
+                "pos": [1.0, 0.0, 0.0],
+                "lin_vel": [0.0, 0.0, 0.5]
+            }]"#,
+            r#"[{ "body": 0, "anchor": [-1.0, 0.0, 0.0], "kind": { "type": "ball" } }]"#,
+        );
+        let mut lowest = 0f32;
+        for _ in 0..400 {
+            simulation.step();
+            let rigid_body = &simulation.bodies[0].rigid_body;
+            let anchor =
+                rigid_body.pos + rigid_body.rot_mat * nalgebra::Vector3::new(-1.0, 0.0, 0.0);
+            assert!(anchor.norm() < 0.02, "anchor drifted to {}", anchor);
+            assert!((rigid_body.pos.norm() - 1.0).abs() < 0.02);
+            lowest = lowest.min(rigid_body.pos.y);
+        }
+        // It did swing
+        assert!(lowest < -0.5, "{}", lowest);
+    }
+
+    #[test]
+    fn fixed_joint_holds_the_relative_pose() {
+        let mut simulation = simulation(
+            r#"[
+                {
+                    "name": "a",
+                    "shape": { "type": "cuboid", "half_extents": [0.2, 0.2, 0.2] },
+                    "mass": 1.0,
+                    "lin_vel": [1.0, 2.0, 0.0],
+                    "ang_mom": [0.0, 0.0, 0.1]
+                },
+                {
+                    "name": "b",
+                    "shape": { "type": "cuboid", "half_extents": [0.1, 0.3, 0.1] },
+                    "mass": 0.5,
+                    "pos": [0.5, 0.2, 0.0],
+                    "orientation": [0.9238795, 0.0, 0.3826834, 0.0]
+                }
+            ]"#,
+            r#"[{ "body": 0, "other": 1, "anchor": [0.25, 0.0, 0.0], "kind": { "type": "fixed" } }]"#,
+        );
+        let relative = |simulation: &simulation::Simulation| {
+            let a = &simulation.bodies[0].rigid_body;
+            let b = &simulation.bodies[1].rigid_body;
+            (
+                a.rot_mat.transpose() * (b.pos - a.pos),
+                a.rot_mat.transpose() * b.rot_mat,
+            )
+        };
+        let (offset, rotation) = relative(&simulation);
+        for _ in 0..200 {
+            simulation.step();
+            let (current_offset, current_rotation) = relative(&simulation);
+            assert!((current_offset - offset).norm() < 0.01);
+            assert!((current_rotation - rotation).norm() < 0.01);
+        }
+        // The pair still moves as one
+        assert!(simulation.bodies[0].rigid_body.pos.x > 0.5);
+    }
+
+    #[test]
+    fn hinge_turns_only_about_its_axis() {
+        let mut simulation = simulation(
+            r#"[{
+                "name": "door",
+                "shape": { "type": "cuboid", "half_extents": [0.5, 0.1, 0.05] },
+                "mass": 1.0,
+                "pos": [1.0, 0.0, 0.0],
+                "lin_vel": [0.0, 0.0, 0.5],
+                "ang_mom": [0.05, 0.05, 0.0]
+            }]"#,
+            r#"[{
+                "body": 0,
+                "anchor": [-1.0, 0.0, 0.0],
+                "kind": { "type": "hinge", "axis": [0.0, 0.0, 1.0] }
+            }]"#,
+        );
+        let mut lowest = 0f32;
+        for _ in 0..400 {
+            simulation.step();
+            let rigid_body = &simulation.bodies[0].rigid_body;
+            let anchor =
+                rigid_body.pos + rigid_body.rot_mat * nalgebra::Vector3::new(-1.0, 0.0, 0.0);
+            let axis = rigid_body.rot_mat * nalgebra::Vector3::z();
+            assert!(anchor.norm() < 0.02, "anchor drifted to {}", anchor);
+            assert!(axis.z > 0.999, "axis tilted to {}", axis);
+            assert!(rigid_body.pos.z.abs() < 0.02);
+            lowest = lowest.min(rigid_body.pos.y);
+        }
+        assert!(lowest < -0.5, "{}", lowest);
+    }
+
+    #[test]
+    fn slider_moves_only_along_its_axis() {
+        // Down an incline, pushed sideways and spun
+        let mut simulation = simulation(
+            r#"[{
+                "name": "carriage",
+                "shape": { "type": "cuboid", "half_extents": [0.2, 0.1, 0.1] },
+                "mass": 1.0,
+                "orientation": [0.9238795, 0.0, 0.0, 0.3826834],
+                "lin_vel": [0.0, 0.0, 0.5],
+                "ang_mom": [0.02, 0.03, 0.01]
+            }]"#,
+            r#"[{ "body": 0, "kind": { "type": "slider", "axis": [1.0, 0.0, 0.0] } }]"#,
+        );
+        let rot_mat = simulation.bodies[0].rigid_body.rot_mat;
+        let axis = rot_mat * nalgebra::Vector3::x();
+        for _ in 0..400 {
+            simulation.step();
+            let rigid_body = &simulation.bodies[0].rigid_body;
+            let offset = rigid_body.pos - axis * rigid_body.pos.dot(&axis);
+            assert!(offset.norm() < 0.02, "left the axis by {}", offset);
+            assert!((rigid_body.rot_mat - rot_mat).norm() < 0.01);
+        }
+        // It slid down
+        assert!(simulation.bodies[0].rigid_body.pos.y < -0.5);
+    }
+
+    #[test]
+    fn distance_joint_keeps_the_anchors_apart() {
+        let mut simulation = simulation(
+            r#"[
+                {
+                    "name": "a",
+                    "shape": { "type": "sphere", "radius": 0.1 },
+                    "mass": 1.0,
+                    "lin_vel": [0.0, 1.0, 0.0]
+                },
+                {
+                    "name": "b",
+                    "shape": { "type": "cuboid", "half_extents": [0.1, 0.1, 0.1] },
+                    "mass": 2.0,
+                    "pos": [1.5, 0.0, 0.0],
+                    "lin_vel": [0.0, -1.0, 0.5],
+                    "ang_mom": [0.0, 0.01, 0.0]
+                }
+            ]"#,
+            r#"[{
+                "body": 0,
+                "other": 1,
+                "anchor": [0.1, 0.0, 0.0],
+                "other_anchor": [-0.1, 0.0, 0.0],
+                "kind": { "type": "distance" }
+            }]"#,
+        );
+        let anchors = |simulation: &simulation::Simulation| {
+            let a = &simulation.bodies[0].rigid_body;
+            let b = &simulation.bodies[1].rigid_body;
+            let a = a.pos + a.rot_mat * nalgebra::Vector3::new(0.1, 0.0, 0.0);
+            let b = b.pos + b.rot_mat * nalgebra::Vector3::new(-0.1, 0.0, 0.0);
+            b - a
+        };
+        assert!((anchors(&simulation).norm() - 1.3).abs() < 1e-6);
+        for _ in 0..400 {
+            simulation.step();
+            assert!((anchors(&simulation).norm() - 1.3).abs() < 0.02);
+        }
+        // The pair spun around rather than being held still
+        let turned = anchors(&simulation)
+            .normalize()
+            .dot(&nalgebra::Vector3::x());
+        assert!(turned < 0.9, "{}", turned);
+    }
+}
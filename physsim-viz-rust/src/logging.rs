@@ -0,0 +1,173 @@
+// Filters look like `warn,physsim_viz_rust::web=debug`: a default level and
+// levels for modules, whose submodules they also cover
+pub const DEFAULT_LEVEL: log::LevelFilter = log::LevelFilter::Warn;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    default: log::LevelFilter,
+    // Sorted by decreasing length, so that the innermost module matches first
+    modules: Vec<(String, log::LevelFilter)>,
+}
+
+impl Filter {
+    pub const fn new(default: log::LevelFilter) -> Self {
+        Self {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::new(DEFAULT_LEVEL);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let level = |name: &str| -> Result<log::LevelFilter, String> {
+                name.trim()
+                    .parse()
+                    .map_err(|_| format!("unknown log level \"{}\"", name.trim()))
+            };
+            match directive.split_once('=') {
+                Some((module, name)) => {
+                    let module = module.trim();
+                    if module.is_empty() {
+                        return Err(format!("missing module in \"{}\"", directive));
+                    }
+                    filter.modules.retain(|(other, _)| other != module);
+                    filter.modules.push((String::from(module), level(name)?));
+                }
+                None => filter.default = level(directive)?,
+            }
+        }
+        filter
+            .modules
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok(filter)
+    }
+
+    // Log records' targets are the module paths they come from
+    pub fn level(&self, target: &str) -> log::LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    // The most verbose level anything passes at
+    pub fn max_level(&self) -> log::LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+// Writes records to the browser's console, at the matching console level
+#[cfg(feature = "web")]
+struct ConsoleLogger {
+    filter: std::sync::RwLock<Filter>,
+}
+
+#[cfg(feature = "web")]
+static LOGGER: ConsoleLogger = ConsoleLogger {
+    filter: std::sync::RwLock::new(Filter::new(DEFAULT_LEVEL)),
+};
+
+#[cfg(feature = "web")]
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.filter.read().unwrap().level(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = format!("{}: {}", record.target(), record.args()).into();
+        match record.level() {
+            log::Level::Error => web_sys::console::error_1(&message),
+            log::Level::Warn => web_sys::console::warn_1(&message),
+            log::Level::Info => web_sys::console::info_1(&message),
+            log::Level::Debug | log::Level::Trace => web_sys::console::debug_1(&message),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Installs the console logger with the default filter, unless it already is
+#[cfg(feature = "web")]
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LOGGER.filter.read().unwrap().max_level());
+    }
+}
+
+// The log macros compare against the maximum level before formatting
+// anything, and `log` checks the module's level first too
+#[cfg(feature = "web")]
+pub fn set_filter(filter: Filter) {
+    init();
+    log::set_max_level(filter.max_level());
+    *LOGGER.filter.write().unwrap() = filter;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_warnings() {
+        let filter = Filter::parse("").unwrap();
+        assert_eq!(filter, Filter::new(DEFAULT_LEVEL));
+        assert_eq!(
+            filter.level("physsim_viz_rust::web"),
+            log::LevelFilter::Warn
+        );
+        assert_eq!(filter.max_level(), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    fn matches_the_innermost_module() {
+        let filter = Filter::parse(
+            "error, physsim_viz_rust=info, physsim_viz_rust::web=trace, physsim_viz_rust::web::x=off",
+        )
+        .unwrap();
+        assert_eq!(filter.level("other"), log::LevelFilter::Error);
+        assert_eq!(
+            filter.level("physsim_viz_rust::scene"),
+            log::LevelFilter::Info
+        );
+        assert_eq!(
+            filter.level("physsim_viz_rust::web"),
+            log::LevelFilter::Trace
+        );
+        assert_eq!(
+            filter.level("physsim_viz_rust::web::x::y"),
+            log::LevelFilter::Off
+        );
+        // Only whole path segments match
+        assert_eq!(
+            filter.level("physsim_viz_rust::webgl"),
+            log::LevelFilter::Info
+        );
+        assert_eq!(filter.max_level(), log::LevelFilter::Trace);
+    }
+
+    #[test]
+    fn later_directives_win() {
+        let filter = Filter::parse("info,a=debug,off,a=error").unwrap();
+        assert_eq!(filter.level("b"), log::LevelFilter::Off);
+        assert_eq!(filter.level("a::b"), log::LevelFilter::Error);
+    }
+
+    #[test]
+    fn rejects_unknown_levels() {
+        assert!(Filter::parse("loud").is_err());
+        assert!(Filter::parse("a=loud").is_err());
+        assert!(Filter::parse("=info").is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use crate::dynamics;
+use crate::geometry;
+use crate::trace;
+
+const ANG_VEL_TRACE_LEN: usize = 4096;
+const ELLIPSOID_SEGMENTS: usize = 24;
+const INTERSECTION_SEGMENTS: usize = 128;
+// Dim teal, to tell the momentum sphere from the energy ellipsoid drawn in the
+// dimmed overlay color
+const MOMENTUM_SPHERE_COLOR: (f32, f32, f32) = (0.1, 0.35, 0.35);
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VectorOverlay {
+    pub enabled: bool,
+    pub color: (f32, f32, f32),
+    pub scale: f32,
+}
+
+impl VectorOverlay {
+    fn new(color: (f32, f32, f32), scale: f32) -> Self {
+        Self {
+            enabled: false,
+            color,
+            scale,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Overlays {
+    pub reference: bool,
+    // Readout of energy, momentum and angular momentum
+    pub conserved: bool,
+    // Panel with the selected body's state next to the canvas
+    pub inspector: bool,
+    pub trails: bool,
+    pub ang_vel: VectorOverlay,
+    pub body_axes: VectorOverlay,
+    pub principal_axes: VectorOverlay,
+    pub polhode: VectorOverlay,
+    pub herpolhode: VectorOverlay,
+    pub inertia_ellipsoid: VectorOverlay,
+    pub invariable_plane: VectorOverlay,
+    pub momentum_sphere: VectorOverlay,
+    pub forces: VectorOverlay,
+    pub torques: VectorOverlay,
+    // Color and scale apply to the impulse arrows
+    pub contacts: VectorOverlay,
+    // Scale is the half-length of the drawn hinge and slider axes
+    pub joints: VectorOverlay,
+    // Scale is the coil radius
+    pub springs: VectorOverlay,
+}
+
+impl Default for Overlays {
+    fn default() -> Self {
+        Self {
+            reference: false,
+            conserved: false,
+            inspector: false,
+            trails: true,
+            ang_vel: VectorOverlay::new((1.0, 0.0, 1.0), 1.0),
+            body_axes: VectorOverlay::new((1.0, 0.5, 0.0), 1.0),
+            principal_axes: VectorOverlay::new((0.6, 0.4, 1.0), 0.8),
+            polhode: VectorOverlay::new((1.0, 0.6, 0.8), 1.0),
+            herpolhode: VectorOverlay::new((0.6, 1.0, 0.6), 1.0),
+            inertia_ellipsoid: VectorOverlay::new((0.4, 0.7, 1.0), 1.0),
+            // Scale is the half-size of the drawn patch of the plane
+            invariable_plane: VectorOverlay::new((0.5, 0.5, 0.5), 1.5),
+            momentum_sphere: VectorOverlay::new((1.0, 1.0, 0.3), 1.0),
+            // Scale is the arrow length per unit force or torque
+            forces: VectorOverlay {
+                enabled: true,
+                ..VectorOverlay::new((1.0, 0.3, 0.3), 0.2)
+            },
+            torques: VectorOverlay {
+                enabled: true,
+                ..VectorOverlay::new((0.3, 0.6, 1.0), 0.2)
+            },
+            contacts: VectorOverlay::new((1.0, 0.4, 1.0), 2.0),
+            joints: VectorOverlay {
+                enabled: true,
+                ..VectorOverlay::new((0.3, 1.0, 0.8), 0.3)
+            },
+            springs: VectorOverlay {
+                enabled: true,
+                ..VectorOverlay::new((0.9, 0.9, 0.9), 0.05)
+            },
+        }
+    }
+}
+
+pub fn overlays_to_vertices(
+    vertices: &mut Vec<f32>,
+    rigid_body: &physsim::RigidBody<f32>,
+    overlays: &Overlays,
+) {
+    if overlays.ang_vel.enabled {
+        geometry::vector_to_vertices(
+            vertices,
+            &rigid_body.pos,
+            &(dynamics::angular_velocity(rigid_body) * overlays.ang_vel.scale),
+            Some(overlays.ang_vel.color),
+            0.1,
+        );
+    }
+
+    if overlays.body_axes.enabled {
+        for axis in rigid_body.rot_mat.column_iter() {
+            geometry::vector_to_vertices(
+                vertices,
+                &rigid_body.pos,
+                &(axis * overlays.body_axes.scale),
+                Some(overlays.body_axes.color),
+                0.05,
+            );
+        }
+    }
+
+    if overlays.principal_axes.enabled {
+        let (_, axes) = dynamics::principal_axes(&rigid_body.inv_ine.cast::<f64>());
+        let axes = rigid_body.rot_mat * axes.cast::<f32>();
+        for axis in axes.column_iter() {
+            // Principal axes have no preferred direction
+            for sign in [1.0, -1.0] {
+                geometry::vector_to_vertices(
+                    vertices,
+                    &rigid_body.pos,
+                    &(axis * sign * overlays.principal_axes.scale),
+                    Some(overlays.principal_axes.color),
+                    0.0,
+                );
+            }
+        }
+    }
+}
+
+// Poinsot's construction: the energy ellipsoid w^T I w = 2T fixed in the body
+// rolls without slipping on the invariable plane, which is perpendicular to
+// the angular momentum at the distance 2T / |L| from the center of mass
+pub fn poinsot_to_vertices(
+    vertices: &mut Vec<f32>,
+    rigid_body: &physsim::RigidBody<f32>,
+    overlays: &Overlays,
+) {
+    let (moments, axes) = dynamics::principal_axes(&rigid_body.inv_ine.cast::<f64>());
+    let moments = moments.cast::<f32>();
+    let world_axes = rigid_body.rot_mat * axes.cast::<f32>();
+
+    let ang_mom_norm = rigid_body.ang_mom.norm();
+    let energy_2 = rigid_body
+        .ang_mom
+        .dot(&dynamics::angular_velocity(rigid_body));
+
+    if overlays.inertia_ellipsoid.enabled {
+        // Without rotation fall back to the classical x^T I x = 1 ellipsoid
+        let level = if energy_2 > 0.0 { energy_2 } else { 1.0 };
+        let semi_axes = world_axes
+            * nalgebra::Matrix3::from_diagonal(&moments.map(|i| (level / i).sqrt()))
+            * overlays.inertia_ellipsoid.scale;
+        geometry::ellipsoid_to_vertices(
+            vertices,
+            &rigid_body.pos,
+            &semi_axes,
+            overlays.inertia_ellipsoid.color,
+            ELLIPSOID_SEGMENTS,
+        );
+    }
+
+    if overlays.invariable_plane.enabled && ang_mom_norm > 0.0 {
+        let normal = rigid_body.ang_mom / ang_mom_norm;
+        let (u, v) = geometry::perpendicular_basis(&normal);
+        let center =
+            rigid_body.pos + normal * (energy_2 / ang_mom_norm) * overlays.inertia_ellipsoid.scale;
+        geometry::grid_to_vertices(
+            vertices,
+            &center,
+            &u,
+            &v,
+            overlays.invariable_plane.scale,
+            10,
+            overlays.invariable_plane.color,
+        );
+    }
+
+    if overlays.momentum_sphere.enabled && ang_mom_norm > 0.0 {
+        // Body-frame angular momentum lies both on the sphere |L| = const and
+        // on the ellipsoid L^T I^-1 L = 2T; drawn dimmed, with their
+        // intersection (the polhode in L-space) in full color
+        let color = overlays.momentum_sphere.color;
+        let dim = (color.0 * 0.35, color.1 * 0.35, color.2 * 0.35);
+        let scale = overlays.momentum_sphere.scale;
+
+        geometry::ellipsoid_to_vertices(
+            vertices,
+            &rigid_body.pos,
+            &(world_axes * ang_mom_norm * scale),
+            MOMENTUM_SPHERE_COLOR,
+            ELLIPSOID_SEGMENTS,
+        );
+        geometry::ellipsoid_to_vertices(
+            vertices,
+            &rigid_body.pos,
+            &(world_axes
+                * nalgebra::Matrix3::from_diagonal(&moments.map(|i| (energy_2 * i).sqrt()))
+                * scale),
+            dim,
+            ELLIPSOID_SEGMENTS,
+        );
+
+        for curve in momentum_energy_intersection(&moments, ang_mom_norm, energy_2) {
+            let points = curve
+                .iter()
+                .map(|p| rigid_body.pos + world_axes * p * scale)
+                .collect::<Vec<_>>();
+            geometry::loop_to_vertices(vertices, &points, color);
+        }
+    }
+}
+
+// Principal-frame curves where |L| = ang_mom_norm and L^T I^-1 L = energy_2.
+// They circle the axis of the largest moment if L^2 > 2T I_2 and the axis of
+// the smallest one otherwise.
+fn momentum_energy_intersection(
+    moments: &nalgebra::Vector3<f32>,
+    ang_mom_norm: f32,
+    energy_2: f32,
+) -> Vec<Vec<nalgebra::Vector3<f32>>> {
+    let l_2 = ang_mom_norm * ang_mom_norm;
+    let (a, b, c) = if l_2 > energy_2 * moments[1] {
+        (0, 1, 2)
+    } else {
+        (2, 1, 0)
+    };
+
+    let mut points = Vec::with_capacity(INTERSECTION_SEGMENTS);
+    for i in 0..INTERSECTION_SEGMENTS {
+        let alpha = 2.0 * std::f32::consts::PI * i as f32 / INTERSECTION_SEGMENTS as f32;
+        let (sin, cos) = alpha.sin_cos();
+        let k = cos * cos / moments[a] + sin * sin / moments[b] - 1.0 / moments[c];
+        let q_2 = (energy_2 - l_2 / moments[c]) / k;
+        if !q_2.is_finite() || q_2 < 0.0 || q_2 > l_2 {
+            return Vec::new();
+        }
+
+        let q = q_2.sqrt();
+        let mut p = nalgebra::Vector3::zeros();
+        p[a] = q * cos;
+        p[b] = q * sin;
+        p[c] = (l_2 - q_2).sqrt();
+        points.push(p);
+    }
+
+    let mirrored = points
+        .iter()
+        .map(|p| {
+            let mut p = *p;
+            p[c] = -p[c];
+            p
+        })
+        .collect();
+    vec![points, mirrored]
+}
+
+// Tip of the angular velocity traced in the body frame (polhode) and in the
+// space frame (herpolhode), both relative to the center of mass
+pub struct AngVelTraces {
+    pub polhode: trace::RingBuffer<nalgebra::Vector3<f32>>,
+    pub herpolhode: trace::RingBuffer<nalgebra::Vector3<f32>>,
+}
+
+impl Default for AngVelTraces {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AngVelTraces {
+    pub fn new() -> Self {
+        Self {
+            polhode: trace::RingBuffer::new(ANG_VEL_TRACE_LEN),
+            herpolhode: trace::RingBuffer::new(ANG_VEL_TRACE_LEN),
+        }
+    }
+
+    pub fn record(&mut self, rigid_body: &physsim::RigidBody<f32>) {
+        let ang_vel = dynamics::angular_velocity(rigid_body);
+        self.polhode.push(rigid_body.rot_mat.transpose() * ang_vel);
+        self.herpolhode.push(ang_vel);
+    }
+
+    pub fn clear(&mut self) {
+        self.polhode.clear();
+        self.herpolhode.clear();
+    }
+}
+
+// Appends the enabled traces as separate line strips and returns the
+// (first vertex, vertex count) range of each one
+pub fn traces_to_vertices(
+    vertices: &mut Vec<f32>,
+    rigid_body: &physsim::RigidBody<f32>,
+    traces: &AngVelTraces,
+    overlays: &Overlays,
+) -> Vec<(i32, i32)> {
+    let mut strips = Vec::new();
+
+    if overlays.polhode.enabled {
+        let first = (vertices.len() / 6) as i32;
+        trace::polyline_to_vertices(
+            vertices,
+            traces
+                .polhode
+                .iter()
+                .map(|p| rigid_body.pos + rigid_body.rot_mat * p * overlays.polhode.scale),
+            overlays.polhode.color,
+        );
+        strips.push((first, traces.polhode.len() as i32));
+    }
+
+    if overlays.herpolhode.enabled {
+        let first = (vertices.len() / 6) as i32;
+        trace::polyline_to_vertices(
+            vertices,
+            traces
+                .herpolhode
+                .iter()
+                .map(|p| rigid_body.pos + p * overlays.herpolhode.scale),
+            overlays.herpolhode.color,
+        );
+        strips.push((first, traces.herpolhode.len() as i32));
+    }
+
+    strips
+}
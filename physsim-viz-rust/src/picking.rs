@@ -0,0 +1,210 @@
+use crate::body;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub body: usize,
+    // World frame
+    pub point: nalgebra::Vector3<f32>,
+    // Along the ray from its origin
+    pub distance: f32,
+}
+
+// Nearest body hit by the ray, `direction` must be a unit vector
+pub fn ray_cast(
+    bodies: &[body::Body],
+    origin: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+) -> Option<Hit> {
+    bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, body)| {
+            let distance = match body.shape {
+                body::Shape::Sphere { radius } => {
+                    ray_sphere(origin, direction, &body.rigid_body.pos, radius)
+                }
+                body::Shape::Cuboid { half_extents } => {
+                    // In the box frame the box is axis aligned
+                    let rot_t = body.rigid_body.rot_mat.transpose();
+                    ray_box(
+                        &(rot_t * (origin - body.rigid_body.pos)),
+                        &(rot_t * direction),
+                        &nalgebra::Vector3::from(half_extents),
+                    )
+                }
+            }?;
+            Some(Hit {
+                body: i,
+                point: origin + direction * distance,
+                distance,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+// Distance to the first intersection in front of the origin, zero if the
+// origin is inside
+fn ray_sphere(
+    origin: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+    center: &nalgebra::Vector3<f32>,
+    radius: f32,
+) -> Option<f32> {
+    let m = origin - center;
+    let b = m.dot(direction);
+    let c = m.norm_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+// Slab test against the box [-half, half]
+fn ray_box(
+    origin: &nalgebra::Vector3<f32>,
+    direction: &nalgebra::Vector3<f32>,
+    half: &nalgebra::Vector3<f32>,
+) -> Option<f32> {
+    let mut t_min: f32 = 0.0;
+    let mut t_max = f32::INFINITY;
+    for i in 0..3 {
+        if direction[i].abs() < f32::EPSILON {
+            if origin[i].abs() > half[i] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (-half[i] - origin[i]) / direction[i];
+        let t2 = (half[i] - origin[i]) / direction[i];
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return None;
+        }
+    }
+    Some(t_min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(shape: body::Shape, pos: [f32; 3], rot: nalgebra::Rotation3<f32>) -> body::Body {
+        body::Body {
+            name: String::new(),
+            shape,
+            mass: 1.0,
+            material: body::Material::default(),
+            rigid_body: physsim::RigidBody {
+                pos: pos.into(),
+                lin_vel: nalgebra::Vector3::zeros(),
+                rot_mat: *rot.matrix(),
+                ang_mom: nalgebra::Vector3::zeros(),
+                inv_ine: nalgebra::Matrix3::identity(),
+            },
+        }
+    }
+
+    fn sphere(radius: f32, pos: [f32; 3]) -> body::Body {
+        body(
+            body::Shape::Sphere { radius },
+            pos,
+            nalgebra::Rotation3::identity(),
+        )
+    }
+
+    fn cast(bodies: &[body::Body], origin: [f32; 3], direction: [f32; 3]) -> Option<Hit> {
+        ray_cast(
+            bodies,
+            &origin.into(),
+            &nalgebra::Vector3::from(direction).normalize(),
+        )
+    }
+
+    #[test]
+    fn hits_spheres_in_front() {
+        let center = nalgebra::Vector3::new(0.0, 0.0, 0.0);
+        let ray = |origin: [f32; 3], direction: [f32; 3]| {
+            ray_sphere(&origin.into(), &direction.into(), &center, 1.0)
+        };
+        assert_eq!(ray([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]), Some(4.0));
+        assert_eq!(ray([0.5, 0.0, 0.0], [0.0, 0.0, 1.0]), Some(0.0));
+        // Beside, and pointing away
+        assert_eq!(ray([2.0, 0.0, -5.0], [0.0, 0.0, 1.0]), None);
+        assert_eq!(ray([0.0, 0.0, -5.0], [0.0, 0.0, -1.0]), None);
+    }
+
+    #[test]
+    fn hits_boxes_in_front() {
+        let half = nalgebra::Vector3::new(1.0, 1.0, 1.0);
+        let ray = |origin: [f32; 3], direction: [f32; 3]| {
+            ray_box(&origin.into(), &direction.into(), &half)
+        };
+        assert_eq!(ray([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]), Some(4.0));
+        assert_eq!(ray([0.5, -0.5, 0.0], [0.0, 0.0, 1.0]), Some(0.0));
+        assert_eq!(ray([2.0, 0.0, -5.0], [0.0, 0.0, 1.0]), None);
+        assert_eq!(ray([0.0, 0.0, -5.0], [0.0, 0.0, -1.0]), None);
+        // Parallel to the y and z slabs, within or outside them
+        assert_eq!(ray([-5.0, 0.2, 0.0], [1.0, 0.0, 0.0]), Some(4.0));
+        assert_eq!(ray([-5.0, 1.5, 0.0], [1.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn hits_rotated_boxes() {
+        let long = |rot| {
+            body(
+                body::Shape::Cuboid {
+                    half_extents: [2.0, 0.5, 0.5],
+                },
+                [0.0, 0.0, 0.0],
+                rot,
+            )
+        };
+        let origin = [0.0, 1.5, 5.0];
+        let direction = [0.0, 0.0, -1.0];
+        assert_eq!(
+            cast(&[long(nalgebra::Rotation3::identity())], origin, direction),
+            None
+        );
+
+        // Turned to lie along y
+        let turned = nalgebra::Rotation3::from_axis_angle(
+            &nalgebra::Vector3::z_axis(),
+            std::f32::consts::FRAC_PI_2,
+        );
+        let hit = cast(&[long(turned)], origin, direction).unwrap();
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        assert!((hit.point - nalgebra::Vector3::new(0.0, 1.5, 0.5)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn reports_zero_distance_from_inside() {
+        let bodies = [body(
+            body::Shape::Cuboid {
+                half_extents: [1.0, 1.0, 1.0],
+            },
+            [0.0, 0.0, 0.0],
+            nalgebra::Rotation3::identity(),
+        )];
+        let hit = cast(&bodies, [0.2, 0.3, 0.4], [1.0, 1.0, 0.0]).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.point, nalgebra::Vector3::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn picks_the_nearer_body() {
+        let bodies = [
+            sphere(1.0, [0.0, 0.0, 6.0]),
+            sphere(1.0, [0.0, 0.0, 3.0]),
+            sphere(1.0, [0.0, 3.0, 1.0]),
+        ];
+        let hit = cast(&bodies, [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]).unwrap();
+        assert_eq!(hit.body, 1);
+        assert_eq!(hit.distance, 2.0);
+        assert_eq!(cast(&bodies, [0.0, 0.0, 0.0], [0.0, 0.0, -1.0]), None);
+    }
+}
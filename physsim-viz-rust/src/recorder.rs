@@ -0,0 +1,263 @@
+use crate::dynamics;
+use crate::simulation;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "json-lines" | "jsonlines" => Some(ExportFormat::JsonLines),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::JsonLines => "application/jsonl",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+const CSV_HEADER: &str = "time,body,pos_x,pos_y,pos_z,lin_vel_x,lin_vel_y,lin_vel_z,\
+    quat_w,quat_x,quat_y,quat_z,ang_mom_x,ang_mom_y,ang_mom_z,energy";
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Sample {
+    pub time: f64,
+    pub body: usize,
+    pub pos: [f32; 3],
+    pub lin_vel: [f32; 3],
+    // Quaternion [w, x, y, z]
+    pub orientation: [f32; 4],
+    pub ang_mom: [f32; 3],
+    pub energy: f32,
+}
+
+impl Sample {
+    fn to_csv_row(&self) -> String {
+        let values = [
+            self.pos[0],
+            self.pos[1],
+            self.pos[2],
+            self.lin_vel[0],
+            self.lin_vel[1],
+            self.lin_vel[2],
+            self.orientation[0],
+            self.orientation[1],
+            self.orientation[2],
+            self.orientation[3],
+            self.ang_mom[0],
+            self.ang_mom[1],
+            self.ang_mom[2],
+            self.energy,
+        ];
+        let mut row = format!("{},{}", self.time, self.body);
+        for value in values {
+            row += &format!(",{}", value);
+        }
+        row
+    }
+}
+
+pub struct Recorder {
+    // Record every n-th physics tick
+    pub decimation: u32,
+    pub samples: Vec<Sample>,
+    recording: bool,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            decimation: 1,
+            samples: Vec::new(),
+            recording: false,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn start(&mut self, decimation: u32, simulation: &simulation::Simulation) {
+        self.decimation = decimation.max(1);
+        self.recording = true;
+        self.push_samples(simulation);
+    }
+
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    // Call after every physics tick
+    pub fn record(&mut self, simulation: &simulation::Simulation) {
+        if self.recording && simulation.counter.is_multiple_of(self.decimation as u64) {
+            self.push_samples(simulation);
+        }
+    }
+
+    fn push_samples(&mut self, simulation: &simulation::Simulation) {
+        let time = simulation.time();
+        for (i, body) in simulation.bodies.iter().enumerate() {
+            let rigid_body = &body.rigid_body;
+            let orientation = nalgebra::UnitQuaternion::from_matrix(&rigid_body.rot_mat);
+            self.samples.push(Sample {
+                time,
+                body: i,
+                pos: rigid_body.pos.into(),
+                lin_vel: rigid_body.lin_vel.into(),
+                orientation: [orientation.w, orientation.i, orientation.j, orientation.k],
+                ang_mom: rigid_body.ang_mom.into(),
+                energy: dynamics::kinetic_energy(rigid_body, body.mass),
+            });
+        }
+    }
+
+    pub fn export(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Csv => {
+                let mut text = String::from(CSV_HEADER);
+                text.push('\n');
+                for sample in self.samples.iter() {
+                    text += &sample.to_csv_row();
+                    text.push('\n');
+                }
+                text
+            }
+            ExportFormat::JsonLines => {
+                let mut text = String::new();
+                for sample in self.samples.iter() {
+                    text += &serde_json::to_string(sample).unwrap();
+                    text.push('\n');
+                }
+                text
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene;
+
+    // Two bodies recorded every other tick over four ticks
+    fn recorder() -> Recorder {
+        let mut scene = scene::Scene::default();
+        let mut second = scene.bodies[0].clone();
+        second.pos = [2.0, 0.0, 0.0];
+        scene.bodies.push(second);
+        let mut simulation = scene.build_simulation().unwrap();
+
+        let mut recorder = Recorder::new();
+        recorder.start(2, &simulation);
+        for _ in 0..4 {
+            simulation.step();
+            recorder.record(&simulation);
+        }
+        recorder.stop();
+        simulation.step();
+        recorder.record(&simulation);
+        recorder
+    }
+
+    #[test]
+    fn records_every_body_at_the_decimated_ticks() {
+        let recorder = recorder();
+        let frames = recorder
+            .samples
+            .iter()
+            .map(|sample| (sample.time, sample.body))
+            .collect::<Vec<_>>();
+        let timestep = scene::Scene::default().integrator.timestep as f64;
+        let expected = [0.0, 2.0, 4.0]
+            .iter()
+            .flat_map(|ticks| [(ticks * timestep, 0), (ticks * timestep, 1)])
+            .collect::<Vec<_>>();
+        assert_eq!(frames.len(), expected.len());
+        for ((time, body), (expected_time, expected_body)) in frames.iter().zip(expected) {
+            assert!((time - expected_time).abs() < 1e-9);
+            assert_eq!(*body, expected_body);
+        }
+    }
+
+    #[test]
+    fn exports_csv_with_a_row_per_sample() {
+        let recorder = recorder();
+        let text = recorder.export(ExportFormat::Csv);
+        let mut lines = text.lines();
+        let header = lines.next().unwrap();
+        assert_eq!(header, CSV_HEADER);
+        let columns = header.split(',').count();
+        assert_eq!(columns, 16);
+
+        let rows = lines.collect::<Vec<_>>();
+        assert_eq!(rows.len(), recorder.samples.len());
+        for (row, sample) in rows.iter().zip(recorder.samples.iter()) {
+            let values = row.split(',').collect::<Vec<_>>();
+            assert_eq!(values.len(), columns);
+            assert_eq!(values[0].parse::<f64>().unwrap(), sample.time);
+            assert_eq!(values[1].parse::<usize>().unwrap(), sample.body);
+            assert_eq!(values[2].parse::<f32>().unwrap(), sample.pos[0]);
+            assert_eq!(values[8].parse::<f32>().unwrap(), sample.orientation[0]);
+            assert_eq!(values[15].parse::<f32>().unwrap(), sample.energy);
+        }
+        assert!(text.ends_with('\n'));
+    }
+
+    #[test]
+    fn exports_json_lines_that_read_back() {
+        let recorder = recorder();
+        let text = recorder.export(ExportFormat::JsonLines);
+        let samples = text
+            .lines()
+            .map(|line| serde_json::from_str::<Sample>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(samples, recorder.samples);
+
+        let first: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+        let mut keys = first
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "ang_mom",
+                "body",
+                "energy",
+                "lin_vel",
+                "orientation",
+                "pos",
+                "time"
+            ]
+        );
+        assert_eq!(first["orientation"].as_array().unwrap().len(), 4);
+    }
+}
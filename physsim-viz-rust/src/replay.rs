@@ -0,0 +1,313 @@
+use crate::recorder;
+
+// Poses of all bodies at one recorded instant
+struct Frame {
+    time: f64,
+    bodies: Vec<Pose>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Pose {
+    pub pos: nalgebra::Vector3<f32>,
+    pub lin_vel: nalgebra::Vector3<f32>,
+    pub orientation: nalgebra::UnitQuaternion<f32>,
+    pub ang_mom: nalgebra::Vector3<f32>,
+}
+
+impl Pose {
+    fn from_sample(sample: &recorder::Sample) -> Self {
+        let [w, x, y, z] = sample.orientation;
+        Self {
+            pos: sample.pos.into(),
+            lin_vel: sample.lin_vel.into(),
+            orientation: nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(
+                w, x, y, z,
+            )),
+            ang_mom: sample.ang_mom.into(),
+        }
+    }
+
+    fn interpolate(&self, other: &Pose, t: f32) -> Pose {
+        // Take the shorter way around, which also keeps slerp well-defined
+        let other_orientation = if self.orientation.coords.dot(&other.orientation.coords) < 0.0 {
+            nalgebra::UnitQuaternion::new_unchecked(-other.orientation.into_inner())
+        } else {
+            other.orientation
+        };
+
+        Pose {
+            pos: self.pos.lerp(&other.pos, t),
+            lin_vel: self.lin_vel.lerp(&other.lin_vel, t),
+            orientation: self.orientation.slerp(&other_orientation, t),
+            ang_mom: self.ang_mom.lerp(&other.ang_mom, t),
+        }
+    }
+}
+
+pub struct Trajectory {
+    frames: Vec<Frame>,
+}
+
+impl Trajectory {
+    pub fn from_samples(samples: &[recorder::Sample]) -> Result<Self, String> {
+        if let Some(sample) = samples.iter().find(|s| !s.time.is_finite()) {
+            return Err(format!("body {}: time must be finite", sample.body));
+        }
+        // Every body needs a sample in each frame, so there can't be more
+        // bodies than samples
+        if let Some(sample) = samples.iter().find(|s| s.body >= samples.len()) {
+            return Err(format!(
+                "t = {}: body index {} out of range",
+                sample.time, sample.body
+            ));
+        }
+        let body_count = samples.iter().map(|s| s.body + 1).max().unwrap_or(0);
+        if body_count == 0 {
+            return Err(String::from("Trajectory is empty"));
+        }
+
+        let mut samples = samples.iter().collect::<Vec<_>>();
+        samples.sort_by(|a, b| a.time.total_cmp(&b.time).then(a.body.cmp(&b.body)));
+
+        let mut frames: Vec<Frame> = Vec::new();
+        for group in samples.chunk_by(|a, b| a.time == b.time) {
+            let time = group[0].time;
+            let mut bodies = vec![None; body_count];
+            for sample in group {
+                bodies[sample.body] = Some(Pose::from_sample(sample));
+            }
+            let bodies = bodies
+                .into_iter()
+                .enumerate()
+                .map(|(i, pose)| pose.ok_or_else(|| format!("t = {}: missing body {}", time, i)))
+                .collect::<Result<Vec<_>, _>>()?;
+            frames.push(Frame { time, bodies });
+        }
+
+        Ok(Self { frames })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let first_line = text.lines().find(|line| !line.trim().is_empty());
+        let samples = match first_line {
+            Some(line) if line.trim_start().starts_with('{') => parse_json_lines(text)?,
+            Some(_) => parse_csv(text)?,
+            None => return Err(String::from("Trajectory is empty")),
+        };
+        Self::from_samples(&samples)
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.frames[0].time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.frames[self.frames.len() - 1].time
+    }
+
+    pub fn poses_at(&self, time: f64) -> Vec<Pose> {
+        let next = self.frames.partition_point(|frame| frame.time <= time);
+        if next == 0 {
+            return self.frames[0].bodies.clone();
+        }
+        if next == self.frames.len() {
+            return self.frames[next - 1].bodies.clone();
+        }
+
+        let (a, b) = (&self.frames[next - 1], &self.frames[next]);
+        let t = ((time - a.time) / (b.time - a.time)) as f32;
+        a.bodies
+            .iter()
+            .zip(b.bodies.iter())
+            .map(|(pose_a, pose_b)| pose_a.interpolate(pose_b, t))
+            .collect()
+    }
+}
+
+fn parse_json_lines(text: &str) -> Result<Vec<recorder::Sample>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|err| format!("line {}: {}", i + 1, err))
+        })
+        .collect()
+}
+
+fn parse_csv(text: &str) -> Result<Vec<recorder::Sample>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or("Trajectory is empty")?;
+    let columns = header.split(',').map(str::trim).collect::<Vec<_>>();
+    let column = |name: &str| {
+        columns
+            .iter()
+            .position(|c| *c == name)
+            .ok_or_else(|| format!("line 1: missing column {}", name))
+    };
+
+    let time_idx = column("time")?;
+    let body_idx = column("body")?;
+    let vector_idx = |prefix: &str| -> Result<[usize; 3], String> {
+        Ok([
+            column(&format!("{}_x", prefix))?,
+            column(&format!("{}_y", prefix))?,
+            column(&format!("{}_z", prefix))?,
+        ])
+    };
+    let pos_idx = vector_idx("pos")?;
+    let lin_vel_idx = vector_idx("lin_vel")?;
+    let ang_mom_idx = vector_idx("ang_mom")?;
+    let quat_idx = [
+        column("quat_w")?,
+        column("quat_x")?,
+        column("quat_y")?,
+        column("quat_z")?,
+    ];
+    // Derived quantity, not needed for playback
+    let energy_idx = column("energy").ok();
+
+    lines
+        .map(|(i, line)| {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let field = |idx: usize| -> Result<&str, String> {
+                fields
+                    .get(idx)
+                    .copied()
+                    .ok_or_else(|| format!("line {}: missing column {}", i + 1, columns[idx]))
+            };
+            let number = |idx: usize| -> Result<f32, String> {
+                field(idx)?
+                    .parse::<f32>()
+                    .map_err(|err| format!("line {}, column {}: {}", i + 1, columns[idx], err))
+            };
+            let vector = |idx: [usize; 3]| -> Result<[f32; 3], String> {
+                Ok([number(idx[0])?, number(idx[1])?, number(idx[2])?])
+            };
+
+            Ok(recorder::Sample {
+                time: field(time_idx)?
+                    .parse::<f64>()
+                    .map_err(|err| format!("line {}, column time: {}", i + 1, err))?,
+                body: field(body_idx)?
+                    .parse::<usize>()
+                    .map_err(|err| format!("line {}, column body: {}", i + 1, err))?,
+                pos: vector(pos_idx)?,
+                lin_vel: vector(lin_vel_idx)?,
+                orientation: [
+                    number(quat_idx[0])?,
+                    number(quat_idx[1])?,
+                    number(quat_idx[2])?,
+                    number(quat_idx[3])?,
+                ],
+                ang_mom: vector(ang_mom_idx)?,
+                energy: match energy_idx {
+                    Some(idx) => number(idx)?,
+                    None => 0.0,
+                },
+            })
+        })
+        .collect()
+}
+
+pub struct Playback {
+    pub trajectory: Trajectory,
+    pub time: f64,
+    pub speed: f64,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl Playback {
+    pub fn new(trajectory: Trajectory) -> Self {
+        Self {
+            time: trajectory.start_time(),
+            trajectory,
+            speed: 1.0,
+            playing: true,
+            looping: false,
+        }
+    }
+
+    // Advances by `dt` seconds of wall-clock time
+    pub fn advance(&mut self, dt: f64) {
+        if !self.playing {
+            return;
+        }
+
+        let start = self.trajectory.start_time();
+        let end = self.trajectory.end_time();
+        self.time += dt * self.speed;
+        if self.time > end || self.time < start {
+            if self.looping && end > start {
+                self.time = start + (self.time - start).rem_euclid(end - start);
+            } else {
+                self.time = self.time.clamp(start, end);
+                self.playing = false;
+            }
+        }
+    }
+
+    pub fn seek(&mut self, time: f64) {
+        if time.is_nan() {
+            return;
+        }
+        self.time = time.clamp(self.trajectory.start_time(), self.trajectory.end_time());
+    }
+
+    pub fn poses(&self) -> Vec<Pose> {
+        self.trajectory.poses_at(self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(time: f64, body: usize) -> recorder::Sample {
+        recorder::Sample {
+            time,
+            body,
+            pos: [0.0; 3],
+            lin_vel: [0.0; 3],
+            orientation: [1.0, 0.0, 0.0, 0.0],
+            ang_mom: [0.0; 3],
+            energy: 0.0,
+        }
+    }
+
+    #[test]
+    fn rejects_non_finite_times() {
+        for time in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(Trajectory::from_samples(&[sample(0.0, 0), sample(time, 0)]).is_err());
+        }
+
+        let csv = "time,body,pos_x,pos_y,pos_z,lin_vel_x,lin_vel_y,lin_vel_z,\
+                   quat_w,quat_x,quat_y,quat_z,ang_mom_x,ang_mom_y,ang_mom_z\n\
+                   0,0,0,0,0,0,0,0,1,0,0,0,0,0,0\n\
+                   NaN,0,0,0,0,0,0,0,1,0,0,0,0,0,0\n";
+        assert!(Trajectory::parse(csv).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_bodies() {
+        assert!(Trajectory::from_samples(&[sample(0.0, usize::MAX)]).is_err());
+        assert!(Trajectory::from_samples(&[sample(0.0, 0), sample(0.0, 1 << 40)]).is_err());
+        assert!(Trajectory::from_samples(&[sample(0.0, 0), sample(0.0, 1)]).is_ok());
+    }
+
+    #[test]
+    fn seeking_stays_within_the_trajectory() {
+        let trajectory =
+            Trajectory::from_samples(&[sample(1.0, 0), sample(2.0, 0), sample(3.0, 0)]).unwrap();
+        let mut playback = Playback::new(trajectory);
+        playback.seek(f64::NAN);
+        assert_eq!(playback.time, 1.0);
+        playback.seek(10.0);
+        assert_eq!(playback.time, 3.0);
+        playback.seek(-10.0);
+        assert_eq!(playback.time, 1.0);
+    }
+}
@@ -0,0 +1,892 @@
+use crate::body;
+use crate::contact;
+use crate::forces;
+use crate::gravity;
+use crate::joints;
+use crate::overlays;
+use crate::simulation;
+use crate::springs;
+use crate::trails;
+
+pub const SCENE_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Ron,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "ron" => Some(Format::Ron),
+            _ => None,
+        }
+    }
+
+    // JSON scenes are objects, RON scenes are structs (optionally named)
+    pub fn detect(text: &str) -> Self {
+        if text.trim_start().starts_with('{') {
+            Format::Json
+        } else {
+            Format::Ron
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    // Path of the offending field, e.g. `bodies[1].mass`
+    pub path: String,
+    pub message: String,
+}
+
+impl SceneError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    pub version: u32,
+    pub bodies: Vec<BodySpec>,
+    #[serde(default)]
+    pub camera: CameraSpec,
+    #[serde(default)]
+    pub integrator: IntegratorSpec,
+    #[serde(default)]
+    pub wireframe: bool,
+    #[serde(default)]
+    pub overlays: overlays::Overlays,
+    #[serde(default)]
+    pub trails: Vec<TrailSpec>,
+    #[serde(default)]
+    pub forces: Vec<forces::ForceGenerator>,
+    // Pairwise attraction between all bodies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mutual_gravity: Option<gravity::MutualGravity>,
+    #[serde(default)]
+    pub joints: Vec<joints::Joint>,
+    #[serde(default)]
+    pub springs: Vec<springs::Spring>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ground: Option<GroundSpec>,
+    // Whether bodies collide with each other
+    #[serde(default = "enabled")]
+    pub collisions: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodySpec {
+    #[serde(default)]
+    pub name: String,
+    pub shape: body::Shape,
+    pub mass: f32,
+    #[serde(default)]
+    pub material: body::Material,
+    // Body-frame inertia tensor (rows); derived from the shape when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inertia: Option<[[f32; 3]; 3]>,
+    #[serde(default)]
+    pub pos: [f32; 3],
+    #[serde(default)]
+    pub lin_vel: [f32; 3],
+    // Quaternion [w, x, y, z], normalized on load
+    #[serde(default = "identity_quaternion")]
+    pub orientation: [f32; 4],
+    #[serde(default)]
+    pub ang_mom: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraSpec {
+    #[serde(default)]
+    pub pos: [f32; 3],
+    // Quaternion [w, x, y, z], normalized on load
+    #[serde(default = "identity_quaternion")]
+    pub orientation: [f32; 4],
+}
+
+impl Default for CameraSpec {
+    fn default() -> Self {
+        Self {
+            pos: [0.0; 3],
+            orientation: identity_quaternion(),
+        }
+    }
+}
+
+// Static plane of the points x with normal . x = offset, bodies are kept on
+// the side the normal points to
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroundSpec {
+    // Normalized on load
+    #[serde(default = "up")]
+    pub normal: [f32; 3],
+    #[serde(default)]
+    pub offset: f32,
+    #[serde(default)]
+    pub material: body::Material,
+}
+
+impl Default for GroundSpec {
+    fn default() -> Self {
+        Self {
+            normal: up(),
+            offset: 0.0,
+            material: body::Material::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IntegratorSpec {
+    // Seconds of simulated time per physics tick
+    pub timestep: f32,
+    // Number of `step_sim` calls each tick is split into
+    #[serde(default = "default_substeps")]
+    pub substeps: u32,
+}
+
+impl Default for IntegratorSpec {
+    fn default() -> Self {
+        Self {
+            timestep: 0.01,
+            substeps: default_substeps(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrailSpec {
+    pub body: usize,
+    // Body-fixed point, the center of mass by default
+    #[serde(default)]
+    pub point: [f32; 3],
+    pub length: usize,
+    #[serde(default = "default_trail_interval")]
+    pub interval: u32,
+    pub color: [f32; 3],
+}
+
+fn identity_quaternion() -> [f32; 4] {
+    [1.0, 0.0, 0.0, 0.0]
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_substeps() -> u32 {
+    1
+}
+
+fn default_trail_interval() -> u32 {
+    1
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            bodies: vec![BodySpec {
+                name: String::from("cube"),
+                shape: body::Shape::Cuboid {
+                    half_extents: [0.5, 0.5, 0.5],
+                },
+                mass: 1.0,
+                material: body::Material::default(),
+                inertia: Some([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+                pos: [0.0, 0.0, 0.0],
+                lin_vel: [0.1, 0.0, 0.0],
+                orientation: identity_quaternion(),
+                ang_mom: [0.5, 0.0, 0.0],
+            }],
+            camera: CameraSpec::default(),
+            integrator: IntegratorSpec::default(),
+            wireframe: false,
+            overlays: overlays::Overlays::default(),
+            trails: vec![
+                TrailSpec {
+                    body: 0,
+                    point: [0.0, 0.0, 0.0],
+                    length: 512,
+                    interval: 5,
+                    color: [1.0, 1.0, 0.5],
+                },
+                TrailSpec {
+                    body: 0,
+                    point: [0.5, 0.5, 0.5],
+                    length: 256,
+                    interval: 2,
+                    color: [1.0, 0.5, 0.2],
+                },
+            ],
+            forces: Vec::new(),
+            mutual_gravity: None,
+            joints: Vec::new(),
+            springs: Vec::new(),
+            ground: None,
+            collisions: true,
+        }
+    }
+}
+
+impl Scene {
+    pub fn parse(text: &str) -> Result<Self, SceneError> {
+        Self::parse_as(text, Format::detect(text))
+    }
+
+    pub fn parse_as(text: &str, format: Format) -> Result<Self, SceneError> {
+        let scene: Scene = match format {
+            Format::Json => {
+                let de = &mut serde_json::Deserializer::from_str(text);
+                serde_path_to_error::deserialize(de).map_err(|err| {
+                    SceneError::new(err.path().to_string(), err.inner().to_string())
+                })?
+            }
+            Format::Ron => {
+                let mut de = ron::Deserializer::from_str(text)
+                    .map_err(|err| SceneError::new("", err.to_string()))?;
+                serde_path_to_error::deserialize(&mut de).map_err(|err| {
+                    SceneError::new(err.path().to_string(), err.inner().to_string())
+                })?
+            }
+        };
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn to_text(&self, format: Format) -> String {
+        match format {
+            Format::Json => serde_json::to_string_pretty(self).unwrap(),
+            Format::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap()
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), SceneError> {
+        if self.version != SCENE_VERSION {
+            return Err(SceneError::new(
+                "version",
+                format!(
+                    "unsupported version {} (expected {})",
+                    self.version, SCENE_VERSION
+                ),
+            ));
+        }
+
+        for (i, body) in self.bodies.iter().enumerate() {
+            body.validate(&format!("bodies[{}]", i))?;
+        }
+
+        check_vector("camera.pos", &self.camera.pos)?;
+        check_quaternion("camera.orientation", &self.camera.orientation)?;
+
+        check_integrator(self.integrator.timestep, self.integrator.substeps)?;
+
+        for (i, trail) in self.trails.iter().enumerate() {
+            let path = format!("trails[{}]", i);
+            check_body(&format!("{}.body", path), trail.body, self.bodies.len())?;
+            check_vector(&format!("{}.point", path), &trail.point)?;
+            if trail.length > trails::MAX_LENGTH {
+                return Err(SceneError::new(
+                    format!("{}.length", path),
+                    format!("must be at most {}", trails::MAX_LENGTH),
+                ));
+            }
+            if trail.interval == 0 {
+                return Err(SceneError::new(
+                    format!("{}.interval", path),
+                    "must be at least 1",
+                ));
+            }
+        }
+
+        check_interactions(
+            &self.forces,
+            self.mutual_gravity.as_ref(),
+            &self.joints,
+            &self.springs,
+            self.bodies.len(),
+        )?;
+
+        if let Some(ground) = &self.ground {
+            check_ground(&ground.normal, ground.offset, &ground.material)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn build_simulation(&self) -> Result<simulation::Simulation, SceneError> {
+        let bodies = self
+            .bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| body.build(&format!("bodies[{}]", i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(simulation::Simulation {
+            joints: self
+                .joints
+                .iter()
+                .map(|joint| joint.resolve(&bodies))
+                .collect(),
+            bodies,
+            forces: self.forces.clone(),
+            mutual_gravity: self.mutual_gravity.clone(),
+            springs: self.springs.clone(),
+            ground: self.ground.as_ref().map(|ground| contact::Plane {
+                normal: nalgebra::Vector3::from(ground.normal).normalize(),
+                offset: ground.offset,
+                material: ground.material,
+            }),
+            collisions: self.collisions,
+            contacts: Vec::new(),
+            timestep: self.integrator.timestep,
+            substeps: self.integrator.substeps,
+            counter: 0,
+        })
+    }
+
+    pub fn build_trails(&self) -> Vec<trails::Trail> {
+        self.trails
+            .iter()
+            .map(|trail| {
+                trails::Trail::new(
+                    trail.body,
+                    nalgebra::Vector3::from(trail.point),
+                    trail.length,
+                    trail.interval,
+                    (trail.color[0], trail.color[1], trail.color[2]),
+                )
+            })
+            .collect()
+    }
+
+    pub fn camera_pos(&self) -> nalgebra::Vector3<f32> {
+        nalgebra::Vector3::from(self.camera.pos)
+    }
+
+    pub fn camera_rot(&self) -> nalgebra::Rotation3<f32> {
+        quaternion_from_array(&self.camera.orientation).to_rotation_matrix()
+    }
+
+    // Describes the current state, so that loading it continues from there
+    pub fn capture(
+        simulation: &simulation::Simulation,
+        camera_pos: &nalgebra::Vector3<f32>,
+        camera_rot: &nalgebra::Rotation3<f32>,
+        wireframe: bool,
+        overlays: &overlays::Overlays,
+        trails: &[trails::Trail],
+    ) -> Result<Self, SceneError> {
+        let bodies = simulation
+            .bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| BodySpec::capture(&format!("bodies[{}]", i), body))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            version: SCENE_VERSION,
+            bodies,
+            camera: CameraSpec {
+                pos: (*camera_pos).into(),
+                orientation: quaternion_to_array(&nalgebra::UnitQuaternion::from_rotation_matrix(
+                    camera_rot,
+                )),
+            },
+            integrator: IntegratorSpec {
+                timestep: simulation.timestep,
+                substeps: simulation.substeps,
+            },
+            wireframe,
+            overlays: overlays.clone(),
+            trails: trails
+                .iter()
+                .map(|trail| TrailSpec {
+                    body: trail.body,
+                    point: trail.point.into(),
+                    length: trail.length(),
+                    interval: trail.interval,
+                    color: [trail.color.0, trail.color.1, trail.color.2],
+                })
+                .collect(),
+            forces: simulation.forces.clone(),
+            mutual_gravity: simulation.mutual_gravity.clone(),
+            joints: simulation.joints.clone(),
+            springs: simulation.springs.clone(),
+            ground: simulation.ground.as_ref().map(|ground| GroundSpec {
+                normal: ground.normal.into(),
+                offset: ground.offset,
+                material: ground.material,
+            }),
+            collisions: simulation.collisions,
+        })
+    }
+}
+
+impl BodySpec {
+    pub fn validate(&self, path: &str) -> Result<(), SceneError> {
+        check_shape(path, &self.shape)?;
+        check_mass(path, self.mass)?;
+        check_material(&format!("{}.material", path), &self.material)?;
+        self.inv_ine(path)?;
+
+        check_vector(&format!("{}.pos", path), &self.pos)?;
+        check_vector(&format!("{}.lin_vel", path), &self.lin_vel)?;
+        check_quaternion(&format!("{}.orientation", path), &self.orientation)?;
+        check_vector(&format!("{}.ang_mom", path), &self.ang_mom)?;
+
+        Ok(())
+    }
+
+    pub fn build(&self, path: &str) -> Result<body::Body, SceneError> {
+        let inv_ine = self.inv_ine(path)?;
+
+        Ok(body::Body {
+            name: self.name.clone(),
+            shape: self.shape,
+            mass: self.mass,
+            material: self.material,
+            rigid_body: physsim::RigidBody {
+                pos: nalgebra::Vector3::from(self.pos),
+                lin_vel: nalgebra::Vector3::from(self.lin_vel),
+                rot_mat: quaternion_from_array(&self.orientation)
+                    .to_rotation_matrix()
+                    .into_inner(),
+                ang_mom: nalgebra::Vector3::from(self.ang_mom),
+                inv_ine,
+            },
+        })
+    }
+
+    // Checks the inertia in use, which is derived from the shape unless given.
+    // Tiny bodies can have one that underflows, or whose inverse overflows.
+    fn inv_ine(&self, path: &str) -> Result<nalgebra::Matrix3<f32>, SceneError> {
+        let path = format!("{}.inertia", path);
+        let inertia = match &self.inertia {
+            Some(inertia) => matrix_from_rows(inertia),
+            None => self.shape.inertia(self.mass),
+        };
+        check_inertia(&path, &inertia)?;
+        let inv_ine = inertia
+            .try_inverse()
+            .ok_or_else(|| SceneError::new(&path, "isn't invertible"))?;
+        check_inertia(&path, &inv_ine)?;
+        Ok(inv_ine)
+    }
+
+    // Fails for bodies whose inverse inertia was edited into a singular one
+    pub fn capture(path: &str, body: &body::Body) -> Result<Self, SceneError> {
+        let rigid_body = &body.rigid_body;
+        let inertia = rigid_body.inv_ine.try_inverse().ok_or_else(|| {
+            SceneError::new(
+                format!("{}.inertia", path),
+                "inverse inertia isn't invertible",
+            )
+        })?;
+        let orientation = nalgebra::UnitQuaternion::from_matrix(&rigid_body.rot_mat);
+
+        Ok(Self {
+            name: body.name.clone(),
+            shape: body.shape,
+            mass: body.mass,
+            material: body.material,
+            inertia: Some([
+                [inertia[(0, 0)], inertia[(0, 1)], inertia[(0, 2)]],
+                [inertia[(1, 0)], inertia[(1, 1)], inertia[(1, 2)]],
+                [inertia[(2, 0)], inertia[(2, 1)], inertia[(2, 2)]],
+            ]),
+            pos: rigid_body.pos.into(),
+            lin_vel: rigid_body.lin_vel.into(),
+            orientation: quaternion_to_array(&orientation),
+            ang_mom: rigid_body.ang_mom.into(),
+        })
+    }
+}
+
+fn matrix_from_rows(rows: &[[f32; 3]; 3]) -> nalgebra::Matrix3<f32> {
+    nalgebra::Matrix3::from_row_slice(rows.as_flattened())
+}
+
+fn quaternion_from_array(q: &[f32; 4]) -> nalgebra::UnitQuaternion<f32> {
+    nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(q[0], q[1], q[2], q[3]))
+}
+
+fn quaternion_to_array(q: &nalgebra::UnitQuaternion<f32>) -> [f32; 4] {
+    [q.w, q.i, q.j, q.k]
+}
+
+// Checks shared with snapshots, which describe the same state differently
+
+pub(crate) fn check_integrator(timestep: f32, substeps: u32) -> Result<(), SceneError> {
+    if !(timestep > 0.0 && timestep.is_finite()) {
+        return Err(SceneError::new(
+            "integrator.timestep",
+            "must be a positive number",
+        ));
+    }
+    if substeps == 0 {
+        return Err(SceneError::new("integrator.substeps", "must be at least 1"));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_interactions(
+    forces: &[forces::ForceGenerator],
+    mutual_gravity: Option<&gravity::MutualGravity>,
+    joints: &[joints::Joint],
+    springs: &[springs::Spring],
+    body_count: usize,
+) -> Result<(), SceneError> {
+    for (i, force) in forces.iter().enumerate() {
+        force.validate(&format!("forces[{}]", i), body_count)?;
+    }
+    if let Some(mutual_gravity) = mutual_gravity {
+        mutual_gravity.validate("mutual_gravity")?;
+    }
+    for (i, joint) in joints.iter().enumerate() {
+        joint.validate(&format!("joints[{}]", i), body_count)?;
+    }
+    for (i, spring) in springs.iter().enumerate() {
+        spring.validate(&format!("springs[{}]", i), body_count)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn check_ground(
+    normal: &[f32; 3],
+    offset: f32,
+    material: &body::Material,
+) -> Result<(), SceneError> {
+    check_vector("ground.normal", normal)?;
+    if normal.iter().map(|x| x * x).sum::<f32>() < 1e-12 {
+        return Err(SceneError::new(
+            "ground.normal",
+            "must be a non-zero vector",
+        ));
+    }
+    check_vector("ground.offset", &[offset])?;
+    check_material("ground.material", material)
+}
+
+// `path` is the body's
+pub(crate) fn check_shape(path: &str, shape: &body::Shape) -> Result<(), SceneError> {
+    match shape {
+        body::Shape::Cuboid { half_extents } => {
+            if !half_extents.iter().all(|h| *h > 0.0 && h.is_finite()) {
+                return Err(SceneError::new(
+                    format!("{}.shape.half_extents", path),
+                    "must be positive numbers",
+                ));
+            }
+        }
+        body::Shape::Sphere { radius } => {
+            if !(*radius > 0.0 && radius.is_finite()) {
+                return Err(SceneError::new(
+                    format!("{}.shape.radius", path),
+                    "must be a positive number",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// `path` is the body's
+pub(crate) fn check_mass(path: &str, mass: f32) -> Result<(), SceneError> {
+    if mass > 0.0 && mass.is_finite() {
+        Ok(())
+    } else {
+        Err(SceneError::new(
+            format!("{}.mass", path),
+            "must be a positive number",
+        ))
+    }
+}
+
+// Holds for an inertia tensor and its inverse alike
+pub(crate) fn check_inertia(
+    path: &str,
+    inertia: &nalgebra::Matrix3<f32>,
+) -> Result<(), SceneError> {
+    if !inertia.iter().all(|x| x.is_finite()) {
+        return Err(SceneError::new(path, "must contain only finite numbers"));
+    }
+    if (inertia - inertia.transpose()).amax() > 1e-6 * inertia.amax() {
+        return Err(SceneError::new(path, "must be symmetric"));
+    }
+    if nalgebra::SymmetricEigen::new(*inertia).eigenvalues.min() <= 0.0 {
+        return Err(SceneError::new(path, "must be positive definite"));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_vector(path: &str, v: &[f32]) -> Result<(), SceneError> {
+    if v.iter().all(|x| x.is_finite()) {
+        Ok(())
+    } else {
+        Err(SceneError::new(path, "must contain only finite numbers"))
+    }
+}
+
+// Axes and such, which have a direction
+pub(crate) fn check_non_zero(path: &str, v: &[f32]) -> Result<(), SceneError> {
+    check_vector(path, v)?;
+    if v.iter().map(|x| x * x).sum::<f32>() < 1e-12 {
+        return Err(SceneError::new(path, "must not be zero"));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_non_negative(path: &str, x: f32) -> Result<(), SceneError> {
+    if x >= 0.0 && x.is_finite() {
+        Ok(())
+    } else {
+        Err(SceneError::new(path, "must be a non-negative number"))
+    }
+}
+
+pub(crate) fn check_body(path: &str, body: usize, body_count: usize) -> Result<(), SceneError> {
+    if body < body_count {
+        Ok(())
+    } else {
+        Err(SceneError::new(
+            path,
+            format!("no body with index {}", body),
+        ))
+    }
+}
+
+fn check_quaternion(path: &str, q: &[f32; 4]) -> Result<(), SceneError> {
+    check_vector(path, q)?;
+    if q.iter().map(|x| x * x).sum::<f32>() < 1e-12 {
+        return Err(SceneError::new(path, "must be a non-zero quaternion"));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_material(path: &str, material: &body::Material) -> Result<(), SceneError> {
+    if !(0.0..=1.0).contains(&material.restitution) {
+        return Err(SceneError::new(
+            format!("{}.restitution", path),
+            "must be between 0 and 1",
+        ));
+    }
+    check_non_negative(&format!("{}.friction", path), material.friction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The default scene with a second body and one of everything that
+    // refers to bodies
+    fn busy_scene() -> Scene {
+        let mut scene = Scene::default();
+        let mut ball = scene.bodies[0].clone();
+        ball.name = String::from("ball");
+        ball.shape = body::Shape::Sphere { radius: 0.25 };
+        ball.inertia = None;
+        ball.pos = [2.0, 0.5, -1.0];
+        ball.orientation = [0.8, 0.6, 0.0, 0.0];
+        scene.bodies.push(ball);
+        scene.forces = vec![
+            forces::ForceGenerator::Gravity {
+                acceleration: [0.0, -9.81, 0.0],
+            },
+            forces::ForceGenerator::LinearDamping {
+                body: Some(1),
+                coefficient: 0.1,
+            },
+        ];
+        scene.mutual_gravity = Some(gravity::MutualGravity {
+            constant: 0.5,
+            softening: 0.1,
+            opening_angle: 0.5,
+        });
+        scene.joints = vec![joints::Joint {
+            body: 0,
+            other: Some(1),
+            anchor: [0.5, 0.0, 0.0],
+            other_anchor: None,
+            kind: joints::JointKind::Ball {},
+        }];
+        scene.springs = vec![springs::Spring {
+            body: 1,
+            other: None,
+            anchor: [0.0, 0.25, 0.0],
+            other_anchor: [2.0, 3.0, -1.0],
+            rest_length: 1.0,
+            stiffness: 20.0,
+            damping: 0.5,
+        }];
+        scene.ground = Some(GroundSpec {
+            normal: [0.0, 1.0, 0.0],
+            offset: -2.0,
+            material: body::Material::default(),
+        });
+        scene
+    }
+
+    fn error_path(edit: impl FnOnce(&mut Scene)) -> String {
+        let mut scene = busy_scene();
+        edit(&mut scene);
+        let text = scene.to_text(Format::Json);
+        Scene::parse(&text).unwrap_err().path
+    }
+
+    #[test]
+    fn round_trips_through_json_and_ron() {
+        let scene = busy_scene();
+        for format in [Format::Json, Format::Ron] {
+            let text = scene.to_text(format);
+            assert_eq!(Scene::parse(&text).unwrap(), scene, "{:?}", format);
+            assert_eq!(Scene::parse_as(&text, format).unwrap(), scene);
+        }
+    }
+
+    #[test]
+    fn captures_what_it_builds() {
+        let scene = busy_scene();
+        let simulation = scene.build_simulation().unwrap();
+        let captured = Scene::capture(
+            &simulation,
+            &scene.camera_pos(),
+            &scene.camera_rot(),
+            scene.wireframe,
+            &scene.overlays,
+            &scene.build_trails(),
+        )
+        .unwrap();
+        let rebuilt = Scene::parse(&captured.to_text(Format::Ron))
+            .unwrap()
+            .build_simulation()
+            .unwrap();
+        for (a, b) in simulation.bodies.iter().zip(rebuilt.bodies.iter()) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.rigid_body.pos, b.rigid_body.pos);
+            assert!((a.rigid_body.rot_mat - b.rigid_body.rot_mat).norm() < 1e-6);
+            assert!((a.rigid_body.inv_ine - b.rigid_body.inv_ine).norm() < 1e-4);
+        }
+        assert_eq!(rebuilt.bodies.len(), 2);
+        assert_eq!(rebuilt.joints, simulation.joints);
+        assert_eq!(rebuilt.springs, simulation.springs);
+        assert_eq!(captured.trails.len(), 2);
+    }
+
+    #[test]
+    fn capture_rejects_singular_inertia() {
+        let mut simulation = busy_scene().build_simulation().unwrap();
+        simulation.bodies[1].rigid_body.inv_ine = nalgebra::Matrix3::zeros();
+        let err = BodySpec::capture("bodies[1]", &simulation.bodies[1]).unwrap_err();
+        assert_eq!(err.path, "bodies[1].inertia");
+    }
+
+    #[test]
+    fn reports_where_validation_failed() {
+        assert_eq!(error_path(|scene| scene.version = 0), "version");
+        assert_eq!(
+            error_path(|scene| scene.bodies[1].mass = -1.0),
+            "bodies[1].mass"
+        );
+        assert_eq!(
+            error_path(|scene| scene.bodies[1].shape = body::Shape::Sphere { radius: 0.0 }),
+            "bodies[1].shape.radius"
+        );
+        assert_eq!(
+            error_path(|scene| scene.bodies[0].material.restitution = 2.0),
+            "bodies[0].material.restitution"
+        );
+        assert_eq!(
+            error_path(|scene| scene.integrator.substeps = 0),
+            "integrator.substeps"
+        );
+        assert_eq!(
+            error_path(
+                |scene| scene.forces[1] = forces::ForceGenerator::LinearDamping {
+                    body: Some(2),
+                    coefficient: 0.1,
+                }
+            ),
+            "forces[1].body"
+        );
+        assert_eq!(
+            error_path(|scene| scene.mutual_gravity.as_mut().unwrap().softening = -1.0),
+            "mutual_gravity.softening"
+        );
+        assert_eq!(
+            error_path(|scene| scene.joints[0].other = Some(0)),
+            "joints[0].other"
+        );
+        assert_eq!(
+            error_path(|scene| scene.springs[0].stiffness = -5.0),
+            "springs[0].stiffness"
+        );
+        assert_eq!(
+            error_path(|scene| scene.ground.as_mut().unwrap().normal = [0.0; 3]),
+            "ground.normal"
+        );
+        assert_eq!(
+            error_path(|scene| scene.trails[1].body = 5),
+            "trails[1].body"
+        );
+    }
+
+    #[test]
+    fn rejects_inertia_of_tiny_bodies() {
+        // The inertia derived from the shape underflows, or its inverse
+        // overflows
+        for radius in [1e-25, 1e-20] {
+            let tiny = |scene: &mut Scene| {
+                scene.bodies[1].shape = body::Shape::Sphere { radius };
+                scene.bodies[1].mass = 1.0;
+            };
+            assert_eq!(error_path(tiny), "bodies[1].inertia");
+
+            let mut scene = busy_scene();
+            tiny(&mut scene);
+            match scene.build_simulation() {
+                Err(err) => assert_eq!(err.path, "bodies[1].inertia"),
+                Ok(_) => panic!("built a body with radius {}", radius),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_overlong_trails() {
+        assert_eq!(
+            error_path(|scene| scene.trails[0].length = trails::MAX_LENGTH + 1),
+            "trails[0].length"
+        );
+    }
+}
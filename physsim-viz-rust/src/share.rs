@@ -0,0 +1,81 @@
+use base64::Engine;
+
+use crate::scene;
+
+// URL fragments look like `#scene=<base64url of the deflated compact JSON>`
+const FRAGMENT_PREFIX: &str = "scene=";
+const COMPRESSION_LEVEL: u8 = 9;
+const MAX_DECOMPRESSED_SIZE: usize = 1 << 22;
+
+pub fn encode(scene: &scene::Scene) -> String {
+    let json = serde_json::to_vec(scene).unwrap();
+    let compressed = miniz_oxide::deflate::compress_to_vec(&json, COMPRESSION_LEVEL);
+    format!(
+        "{}{}",
+        FRAGMENT_PREFIX,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(compressed)
+    )
+}
+
+// Accepts the fragment with or without the leading `#`. Returns `None` if
+// the fragment doesn't describe a scene at all.
+pub fn decode(fragment: &str) -> Option<Result<scene::Scene, scene::SceneError>> {
+    let payload = fragment
+        .strip_prefix('#')
+        .unwrap_or(fragment)
+        .strip_prefix(FRAGMENT_PREFIX)?;
+    Some(decode_payload(payload))
+}
+
+fn decode_payload(payload: &str) -> Result<scene::Scene, scene::SceneError> {
+    let compressed = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|err| scene::SceneError::new("", format!("Invalid scene link: {}", err)))?;
+    let json =
+        miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, MAX_DECOMPRESSED_SIZE)
+            .map_err(|err| scene::SceneError::new("", format!("Invalid scene link: {}", err)))?;
+    let text = String::from_utf8(json)
+        .map_err(|err| scene::SceneError::new("", format!("Invalid scene link: {}", err)))?;
+    scene::Scene::parse_as(&text, scene::Format::Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_default_scene() {
+        let scene = scene::Scene::default();
+        let fragment = encode(&scene);
+
+        assert!(fragment.starts_with(FRAGMENT_PREFIX));
+        assert!(fragment[FRAGMENT_PREFIX.len()..]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(decode(&fragment).unwrap().unwrap(), scene);
+        assert_eq!(decode(&format!("#{}", fragment)).unwrap().unwrap(), scene);
+    }
+
+    #[test]
+    fn round_trip_preserves_floats_and_camera() {
+        let mut scene = scene::Scene::default();
+        scene.bodies[0].pos = [0.1, -1.0e-7, 123456.79];
+        scene.bodies[0].ang_mom = [std::f32::consts::PI, 0.3, -0.7];
+        scene.camera.pos = [1.0, 2.0, 3.0];
+        scene.camera.orientation = [0.5, 0.5, 0.5, 0.5];
+
+        assert_eq!(decode(&encode(&scene)).unwrap().unwrap(), scene);
+    }
+
+    #[test]
+    fn ignores_unrelated_fragments() {
+        assert!(decode("").is_none());
+        assert!(decode("#section-2").is_none());
+    }
+
+    #[test]
+    fn rejects_corrupted_payload() {
+        assert!(decode("#scene=not*base64").unwrap().is_err());
+        assert!(decode("#scene=AAAA").unwrap().is_err());
+    }
+}
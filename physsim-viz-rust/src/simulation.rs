@@ -0,0 +1,328 @@
+use crate::body;
+use crate::collision;
+use crate::contact;
+use crate::dynamics;
+use crate::forces;
+use crate::gravity;
+use crate::joints;
+use crate::springs;
+
+// Longest stretch of wall-clock time caught up on in one go, anything beyond
+// that (e.g. a backgrounded tab) is dropped instead of simulated
+const MAX_CATCH_UP: f64 = 0.25;
+
+pub struct Simulation {
+    pub bodies: Vec<body::Body>,
+    pub forces: Vec<forces::ForceGenerator>,
+    pub mutual_gravity: Option<gravity::MutualGravity>,
+    // Resolved, see `joints::Joint::resolve`
+    pub joints: Vec<joints::Joint>,
+    pub springs: Vec<springs::Spring>,
+    pub ground: Option<contact::Plane>,
+    // Whether bodies collide with each other
+    pub collisions: bool,
+    // Contacts resolved during the last step
+    pub contacts: Vec<contact::Contact>,
+    pub timestep: f32,
+    pub substeps: u32,
+    pub counter: u64,
+}
+
+impl Simulation {
+    pub fn time(&self) -> f64 {
+        self.counter as f64 * self.timestep as f64
+    }
+
+    pub fn step(&mut self) {
+        let dt = self.timestep / self.substeps as f32;
+        self.contacts.clear();
+        for _ in 0..self.substeps {
+            for (i, body) in self.bodies.iter_mut().enumerate() {
+                forces::apply(&self.forces, i, body, dt);
+            }
+            if let Some(mutual_gravity) = &self.mutual_gravity {
+                mutual_gravity.apply(&mut self.bodies, dt);
+            }
+            if !self.springs.is_empty() {
+                springs::apply(&self.springs, &mut self.bodies, dt);
+            }
+            if !self.joints.is_empty() {
+                joints::solve(&self.joints, &mut self.bodies, dt);
+            }
+            for body in self.bodies.iter_mut() {
+                body.rigid_body.step_sim(dt);
+            }
+
+            let mut contacts = Vec::new();
+            if let Some(ground) = &self.ground {
+                for (i, body) in self.bodies.iter().enumerate() {
+                    contacts.extend(contact::plane_contacts(ground, i, body));
+                }
+            }
+            if self.collisions {
+                // Jointed bodies usually overlap around the joint
+                let connected: Vec<(usize, usize)> = self
+                    .joints
+                    .iter()
+                    .filter_map(|joint| {
+                        let other = joint.other?;
+                        Some((joint.body.min(other), joint.body.max(other)))
+                    })
+                    .collect();
+                contacts.extend(
+                    collision::detect(&self.bodies)
+                        .into_iter()
+                        .filter(|c| !connected.contains(&(c.body, c.other.unwrap_or(c.body)))),
+                );
+            }
+            contact::resolve(&mut contacts, &mut self.bodies);
+            self.contacts.extend(contacts);
+        }
+        self.counter += 1;
+    }
+
+    pub fn totals(&self) -> dynamics::Totals {
+        self.totals_of(&self.bodies)
+    }
+
+    // Includes the potential of the mutual gravity, so the energy stays
+    // conserved in orbital scenes. Takes the bodies separately for replayed
+    // states.
+    pub fn totals_of(&self, bodies: &[body::Body]) -> dynamics::Totals {
+        let mut totals = dynamics::totals(bodies);
+        if let Some(mutual_gravity) = &self.mutual_gravity {
+            totals.energy += mutual_gravity.potential_energy(bodies);
+        }
+        totals
+    }
+
+    // FNV-1a over the exact bits of the integrator state. Two runs agree on it
+    // iff they are bit-identical, so it pinpoints the first diverging tick.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = StateHasher::new();
+        hash.write(&self.counter.to_le_bytes());
+        hash.write_f32(self.timestep);
+        hash.write(&self.substeps.to_le_bytes());
+        for body in self.bodies.iter() {
+            let rigid_body = &body.rigid_body;
+            hash.write_f32(body.mass);
+            for x in rigid_body
+                .pos
+                .iter()
+                .chain(rigid_body.lin_vel.iter())
+                .chain(rigid_body.rot_mat.iter())
+                .chain(rigid_body.ang_mom.iter())
+                .chain(rigid_body.inv_ine.iter())
+            {
+                hash.write_f32(*x);
+            }
+        }
+        hash.finish()
+    }
+}
+
+struct StateHasher(u64);
+
+impl StateHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_f32(&mut self, x: f32) {
+        self.write(&x.to_bits().to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Turns elapsed wall-clock time into a whole number of fixed steps, so the
+// trajectory only depends on the scene and the step count, never on how often
+// or how regularly the timer fires
+pub struct FixedStepClock {
+    accumulator: f64,
+}
+
+impl Default for FixedStepClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FixedStepClock {
+    pub fn new() -> Self {
+        Self { accumulator: 0.0 }
+    }
+
+    pub fn advance(&mut self, elapsed: f64, timestep: f32) -> u32 {
+        let timestep = timestep as f64;
+        // Never less than a step, or steps longer than the cap would never come
+        let max_catch_up = MAX_CATCH_UP.max(timestep);
+        self.accumulator = (self.accumulator + elapsed.max(0.0)).min(max_catch_up);
+        // Tolerate rounding so that e.g. 100 ticks of 0.01 make 100 steps
+        let steps = ((self.accumulator + 1e-9) / timestep).floor();
+        self.accumulator = (self.accumulator - steps * timestep).max(0.0);
+        steps as u32
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recorder, replay, scene, snapshot};
+
+    const SCENE: &str = r#"{
+        "version": 1,
+        "bodies": [
+            {
+                "name": "brick",
+                "shape": { "type": "cuboid", "half_extents": [0.2, 0.5, 1.0] },
+                "mass": 2.0,
+                "pos": [0.0, 1.0, 0.0],
+                "lin_vel": [0.3, 0.0, -0.1],
+                "ang_mom": [0.4, 1.5, 0.05]
+            },
+            {
+                "name": "ball",
+                "shape": { "type": "sphere", "radius": 0.3 },
+                "mass": 1.0,
+                "ang_mom": [0.0, 0.0, 0.2]
+            }
+        ],
+        "integrator": { "timestep": 0.01, "substeps": 2 }
+    }"#;
+
+    fn simulation() -> Simulation {
+        scene::Scene::parse(SCENE)
+            .unwrap()
+            .build_simulation()
+            .unwrap()
+    }
+
+    fn run(simulation: &mut Simulation, steps: u32) {
+        for _ in 0..steps {
+            simulation.step();
+        }
+    }
+
+    #[test]
+    fn identical_runs_hash_identically() {
+        let mut a = simulation();
+        let mut b = simulation();
+        for _ in 0..500 {
+            a.step();
+            b.step();
+            assert_eq!(a.state_hash(), b.state_hash(), "tick {}", a.counter);
+        }
+    }
+
+    #[test]
+    fn hash_detects_single_bit_changes() {
+        let a = simulation();
+        let mut b = simulation();
+        let pos = &mut b.bodies[1].rigid_body.pos;
+        pos.x = f32::from_bits(pos.x.to_bits() ^ 1);
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn timer_rate_does_not_change_trajectory() {
+        let mut hashes = Vec::new();
+        for rate in [30.0, 60.0, 144.0, 100.0] {
+            let mut simulation = simulation();
+            let mut clock = FixedStepClock::new();
+            for _ in 0..(rate as u32 * 2) {
+                let steps = clock.advance(1.0 / rate, simulation.timestep);
+                run(&mut simulation, steps);
+            }
+            assert_eq!(simulation.counter, 200, "{} Hz", rate);
+            hashes.push(simulation.state_hash());
+        }
+        assert!(hashes.iter().all(|hash| *hash == hashes[0]));
+    }
+
+    #[test]
+    fn timesteps_beyond_catch_up_limit_still_advance() {
+        let mut clock = FixedStepClock::new();
+        let steps: u32 = (0..100).map(|_| clock.advance(0.01, 0.5)).sum();
+        assert_eq!(steps, 2);
+
+        // A long stall still only catches up one such step
+        assert_eq!(clock.advance(10.0, 0.5), 1);
+    }
+
+    #[test]
+    fn restored_snapshot_continues_identically() {
+        let mut original = simulation();
+        run(&mut original, 137);
+        let mut restored = snapshot::restore(&snapshot::take(&original)).unwrap();
+        assert_eq!(original.state_hash(), restored.state_hash());
+
+        run(&mut original, 263);
+        run(&mut restored, 263);
+        assert_eq!(original.state_hash(), restored.state_hash());
+    }
+
+    #[test]
+    fn restore_rejects_unsound_snapshots() {
+        let blob = snapshot::take(&simulation());
+        let tampered = |edit: &dyn Fn(&mut serde_json::Value)| {
+            let mut value: serde_json::Value = serde_json::from_slice(&blob).unwrap();
+            edit(&mut value);
+            snapshot::restore(&serde_json::to_vec(&value).unwrap())
+        };
+
+        assert!(tampered(&|_| {}).is_ok());
+        let err = tampered(&|v| v["timestep"] = 0.0.into()).err().unwrap();
+        assert!(err.contains("integrator.timestep"), "{}", err);
+        assert!(tampered(&|v| v["substeps"] = 0.into()).is_err());
+        let err = tampered(&|v| {
+            v["joints"] = serde_json::json!([{ "body": 0, "other": 5, "kind": { "type": "ball" } }])
+        })
+        .err()
+        .unwrap();
+        assert!(err.contains("joints[0].other"), "{}", err);
+        assert!(tampered(&|v| {
+            v["springs"] = serde_json::json!([{ "body": 9, "rest_length": 1.0, "stiffness": 1.0 }])
+        })
+        .is_err());
+        assert!(tampered(&|v| {
+            v["forces"] =
+                serde_json::json!([{ "type": "constant_force", "body": 2, "force": [1.0, 0.0, 0.0] }])
+        })
+        .is_err());
+        assert!(tampered(&|v| v["bodies"][0]["inv_ine"][1][1] = (-1.0).into()).is_err());
+    }
+
+    #[test]
+    fn replayed_recording_matches_simulation() {
+        let mut simulation = simulation();
+        let mut recorder = recorder::Recorder::new();
+        recorder.start(1, &simulation);
+        let mut expected = Vec::new();
+        for _ in 0..300 {
+            simulation.step();
+            recorder.record(&simulation);
+            expected.push((simulation.time(), simulation.bodies[0].rigid_body.pos));
+        }
+
+        let text = recorder.export(recorder::ExportFormat::JsonLines);
+        let trajectory = replay::Trajectory::parse(&text).unwrap();
+        for (time, pos) in expected {
+            let poses = trajectory.poses_at(time);
+            assert_eq!(poses[0].pos, pos, "t = {}", time);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::body;
+use crate::contact;
+use crate::forces;
+use crate::gravity;
+use crate::joints;
+use crate::scene;
+use crate::simulation;
+use crate::springs;
+
+// Snapshots are opaque to JS, but internally plain JSON. Unlike scenes they
+// keep every matrix element as is, so restoring one continues bit-exactly.
+const SNAPSHOT_MAGIC: &str = "physsim-viz-snapshot";
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+    magic: String,
+    version: u32,
+    timestep: f32,
+    substeps: u32,
+    counter: u64,
+    bodies: Vec<BodySnapshot>,
+    #[serde(default)]
+    forces: Vec<forces::ForceGenerator>,
+    #[serde(default)]
+    mutual_gravity: Option<gravity::MutualGravity>,
+    #[serde(default)]
+    joints: Vec<joints::Joint>,
+    #[serde(default)]
+    springs: Vec<springs::Spring>,
+    #[serde(default)]
+    ground: Option<PlaneSnapshot>,
+    #[serde(default = "enabled")]
+    collisions: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BodySnapshot {
+    name: String,
+    shape: body::Shape,
+    mass: f32,
+    #[serde(default)]
+    material: body::Material,
+    pos: [f32; 3],
+    lin_vel: [f32; 3],
+    // Columns
+    rot_mat: [[f32; 3]; 3],
+    ang_mom: [f32; 3],
+    inv_ine: [[f32; 3]; 3],
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PlaneSnapshot {
+    normal: [f32; 3],
+    offset: f32,
+    material: body::Material,
+}
+
+pub fn take(simulation: &simulation::Simulation) -> Vec<u8> {
+    let snapshot = Snapshot {
+        magic: String::from(SNAPSHOT_MAGIC),
+        version: SNAPSHOT_VERSION,
+        timestep: simulation.timestep,
+        substeps: simulation.substeps,
+        counter: simulation.counter,
+        bodies: simulation
+            .bodies
+            .iter()
+            .map(|body| {
+                let rigid_body = &body.rigid_body;
+                BodySnapshot {
+                    name: body.name.clone(),
+                    shape: body.shape,
+                    mass: body.mass,
+                    material: body.material,
+                    pos: rigid_body.pos.into(),
+                    lin_vel: rigid_body.lin_vel.into(),
+                    rot_mat: rigid_body.rot_mat.into(),
+                    ang_mom: rigid_body.ang_mom.into(),
+                    inv_ine: rigid_body.inv_ine.into(),
+                }
+            })
+            .collect(),
+        forces: simulation.forces.clone(),
+        mutual_gravity: simulation.mutual_gravity.clone(),
+        joints: simulation.joints.clone(),
+        springs: simulation.springs.clone(),
+        ground: simulation.ground.as_ref().map(|ground| PlaneSnapshot {
+            normal: ground.normal.into(),
+            offset: ground.offset,
+            material: ground.material,
+        }),
+        collisions: simulation.collisions,
+    };
+    serde_json::to_vec(&snapshot).unwrap()
+}
+
+pub fn restore(blob: &[u8]) -> Result<simulation::Simulation, String> {
+    let snapshot: Snapshot =
+        serde_json::from_slice(blob).map_err(|err| format!("Invalid snapshot: {}", err))?;
+    if snapshot.magic != SNAPSHOT_MAGIC {
+        return Err(String::from("Invalid snapshot"));
+    }
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported snapshot version {} (expected {})",
+            snapshot.version, SNAPSHOT_VERSION
+        ));
+    }
+    validate(&snapshot).map_err(|err| format!("Invalid snapshot: {}", err))?;
+
+    Ok(simulation::Simulation {
+        bodies: snapshot
+            .bodies
+            .into_iter()
+            .map(|body| body::Body {
+                name: body.name,
+                shape: body.shape,
+                mass: body.mass,
+                material: body.material,
+                rigid_body: physsim::RigidBody {
+                    pos: body.pos.into(),
+                    lin_vel: body.lin_vel.into(),
+                    rot_mat: body.rot_mat.into(),
+                    ang_mom: body.ang_mom.into(),
+                    inv_ine: body.inv_ine.into(),
+                },
+            })
+            .collect(),
+        forces: snapshot.forces,
+        mutual_gravity: snapshot.mutual_gravity,
+        joints: snapshot.joints,
+        springs: snapshot.springs,
+        ground: snapshot.ground.map(|ground| contact::Plane {
+            normal: ground.normal.into(),
+            offset: ground.offset,
+            material: ground.material,
+        }),
+        collisions: snapshot.collisions,
+        contacts: Vec::new(),
+        timestep: snapshot.timestep,
+        substeps: snapshot.substeps,
+        counter: snapshot.counter,
+    })
+}
+
+// The checks scenes go through, as a restored simulation is stepped without
+// any further ones
+fn validate(snapshot: &Snapshot) -> Result<(), scene::SceneError> {
+    scene::check_integrator(snapshot.timestep, snapshot.substeps)?;
+
+    for (i, body) in snapshot.bodies.iter().enumerate() {
+        let path = format!("bodies[{}]", i);
+        scene::check_shape(&path, &body.shape)?;
+        scene::check_mass(&path, body.mass)?;
+        scene::check_material(&format!("{}.material", path), &body.material)?;
+        scene::check_vector(&format!("{}.pos", path), &body.pos)?;
+        scene::check_vector(&format!("{}.lin_vel", path), &body.lin_vel)?;
+        scene::check_vector(&format!("{}.ang_mom", path), &body.ang_mom)?;
+
+        let rot_mat = nalgebra::Matrix3::from(body.rot_mat);
+        scene::check_vector(&format!("{}.rot_mat", path), rot_mat.as_slice())?;
+        if (rot_mat.transpose() * rot_mat - nalgebra::Matrix3::identity()).amax() > 1e-3
+            || rot_mat.determinant() <= 0.0
+        {
+            return Err(scene::SceneError::new(
+                format!("{}.rot_mat", path),
+                "must be a rotation matrix",
+            ));
+        }
+        scene::check_inertia(
+            &format!("{}.inv_ine", path),
+            &nalgebra::Matrix3::from(body.inv_ine),
+        )?;
+    }
+
+    scene::check_interactions(
+        &snapshot.forces,
+        snapshot.mutual_gravity.as_ref(),
+        &snapshot.joints,
+        &snapshot.springs,
+        snapshot.bodies.len(),
+    )?;
+
+    if let Some(ground) = &snapshot.ground {
+        scene::check_ground(&ground.normal, ground.offset, &ground.material)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use crate::body;
+use crate::dynamics;
+use crate::geometry;
+use crate::overlays;
+use crate::scene;
+
+const COILS: usize = 10;
+const SEGMENTS_PER_COIL: usize = 8;
+// Relative extension at which the color saturates
+const FULL_TENSION_STRAIN: f32 = 0.5;
+const STRETCHED_COLOR: (f32, f32, f32) = (1.0, 0.2, 0.2);
+const COMPRESSED_COLOR: (f32, f32, f32) = (0.2, 0.4, 1.0);
+
+// Hookean spring with a linear damper between a body-fixed point on `body`
+// and one on `other`, or a world point if there is no other body
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spring {
+    pub body: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other: Option<usize>,
+    #[serde(default)]
+    pub anchor: [f32; 3],
+    #[serde(default)]
+    pub other_anchor: [f32; 3],
+    pub rest_length: f32,
+    pub stiffness: f32,
+    #[serde(default)]
+    pub damping: f32,
+}
+
+impl Spring {
+    pub fn validate(&self, path: &str, body_count: usize) -> Result<(), scene::SceneError> {
+        let field = |name: &str| format!("{}.{}", path, name);
+
+        scene::check_body(&field("body"), self.body, body_count)?;
+        if let Some(other) = self.other {
+            scene::check_body(&field("other"), other, body_count)?;
+            if other == self.body {
+                return Err(scene::SceneError::new(
+                    field("other"),
+                    "must differ from body",
+                ));
+            }
+        }
+        scene::check_vector(&field("anchor"), &self.anchor)?;
+        scene::check_vector(&field("other_anchor"), &self.other_anchor)?;
+        scene::check_non_negative(&field("rest_length"), self.rest_length)?;
+        scene::check_non_negative(&field("stiffness"), self.stiffness)?;
+        scene::check_non_negative(&field("damping"), self.damping)
+    }
+
+    pub fn body_indices_mut(&mut self) -> Vec<&mut usize> {
+        std::iter::once(&mut self.body)
+            .chain(self.other.iter_mut())
+            .collect()
+    }
+
+    // World-frame attachment points on both ends
+    pub fn endpoints(
+        &self,
+        bodies: &[body::Body],
+    ) -> Option<(nalgebra::Vector3<f32>, nalgebra::Vector3<f32>)> {
+        let attach = |body: &body::Body, anchor: &[f32; 3]| {
+            body.rigid_body.pos + body.rigid_body.rot_mat * nalgebra::Vector3::from(*anchor)
+        };
+        let a = attach(bodies.get(self.body)?, &self.anchor);
+        let b = match self.other {
+            Some(other) => attach(bodies.get(other)?, &self.other_anchor),
+            None => nalgebra::Vector3::from(self.other_anchor),
+        };
+        Some((a, b))
+    }
+
+    // Force on `body` at its anchor, the other end gets the opposite
+    fn force(&self, bodies: &[body::Body]) -> nalgebra::Vector3<f32> {
+        let (a, b) = match self.endpoints(bodies) {
+            Some(endpoints) => endpoints,
+            None => return nalgebra::Vector3::zeros(),
+        };
+        let d = a - b;
+        let length = d.norm();
+        if length <= f32::EPSILON {
+            return nalgebra::Vector3::zeros();
+        }
+        let n = d / length;
+
+        let point_velocity = |body: &body::Body, p: &nalgebra::Vector3<f32>| {
+            body.rigid_body.lin_vel
+                + dynamics::angular_velocity(&body.rigid_body).cross(&(p - body.rigid_body.pos))
+        };
+        let mut velocity = point_velocity(&bodies[self.body], &a);
+        if let Some(other) = self.other {
+            velocity -= point_velocity(&bodies[other], &b);
+        }
+
+        -n * (self.stiffness * (length - self.rest_length) + self.damping * velocity.dot(&n))
+    }
+}
+
+// Kicks the momenta of the connected bodies by the spring impulses over `dt`.
+// All forces are evaluated before any body is touched.
+pub fn apply(springs: &[Spring], bodies: &mut [body::Body], dt: f32) {
+    let forces: Vec<nalgebra::Vector3<f32>> =
+        springs.iter().map(|spring| spring.force(bodies)).collect();
+    for (spring, force) in springs.iter().zip(forces) {
+        let (a, b) = match spring.endpoints(bodies) {
+            Some(endpoints) => endpoints,
+            None => continue,
+        };
+        let mut kick = |index: usize, point: &nalgebra::Vector3<f32>, force| {
+            let body = &mut bodies[index];
+            let rigid_body = &mut body.rigid_body;
+            rigid_body.lin_vel += force * (dt / body.mass);
+            rigid_body.ang_mom += (point - rigid_body.pos).cross(&force) * dt;
+        };
+        kick(spring.body, &a, force);
+        if let Some(other) = spring.other {
+            kick(other, &b, -force);
+        }
+    }
+}
+
+// Helix between the endpoints, blending from the overlay color at rest
+// towards red when stretched and blue when compressed
+pub fn springs_to_vertices(
+    vertices: &mut Vec<f32>,
+    springs: &[Spring],
+    bodies: &[body::Body],
+    overlay: &overlays::VectorOverlay,
+) {
+    if !overlay.enabled {
+        return;
+    }
+
+    for spring in springs {
+        let (a, b) = match spring.endpoints(bodies) {
+            Some(endpoints) => endpoints,
+            None => continue,
+        };
+        let d = b - a;
+        let length = d.norm();
+        if length <= f32::EPSILON {
+            continue;
+        }
+
+        let strain = (length - spring.rest_length) / spring.rest_length.max(f32::EPSILON);
+        let t = (strain / FULL_TENSION_STRAIN).clamp(-1.0, 1.0);
+        let target = if t > 0.0 {
+            STRETCHED_COLOR
+        } else {
+            COMPRESSED_COLOR
+        };
+        let t = t.abs();
+        let color = (
+            overlay.color.0 + (target.0 - overlay.color.0) * t,
+            overlay.color.1 + (target.1 - overlay.color.1) * t,
+            overlay.color.2 + (target.2 - overlay.color.2) * t,
+        );
+
+        let (u, v) = geometry::perpendicular_basis(&(d / length));
+        let segments = COILS * SEGMENTS_PER_COIL;
+        let point = |i: usize| {
+            // Straight at both ends so the coil meets the anchors
+            if i == 0 {
+                return a;
+            }
+            if i == segments {
+                return b;
+            }
+            let angle = i as f32 / SEGMENTS_PER_COIL as f32 * std::f32::consts::TAU;
+            a + d * (i as f32 / segments as f32)
+                + (u * angle.cos() + v * angle.sin()) * overlay.scale
+        };
+        for i in 0..segments {
+            geometry::segment_to_vertices(vertices, &point(i), &point(i + 1), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(springs: &str) -> crate::simulation::Simulation {
+        let text = format!(
+            r#"{{
+                "version": 1,
+                "bodies": [
+                    {{
+                        "name": "light",
+                        "shape": {{ "type": "sphere", "radius": 0.1 }},
+                        "mass": 2.0,
+                        "pos": [1.5, 0.0, 0.0]
+                    }},
+                    {{
+                        "name": "heavy",
+                        "shape": {{ "type": "cuboid", "half_extents": [0.1, 0.2, 0.3] }},
+                        "mass": 5.0,
+                        "pos": [4.5, 0.0, 0.0],
+                        "orientation": [0.8660254, 0.5, 0.0, 0.0]
+                    }}
+                ],
+                "springs": {},
+                "integrator": {{ "timestep": 0.001, "substeps": 1 }}
+            }}"#,
+            springs
+        );
+        scene::Scene::parse(&text)
+            .unwrap()
+            .build_simulation()
+            .unwrap()
+    }
+
+    fn spring(rest_length: f32, damping: f32) -> Spring {
+        Spring {
+            body: 0,
+            other: Some(1),
+            anchor: [0.0; 3],
+            other_anchor: [0.0; 3],
+            rest_length,
+            stiffness: 10.0,
+            damping,
+        }
+    }
+
+    #[test]
+    fn pulls_stretched_ends_together() {
+        let simulation = simulation("[]");
+        let bodies = &simulation.bodies;
+        // 3 apart
+        assert!(
+            (spring(1.0, 0.0).force(bodies) - nalgebra::Vector3::new(20.0, 0.0, 0.0)).norm() < 1e-4
+        );
+        assert!(
+            (spring(4.0, 0.0).force(bodies) - nalgebra::Vector3::new(-10.0, 0.0, 0.0)).norm()
+                < 1e-4
+        );
+        assert_eq!(spring(3.0, 0.0).force(bodies), nalgebra::Vector3::zeros());
+    }
+
+    #[test]
+    fn damping_opposes_relative_velocity() {
+        let mut simulation = simulation("[]");
+        let spring = spring(3.0, 0.5);
+        simulation.bodies[0].rigid_body.lin_vel = nalgebra::Vector3::new(1.0, 0.0, 0.0);
+        simulation.bodies[1].rigid_body.lin_vel = nalgebra::Vector3::new(-1.0, 0.0, 0.0);
+        let force = spring.force(&simulation.bodies);
+        assert!((force - nalgebra::Vector3::new(-1.0, 0.0, 0.0)).norm() < 1e-5);
+
+        // Only the rate at which the length changes is damped
+        simulation.bodies[0].rigid_body.lin_vel = nalgebra::Vector3::new(-1.0, 2.0, 0.0);
+        let force = spring.force(&simulation.bodies);
+        assert!((force - nalgebra::Vector3::new(0.0, 0.0, 0.0)).norm() < 1e-5);
+        simulation.bodies[1].rigid_body.lin_vel = nalgebra::Vector3::zeros();
+        let force = spring.force(&simulation.bodies);
+        assert!((force - nalgebra::Vector3::new(0.5, 0.0, 0.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn conserves_linear_momentum_between_bodies() {
+        let mut simulation = simulation("[]");
+        let springs = [Spring {
+            anchor: [0.0, 0.1, 0.0],
+            other_anchor: [0.1, 0.0, 0.2],
+            ..spring(1.0, 0.3)
+        }];
+        simulation.bodies[0].rigid_body.lin_vel = nalgebra::Vector3::new(0.0, 1.0, 0.5);
+        let momentum = |bodies: &[body::Body]| {
+            bodies
+                .iter()
+                .map(|body| body.rigid_body.lin_vel * body.mass)
+                .sum::<nalgebra::Vector3<f32>>()
+        };
+        let initial = momentum(&simulation.bodies);
+
+        // The anchors are off center, so the spring also exerts torques
+        let force = springs[0].force(&simulation.bodies);
+        let (a, _) = springs[0].endpoints(&simulation.bodies).unwrap();
+        let lever = a - simulation.bodies[0].rigid_body.pos;
+        apply(&springs, &mut simulation.bodies, 0.01);
+        assert!(
+            (simulation.bodies[0].rigid_body.ang_mom - lever.cross(&force) * 0.01).norm() < 1e-6
+        );
+        assert!(simulation.bodies[1].rigid_body.ang_mom.norm() > 0.0);
+
+        for _ in 0..1000 {
+            apply(&springs, &mut simulation.bodies, 0.001);
+            for body in simulation.bodies.iter_mut() {
+                let rigid_body = &mut body.rigid_body;
+                rigid_body.pos += rigid_body.lin_vel * 0.001;
+            }
+        }
+        assert!((momentum(&simulation.bodies) - initial).norm() < 1e-4);
+    }
+
+    #[test]
+    fn oscillates_at_the_natural_frequency() {
+        // Mass 2 and stiffness 8 on a world anchor, at rest at x = 1 and
+        // released at 1.5
+        let mut simulation = simulation(
+            r#"[{
+                "body": 0,
+                "other_anchor": [0.0, 0.0, 0.0],
+                "rest_length": 1.0,
+                "stiffness": 8.0
+            }]"#,
+        );
+        let mut crossings = Vec::new();
+        let mut last = 0.5;
+        while simulation.time() < 5.0 {
+            simulation.step();
+            let displacement = simulation.bodies[0].rigid_body.pos.x - 1.0;
+            if (displacement > 0.0) != (last > 0.0) {
+                crossings.push(simulation.time());
+            }
+            assert!(displacement.abs() < 0.51);
+            last = displacement;
+        }
+
+        // Every half period of π / √(8 / 2)
+        let half_period = std::f64::consts::PI / 2.0;
+        assert!(crossings.len() >= 3);
+        for pair in crossings.windows(2) {
+            assert!(((pair[1] - pair[0]) - half_period).abs() < 0.01 * half_period);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+#[derive(Clone)]
+pub struct RingBuffer<T> {
+    items: Vec<T>,
+    start: usize,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            start: 0,
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else if self.capacity > 0 {
+            self.items[self.start] = item;
+            self.start = (self.start + 1) % self.capacity;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.start = 0;
+    }
+
+    // Oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items[self.start..]
+            .iter()
+            .chain(self.items[..self.start].iter())
+    }
+}
+
+pub fn polyline_to_vertices(
+    vertices: &mut Vec<f32>,
+    points: impl Iterator<Item = nalgebra::Vector3<f32>>,
+    color: (f32, f32, f32),
+) {
+    for p in points {
+        vertices.push(p.x);
+        vertices.push(p.y);
+        vertices.push(p.z);
+        vertices.push(color.0);
+        vertices.push(color.1);
+        vertices.push(color.2);
+    }
+}
+
+// Older points fade towards the (black) background
+pub fn fading_polyline_to_vertices(
+    vertices: &mut Vec<f32>,
+    points: &RingBuffer<nalgebra::Vector3<f32>>,
+    color: (f32, f32, f32),
+) {
+    let count = points.len();
+    for (i, p) in points.iter().enumerate() {
+        let fade = (i + 1) as f32 / count as f32;
+        vertices.push(p.x);
+        vertices.push(p.y);
+        vertices.push(p.z);
+        vertices.push(color.0 * fade);
+        vertices.push(color.1 * fade);
+        vertices.push(color.2 * fade);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::trace;
+
+// Most positions a trail keeps, the buffer is allocated up front
+pub const MAX_LENGTH: usize = 100_000;
+
+#[derive(Clone)]
+pub struct Trail {
+    pub body: usize,
+    // Body-fixed point, zero for the center of mass
+    pub point: nalgebra::Vector3<f32>,
+    // Number of physics steps between samples
+    pub interval: u32,
+    pub color: (f32, f32, f32),
+    positions: trace::RingBuffer<nalgebra::Vector3<f32>>,
+}
+
+impl Trail {
+    pub fn new(
+        body: usize,
+        point: nalgebra::Vector3<f32>,
+        length: usize,
+        interval: u32,
+        color: (f32, f32, f32),
+    ) -> Self {
+        Self {
+            body,
+            point,
+            interval: interval.max(1),
+            color,
+            positions: trace::RingBuffer::new(length),
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.positions.capacity()
+    }
+
+    pub fn record(&mut self, step: u64, rigid_body: &physsim::RigidBody<f32>) {
+        if step.is_multiple_of(self.interval as u64) {
+            self.positions
+                .push(rigid_body.pos + rigid_body.rot_mat * self.point);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+}
+
+// Appends each trail as a separate line strip and returns the
+// (first vertex, vertex count) range of each one
+pub fn trails_to_vertices(vertices: &mut Vec<f32>, trails: &[Trail]) -> Vec<(i32, i32)> {
+    trails
+        .iter()
+        .map(|trail| {
+            let first = (vertices.len() / 6) as i32;
+            trace::fading_polyline_to_vertices(vertices, &trail.positions, trail.color);
+            (first, trail.positions.len() as i32)
+        })
+        .collect()
+}
@@ -0,0 +1,2126 @@
+use crate::analytic;
+use crate::body;
+use crate::camera;
+use crate::contact;
+use crate::drag;
+use crate::dynamics;
+use crate::forces;
+use crate::geometry;
+use crate::gizmo;
+use crate::history;
+use crate::inspector;
+use crate::joints;
+use crate::logging;
+use crate::overlays;
+use crate::picking;
+use crate::recorder;
+use crate::replay;
+use crate::scene;
+use crate::share;
+use crate::simulation;
+use crate::snapshot;
+use crate::springs;
+use crate::trace;
+use crate::trails;
+
+use wasm_bindgen::prelude::*;
+use web_sys;
+
+use physsim;
+
+const DRAW_INTERVAL: f32 = 100.0;
+const PHYSICS_INTERVAL: f32 = 10.0;
+const ASPECT: f32 = 1.333;
+const SELECTION_COLOR: (f32, f32, f32) = (1.0, 0.8, 0.2);
+// Ticks of state hashes kept around for comparing runs
+const STATE_HASH_HISTORY: usize = 4096;
+
+struct KeysPressed {
+    w: bool,
+    s: bool,
+    a: bool,
+    d: bool,
+    q: bool,
+    e: bool,
+    i: bool,
+    k: bool,
+    j: bool,
+    l: bool,
+    u: bool,
+    o: bool,
+}
+
+impl KeysPressed {
+    fn new() -> Self {
+        Self {
+            w: false,
+            s: false,
+            a: false,
+            d: false,
+            q: false,
+            e: false,
+            i: false,
+            k: false,
+            j: false,
+            l: false,
+            u: false,
+            o: false,
+        }
+    }
+}
+
+struct RunnerState {
+    simulation: simulation::Simulation,
+    wireframe: bool,
+    keys_pressed: KeysPressed,
+    camera_pos: nalgebra::Vector3<f32>,
+    camera_rot: nalgebra::Rotation3<f32>,
+    references: Vec<analytic::TorqueFreeSolution>,
+    overlays: overlays::Overlays,
+    ang_vel_traces: Vec<overlays::AngVelTraces>,
+    contact_history: contact::ContactHistory,
+    trails: Vec<trails::Trail>,
+    recorder: recorder::Recorder,
+    playback: Option<replay::Playback>,
+    clock: simulation::FixedStepClock,
+    last_physics_time: Option<f64>,
+    state_hashes: trace::RingBuffer<(u64, u64)>,
+    // Reference for the drift in the conserved quantities readout
+    initial_totals: dynamics::Totals,
+    // Readout totals with the tick and time they were taken at, so that they
+    // are only worked out again once the bodies moved. The mutual gravity's
+    // potential is quadratic in the number of bodies.
+    readout_totals: Option<(u64, f64, dynamics::Totals)>,
+    // Body the per-body tools act on, all bodies' overlays are shown if none
+    selected: Option<usize>,
+    drag: Option<drag::Drag>,
+    drag_mode: drag::DragMode,
+    // Live simulation halted for editing, independent of replay
+    paused: bool,
+    manipulation: Option<gizmo::Manipulation>,
+    snapping: gizmo::Snapping,
+    history: history::History,
+}
+
+impl RunnerState {
+    fn new() -> Self {
+        Self::from_scene(&scene::Scene::default()).unwrap()
+    }
+
+    fn from_scene(scene: &scene::Scene) -> Result<Self, scene::SceneError> {
+        let simulation = scene.build_simulation()?;
+        let references = simulation
+            .bodies
+            .iter()
+            .map(|body| analytic::TorqueFreeSolution::new(&body.rigid_body))
+            .collect();
+        let ang_vel_traces = simulation
+            .bodies
+            .iter()
+            .map(|_| overlays::AngVelTraces::new())
+            .collect();
+
+        Ok(Self {
+            wireframe: scene.wireframe,
+            keys_pressed: KeysPressed::new(),
+            camera_pos: scene.camera_pos(),
+            camera_rot: scene.camera_rot(),
+            references,
+            overlays: scene.overlays.clone(),
+            ang_vel_traces,
+            contact_history: contact::ContactHistory::new(),
+            trails: scene.build_trails(),
+            recorder: recorder::Recorder::new(),
+            playback: None,
+            clock: simulation::FixedStepClock::new(),
+            last_physics_time: None,
+            state_hashes: trace::RingBuffer::new(STATE_HASH_HISTORY),
+            initial_totals: simulation.totals(),
+            readout_totals: None,
+            selected: None,
+            drag: None,
+            drag_mode: drag::DragMode::default(),
+            paused: false,
+            manipulation: None,
+            snapping: gizmo::Snapping::default(),
+            history: history::History::new(),
+            simulation,
+        })
+    }
+
+    // Bodies as they should be drawn, with replayed poses during playback
+    fn displayed_bodies(&self) -> std::borrow::Cow<'_, [body::Body]> {
+        let playback = match &self.playback {
+            Some(playback) => playback,
+            None => return std::borrow::Cow::Borrowed(&self.simulation.bodies),
+        };
+
+        let bodies = playback
+            .poses()
+            .iter()
+            .enumerate()
+            .map(|(i, pose)| {
+                // Trajectories don't describe shapes, imported ones may even
+                // have more bodies than the scene
+                let mut body =
+                    self.simulation
+                        .bodies
+                        .get(i)
+                        .cloned()
+                        .unwrap_or_else(|| body::Body {
+                            name: format!("body {}", i),
+                            shape: body::Shape::Cuboid {
+                                half_extents: [0.5, 0.5, 0.5],
+                            },
+                            mass: 1.0,
+                            material: body::Material::default(),
+                            rigid_body: physsim::RigidBody {
+                                pos: nalgebra::Vector3::zeros(),
+                                lin_vel: nalgebra::Vector3::zeros(),
+                                rot_mat: nalgebra::Matrix3::identity(),
+                                ang_mom: nalgebra::Vector3::zeros(),
+                                inv_ine: nalgebra::Matrix3::identity(),
+                            },
+                        });
+                body.rigid_body.pos = pose.pos;
+                body.rigid_body.lin_vel = pose.lin_vel;
+                body.rigid_body.rot_mat = pose.orientation.to_rotation_matrix().into_inner();
+                body.rigid_body.ang_mom = pose.ang_mom;
+                body
+            })
+            .collect();
+        std::borrow::Cow::Owned(bodies)
+    }
+
+    fn is_shown(&self, index: usize) -> bool {
+        self.selected.is_none_or(|selected| selected == index)
+    }
+
+    fn cursor_ray(
+        &self,
+        ndc: &nalgebra::Vector2<f32>,
+    ) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+        camera::ray(&self.camera_pos, &self.camera_rot, ASPECT, ndc)
+    }
+
+    // Gizmos are only offered on the live simulation while it is paused
+    fn editable_selection(&self) -> Option<usize> {
+        if self.paused && self.playback.is_none() {
+            self.selected
+                .filter(|&selected| selected < self.simulation.bodies.len())
+        } else {
+            None
+        }
+    }
+
+    // Selects the body under the viewport point `ndc`, or clears the selection
+    // if there is none. The hit comes with the body's name, which during
+    // playback may be one the scene doesn't have.
+    fn pick(&mut self, ndc: &nalgebra::Vector2<f32>) -> Option<(picking::Hit, String)> {
+        let (origin, direction) = self.cursor_ray(ndc);
+        let bodies = self.displayed_bodies();
+        let hit = picking::ray_cast(&bodies, &origin, &direction)
+            .map(|hit| (hit, bodies[hit.body].name.clone()));
+        drop(bodies);
+        self.selected = hit.as_ref().map(|(hit, _)| hit.body);
+        hit
+    }
+
+    // Back to the live simulation, which may have fewer bodies than were
+    // replayed
+    fn stop_playback(&mut self) {
+        self.playback = None;
+        if self.selected >= Some(self.simulation.bodies.len()) {
+            self.selected = None;
+        }
+    }
+
+    fn readout_totals(&mut self) -> dynamics::Totals {
+        let (counter, time) = (self.simulation.counter, self.displayed_time());
+        match self.readout_totals {
+            Some((c, t, totals)) if c == counter && t == time => totals,
+            _ => {
+                let totals = self.simulation.totals_of(&self.displayed_bodies());
+                self.readout_totals = Some((counter, time, totals));
+                totals
+            }
+        }
+    }
+
+    fn displayed_time(&self) -> f64 {
+        match &self.playback {
+            Some(playback) => playback.time,
+            None => self.simulation.time(),
+        }
+    }
+
+    // Continues from a snapshot. References are kept when they still describe
+    // the same bodies, traced history is dropped as it no longer applies.
+    fn restore_simulation(&mut self, simulation: simulation::Simulation) {
+        if simulation.bodies.len() != self.simulation.bodies.len() {
+            self.references = simulation
+                .bodies
+                .iter()
+                .map(|body| analytic::TorqueFreeSolution::new(&body.rigid_body))
+                .collect();
+            self.trails
+                .retain(|trail| trail.body < simulation.bodies.len());
+        }
+        self.ang_vel_traces = simulation
+            .bodies
+            .iter()
+            .map(|_| overlays::AngVelTraces::new())
+            .collect();
+        for trail in self.trails.iter_mut() {
+            trail.clear();
+        }
+        self.contact_history.clear();
+        self.playback = None;
+        self.clock.reset();
+        self.state_hashes.clear();
+        self.initial_totals = simulation.totals();
+        self.readout_totals = None;
+        self.drag = None;
+        self.manipulation = None;
+        self.history.clear();
+        if self.selected >= Some(simulation.bodies.len()) {
+            self.selected = None;
+        }
+        self.simulation = simulation;
+    }
+
+    // The body as it would be after the change, checked like a scene body
+    fn changed_body(
+        &self,
+        index: usize,
+        change: impl FnOnce(&mut scene::BodySpec),
+    ) -> Result<body::Body, wasm_bindgen::JsValue> {
+        let body = self
+            .simulation
+            .bodies
+            .get(index)
+            .ok_or("Body index out of range")?;
+        let path = format!("bodies[{}]", index);
+        let mut spec = scene::BodySpec::capture(&path, body).map_err(|err| err.to_string())?;
+        change(&mut spec);
+        spec.validate(&path).map_err(|err| err.to_string())?;
+        spec.build(&path).map_err(|err| err.to_string().into())
+    }
+
+    // Shows the selected body, as replayed during playback
+    fn update_inspector(&self, inspector: &inspector::Inspector) {
+        let bodies = self.displayed_bodies();
+        let selected = self
+            .selected
+            .and_then(|index| Some((index, bodies.get(index)?)));
+        inspector.update(self.overlays.inspector, selected, self.playback.is_none());
+    }
+
+    // Writes values entered into the inspector to the selected body
+    fn inspector_edit(
+        &mut self,
+        field: inspector::Field,
+        values: &[f32],
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if self.playback.is_some() {
+            return Err("Replayed bodies can't be edited".into());
+        }
+        let index = self.selected.ok_or("No body selected")?;
+        let body = self
+            .simulation
+            .bodies
+            .get(index)
+            .ok_or("Body index out of range")?;
+        let mut edited = scene::BodySpec::capture(&format!("bodies[{}]", index), body)
+            .map_err(|err| err.to_string())?;
+        field.write(values, body, &mut edited)?;
+        let changed = self.changed_body(index, |spec| *spec = edited)?;
+
+        let edit = match field {
+            inspector::Field::Pos | inspector::Field::Euler | inspector::Field::Quaternion => {
+                history::Edit::set_pose(&self.simulation, index, history::Pose::of(&changed))
+            }
+            inspector::Field::LinVel | inspector::Field::AngMom | inspector::Field::AngVel => {
+                history::Edit::set_velocity(
+                    &self.simulation,
+                    index,
+                    history::Velocity::of(&changed),
+                )
+            }
+            inspector::Field::InvIne => history::Edit::set_mass_properties(
+                &self.simulation,
+                index,
+                history::MassProperties::of(&changed),
+            ),
+        };
+        self.execute(edit);
+        Ok(())
+    }
+
+    fn execute(&mut self, edit: history::Edit) {
+        let edit = self.history.execute(edit, &mut self.simulation);
+        edit.apply_to_trails(&mut self.trails);
+        let change = edit.body_change(&self.simulation, false);
+        self.edited(change);
+    }
+
+    // For edits that have already been applied, such as gizmo drags
+    fn record(&mut self, edit: history::Edit) {
+        edit.apply_to_trails(&mut self.trails);
+        let change = edit.body_change(&self.simulation, false);
+        self.history.record(edit);
+        self.edited(change);
+    }
+
+    // Whether there was anything to undo
+    fn undo(&mut self) -> bool {
+        let change = match self.history.undo(&mut self.simulation) {
+            Some(edit) => {
+                edit.revert_trails(&self.simulation, &mut self.trails);
+                edit.body_change(&self.simulation, true)
+            }
+            None => return false,
+        };
+        self.edited(change);
+        true
+    }
+
+    fn redo(&mut self) -> bool {
+        let change = match self.history.redo(&mut self.simulation) {
+            Some(edit) => {
+                edit.apply_to_trails(&mut self.trails);
+                edit.body_change(&self.simulation, false)
+            }
+            None => return false,
+        };
+        self.edited(change);
+        true
+    }
+
+    // Keeps the per-body state in line with the edited bodies, trails aside.
+    // Recording stops as the samples so far no longer match the bodies.
+    fn edited(&mut self, change: Option<history::BodyChange>) {
+        self.drag = None;
+        self.manipulation = None;
+        self.initial_totals = self.simulation.totals();
+        self.readout_totals = None;
+        if let Some(history::BodyChange::Inserted(_) | history::BodyChange::Removed(_)) = change {
+            self.recorder.stop();
+        }
+        match change {
+            Some(history::BodyChange::Changed(index)) => {
+                self.references[index] =
+                    analytic::TorqueFreeSolution::new(&self.simulation.bodies[index].rigid_body);
+            }
+            Some(history::BodyChange::Inserted(index)) => {
+                let body = &self.simulation.bodies[index];
+                self.references
+                    .insert(index, analytic::TorqueFreeSolution::new(&body.rigid_body));
+                self.ang_vel_traces
+                    .insert(index, overlays::AngVelTraces::new());
+                if self.selected >= Some(index) {
+                    self.selected = self.selected.map(|selected| selected + 1);
+                }
+                self.contact_history.clear();
+            }
+            Some(history::BodyChange::Removed(index)) => {
+                self.references.remove(index);
+                self.ang_vel_traces.remove(index);
+                self.selected = match self.selected {
+                    Some(selected) if selected == index => None,
+                    Some(selected) if selected > index => Some(selected - 1),
+                    selected => selected,
+                };
+                self.contact_history.clear();
+            }
+            None => {}
+        }
+    }
+
+    fn capture_scene(&self) -> Result<scene::Scene, wasm_bindgen::JsValue> {
+        scene::Scene::capture(
+            &self.simulation,
+            &self.camera_pos,
+            &self.camera_rot,
+            self.wireframe,
+            &self.overlays,
+            &self.trails,
+        )
+        .map_err(|err| err.to_string().into())
+    }
+}
+
+// Sets which log records reach the console, e.g.
+// `setLogFilter("warn,physsim_viz_rust::web=debug")`. Only warnings and errors
+// do by default.
+#[wasm_bindgen(js_name = setLogFilter)]
+pub fn set_log_filter(spec: &str) -> Result<(), wasm_bindgen::JsValue> {
+    logging::set_filter(logging::Filter::parse(spec)?);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub struct Runner {
+    draw_interval_closure: wasm_bindgen::closure::Closure<dyn FnMut()>,
+    draw_interval_token: i32,
+    physics_interval_closure: wasm_bindgen::closure::Closure<dyn FnMut()>,
+    physics_interval_token: i32,
+    keydown_closure: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::KeyboardEvent)>,
+    keyup_closure: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::KeyboardEvent)>,
+    mousedown_closure: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MouseEvent)>,
+    mousemove_closure: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MouseEvent)>,
+    mouseup_closure: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MouseEvent)>,
+    inspector_change_closure: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::Event)>,
+    // Targets of the mouse and key listeners, which are removed on drop
+    canvas: web_sys::HtmlCanvasElement,
+    document: web_sys::Document,
+    inspector: std::rc::Rc<inspector::Inspector>,
+    state: std::sync::Arc<std::sync::RwLock<RunnerState>>,
+}
+
+#[wasm_bindgen]
+impl Runner {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Result<Self, wasm_bindgen::JsValue> {
+        logging::init();
+
+        let window = web_sys::window().unwrap();
+        let document = window.document().unwrap();
+
+        let canvas = document
+            .get_element_by_id("physsim-viz-canvas")
+            .expect("Canvas not found")
+            .dyn_into::<web_sys::HtmlCanvasElement>()
+            .expect("Canvas isn't canvas");
+
+        let ctx = canvas
+            .get_context("webgl2")
+            .expect("Couldn't get WebGL2 context")
+            .unwrap()
+            .dyn_into::<web_sys::WebGl2RenderingContext>()
+            .unwrap();
+
+        let vbo = ctx.create_buffer().ok_or("Couldn't create VBO")?;
+
+        let vert_shader_plain = compile_shader(
+            &ctx,
+            web_sys::WebGl2RenderingContext::VERTEX_SHADER,
+            r##"#version 300 es
+
+            in vec3 position;
+            uniform mat4 projection;
+
+            void main() {
+                gl_Position = projection * vec4(position, 1.0);
+            }
+            "##,
+        )?;
+        let frag_shader_plain = compile_shader(
+            &ctx,
+            web_sys::WebGl2RenderingContext::FRAGMENT_SHADER,
+            r##"#version 300 es
+
+            precision highp float;
+            uniform vec4 color;
+            out vec4 outColor;
+
+            void main() {
+                outColor = color;
+            }
+            "##,
+        )?;
+
+        let program_plain = link_program(&ctx, &vert_shader_plain, &frag_shader_plain)?;
+        ctx.use_program(Some(&program_plain));
+
+        let vao_plain = ctx.create_vertex_array().ok_or("Couldn't create VAO")?;
+        ctx.bind_vertex_array(Some(&vao_plain));
+        ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&vbo));
+
+        let plain_pos_attrib_idx = ctx.get_attrib_location(&program_plain, "position");
+        ctx.enable_vertex_attrib_array(plain_pos_attrib_idx as u32);
+        ctx.vertex_attrib_pointer_with_i32(
+            plain_pos_attrib_idx as u32,
+            3,
+            web_sys::WebGl2RenderingContext::FLOAT,
+            false,
+            3 * 4,
+            0 * 4,
+        );
+
+        let vert_shader_colored = compile_shader(
+            &ctx,
+            web_sys::WebGl2RenderingContext::VERTEX_SHADER,
+            r##"#version 300 es
+
+            in vec3 position;
+            in vec3 color;
+            uniform mat4 projection;
+            out vec3 fColor;
+
+            void main() {
+                gl_Position = projection * vec4(position, 1.0);
+                fColor = color;
+            }
+            "##,
+        )?;
+        let frag_shader_colored = compile_shader(
+            &ctx,
+            web_sys::WebGl2RenderingContext::FRAGMENT_SHADER,
+            r##"#version 300 es
+
+            precision highp float;
+            in vec3 fColor;
+            out vec4 outColor;
+
+            void main() {
+                outColor = vec4(fColor, 1);
+            }
+            "##,
+        )?;
+
+        let program_colored = link_program(&ctx, &vert_shader_colored, &frag_shader_colored)?;
+        ctx.use_program(Some(&program_colored));
+
+        let vao_colored = ctx.create_vertex_array().ok_or("Couldn't create VAO")?;
+        ctx.bind_vertex_array(Some(&vao_colored));
+        ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&vbo));
+
+        let colored_pos_attrib_idx = ctx.get_attrib_location(&program_colored, "position");
+        ctx.enable_vertex_attrib_array(colored_pos_attrib_idx as u32);
+        ctx.vertex_attrib_pointer_with_i32(
+            colored_pos_attrib_idx as u32,
+            3,
+            web_sys::WebGl2RenderingContext::FLOAT,
+            false,
+            6 * 4,
+            0 * 4,
+        );
+        let colored_color_attrib_idx = ctx.get_attrib_location(&program_colored, "color");
+        ctx.enable_vertex_attrib_array(colored_color_attrib_idx as u32);
+        ctx.vertex_attrib_pointer_with_i32(
+            colored_color_attrib_idx as u32,
+            3,
+            web_sys::WebGl2RenderingContext::FLOAT,
+            false,
+            6 * 4,
+            3 * 4,
+        );
+
+        log::info!("Initialized WebGL2");
+
+        // Scene shared through the URL fragment
+        let runner_state = match window
+            .location()
+            .hash()
+            .ok()
+            .and_then(|hash| share::decode(&hash))
+            .map(|scene| scene.and_then(|scene| RunnerState::from_scene(&scene)))
+        {
+            Some(Ok(runner_state)) => runner_state,
+            Some(Err(err)) => {
+                log::error!("Couldn't load shared scene: {}", err);
+                RunnerState::new()
+            }
+            None => RunnerState::new(),
+        };
+        let runner_state = std::sync::Arc::new(std::sync::RwLock::new(runner_state));
+        let inspector = std::rc::Rc::new(inspector::Inspector::new(&document, &canvas)?);
+
+        let draw_interval_closure = {
+            let runner_state = runner_state.clone();
+            let inspector = inspector.clone();
+            Closure::new(move || {
+                draw(
+                    &ctx,
+                    &vbo,
+                    &vao_plain,
+                    &program_plain,
+                    &vao_colored,
+                    &program_colored,
+                    runner_state.clone(),
+                );
+                runner_state.read().unwrap().update_inspector(&inspector);
+            })
+        };
+        let draw_interval_token = window.set_interval_with_callback_and_timeout_and_arguments_0(
+            draw_interval_closure.as_ref().unchecked_ref(),
+            DRAW_INTERVAL as i32,
+        )?;
+
+        let physics_interval_closure = {
+            let runner_state = runner_state.clone();
+            Closure::new(move || {
+                physics_step(runner_state.clone());
+            })
+        };
+        let physics_interval_token = window
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                physics_interval_closure.as_ref().unchecked_ref(),
+                PHYSICS_INTERVAL as i32,
+            )?;
+
+        // Keypresses
+        let keydown_closure = {
+            let runner_state = runner_state.clone();
+            wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::KeyboardEvent)>::new(
+                move |ev: web_sys::KeyboardEvent| {
+                    // Typing into the inspector's fields isn't meant for the viewer
+                    if ev
+                        .target()
+                        .is_some_and(|target| target.has_type::<web_sys::HtmlInputElement>())
+                    {
+                        return;
+                    }
+
+                    let mut state_locked = runner_state.write().unwrap();
+                    let command = ev.ctrl_key() || ev.meta_key();
+                    match ev.code().as_str() {
+                        "KeyZ" if command && ev.shift_key() => {
+                            ev.prevent_default();
+                            state_locked.redo();
+                        }
+                        "KeyZ" if command => {
+                            ev.prevent_default();
+                            state_locked.undo();
+                        }
+                        "KeyY" if command => {
+                            ev.prevent_default();
+                            state_locked.redo();
+                        }
+                        "KeyV" => state_locked.wireframe = !state_locked.wireframe,
+                        "KeyR" => {
+                            state_locked.overlays.reference = !state_locked.overlays.reference
+                        }
+                        "Digit1" => {
+                            state_locked.overlays.ang_vel.enabled =
+                                !state_locked.overlays.ang_vel.enabled
+                        }
+                        "Digit2" => {
+                            state_locked.overlays.body_axes.enabled =
+                                !state_locked.overlays.body_axes.enabled
+                        }
+                        "Digit3" => {
+                            state_locked.overlays.principal_axes.enabled =
+                                !state_locked.overlays.principal_axes.enabled
+                        }
+                        "KeyC" => {
+                            for traces in state_locked.ang_vel_traces.iter_mut() {
+                                traces.clear();
+                            }
+                            for trail in state_locked.trails.iter_mut() {
+                                trail.clear();
+                            }
+                            state_locked.contact_history.clear();
+                        }
+                        "KeyX" => {
+                            state_locked.overlays.contacts.enabled =
+                                !state_locked.overlays.contacts.enabled
+                        }
+                        "KeyB" => {
+                            state_locked.overlays.joints.enabled =
+                                !state_locked.overlays.joints.enabled
+                        }
+                        "KeyH" => {
+                            state_locked.overlays.springs.enabled =
+                                !state_locked.overlays.springs.enabled
+                        }
+                        "KeyM" => {
+                            state_locked.overlays.conserved = !state_locked.overlays.conserved
+                        }
+                        "KeyN" => {
+                            state_locked.overlays.inspector = !state_locked.overlays.inspector
+                        }
+                        "KeyT" => state_locked.overlays.trails = !state_locked.overlays.trails,
+                        "Space" => match state_locked.playback.as_mut() {
+                            Some(playback) => playback.playing = !playback.playing,
+                            None => state_locked.paused = !state_locked.paused,
+                        },
+                        "Digit4" => {
+                            state_locked.overlays.polhode.enabled =
+                                !state_locked.overlays.polhode.enabled
+                        }
+                        "Digit5" => {
+                            state_locked.overlays.herpolhode.enabled =
+                                !state_locked.overlays.herpolhode.enabled
+                        }
+                        "Digit6" => {
+                            state_locked.overlays.inertia_ellipsoid.enabled =
+                                !state_locked.overlays.inertia_ellipsoid.enabled
+                        }
+                        "Digit7" => {
+                            state_locked.overlays.invariable_plane.enabled =
+                                !state_locked.overlays.invariable_plane.enabled
+                        }
+                        "Digit8" => {
+                            state_locked.overlays.momentum_sphere.enabled =
+                                !state_locked.overlays.momentum_sphere.enabled
+                        }
+                        "Digit9" => {
+                            state_locked.overlays.forces.enabled =
+                                !state_locked.overlays.forces.enabled
+                        }
+                        "Digit0" => {
+                            state_locked.overlays.torques.enabled =
+                                !state_locked.overlays.torques.enabled
+                        }
+                        "KeyW" => state_locked.keys_pressed.w = true,
+                        "KeyS" => state_locked.keys_pressed.s = true,
+                        "KeyA" => state_locked.keys_pressed.a = true,
+                        "KeyD" => state_locked.keys_pressed.d = true,
+                        "KeyQ" => state_locked.keys_pressed.q = true,
+                        "KeyE" => state_locked.keys_pressed.e = true,
+                        "KeyI" => state_locked.keys_pressed.i = true,
+                        "KeyK" => state_locked.keys_pressed.k = true,
+                        "KeyJ" => state_locked.keys_pressed.j = true,
+                        "KeyL" => state_locked.keys_pressed.l = true,
+                        "KeyU" => state_locked.keys_pressed.u = true,
+                        "KeyO" => state_locked.keys_pressed.o = true,
+                        _ => {}
+                    }
+                },
+            )
+        };
+        document
+            .add_event_listener_with_callback(&"keydown", keydown_closure.as_ref().unchecked_ref())
+            .unwrap();
+        let keyup_closure = {
+            let runner_state = runner_state.clone();
+            wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::KeyboardEvent)>::new(
+                move |ev: web_sys::KeyboardEvent| {
+                    let mut state_locked = runner_state.write().unwrap();
+                    match ev.code().as_str() {
+                        "KeyW" => state_locked.keys_pressed.w = false,
+                        "KeyS" => state_locked.keys_pressed.s = false,
+                        "KeyA" => state_locked.keys_pressed.a = false,
+                        "KeyD" => state_locked.keys_pressed.d = false,
+                        "KeyQ" => state_locked.keys_pressed.q = false,
+                        "KeyE" => state_locked.keys_pressed.e = false,
+                        "KeyI" => state_locked.keys_pressed.i = false,
+                        "KeyK" => state_locked.keys_pressed.k = false,
+                        "KeyJ" => state_locked.keys_pressed.j = false,
+                        "KeyL" => state_locked.keys_pressed.l = false,
+                        "KeyU" => state_locked.keys_pressed.u = false,
+                        "KeyO" => state_locked.keys_pressed.o = false,
+                        _ => {}
+                    }
+                },
+            )
+        };
+        document
+            .add_event_listener_with_callback(&"keyup", keyup_closure.as_ref().unchecked_ref())
+            .unwrap();
+
+        // Picking, dragging the picked body
+        let mousedown_closure = {
+            let runner_state = runner_state.clone();
+            let canvas = canvas.clone();
+            wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::MouseEvent)>::new(
+                move |ev: web_sys::MouseEvent| {
+                    let ndc = canvas_to_ndc(&canvas, ev.offset_x() as f32, ev.offset_y() as f32);
+                    let mut state_locked = runner_state.write().unwrap();
+                    let (origin, direction) = state_locked.cursor_ray(&ndc);
+
+                    // Gizmo handles take precedence over the bodies behind them
+                    if let Some(selected) = state_locked.editable_selection() {
+                        let body = &state_locked.simulation.bodies[selected];
+                        let manipulation =
+                            gizmo::hit_handle(body, &origin, &direction).and_then(|handle| {
+                                gizmo::Manipulation::begin(
+                                    selected, body, handle, &origin, &direction,
+                                )
+                            });
+                        if manipulation.is_some() {
+                            state_locked.manipulation = manipulation;
+                            return;
+                        }
+                    }
+
+                    let hit = state_locked.pick(&ndc);
+
+                    // Replayed bodies can't be pushed around, paused ones are
+                    // edited with the gizmos instead
+                    if state_locked.playback.is_some() || state_locked.paused {
+                        return;
+                    }
+                    state_locked.drag = hit.map(|(hit, _)| {
+                        drag::Drag::new(
+                            hit.body,
+                            &state_locked.simulation.bodies[hit.body],
+                            &hit.point,
+                            &direction,
+                            state_locked.drag_mode,
+                        )
+                    });
+                },
+            )
+        };
+        canvas
+            .add_event_listener_with_callback(
+                "mousedown",
+                mousedown_closure.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        let mousemove_closure = {
+            let runner_state = runner_state.clone();
+            let canvas = canvas.clone();
+            wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::MouseEvent)>::new(
+                move |ev: web_sys::MouseEvent| {
+                    let state_locked = &mut *runner_state.write().unwrap();
+                    if state_locked.drag.is_none() && state_locked.manipulation.is_none() {
+                        return;
+                    }
+                    let ndc = canvas_to_ndc(&canvas, ev.offset_x() as f32, ev.offset_y() as f32);
+                    let (origin, direction) = state_locked.cursor_ray(&ndc);
+                    if let Some(drag) = state_locked.drag.as_mut() {
+                        drag.move_to(&origin, &direction);
+                    }
+                    if let Some(manipulation) = &state_locked.manipulation {
+                        state_locked.readout_totals = None;
+                        manipulation.update(
+                            &mut state_locked.simulation.bodies[manipulation.body],
+                            &origin,
+                            &direction,
+                            &state_locked.snapping,
+                        );
+                    }
+                },
+            )
+        };
+        canvas
+            .add_event_listener_with_callback(
+                "mousemove",
+                mousemove_closure.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        // On the document, so releasing outside the canvas ends the drag too
+        let mouseup_closure = {
+            let runner_state = runner_state.clone();
+            wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::MouseEvent)>::new(
+                move |_: web_sys::MouseEvent| {
+                    let state_locked = &mut *runner_state.write().unwrap();
+                    if let Some(drag) = state_locked.drag.take() {
+                        drag.release(&mut state_locked.simulation.bodies);
+                    }
+                    if let Some(manipulation) = state_locked.manipulation.take() {
+                        let body = &state_locked.simulation.bodies[manipulation.body];
+                        if let Some(edit) = manipulation.finish(body) {
+                            state_locked.record(edit);
+                        }
+                    }
+                },
+            )
+        };
+        document
+            .add_event_listener_with_callback("mouseup", mouseup_closure.as_ref().unchecked_ref())
+            .unwrap();
+
+        let inspector_change_closure = {
+            let runner_state = runner_state.clone();
+            let inspector = inspector.clone();
+            wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::Event)>::new(
+                move |ev: web_sys::Event| {
+                    let entered = ev.target().and_then(|target| inspector.entered(&target));
+                    let result = match entered {
+                        Some((field, values)) => {
+                            runner_state.write().unwrap().inspector_edit(field, &values)
+                        }
+                        None => Err("Expected numbers".into()),
+                    };
+                    inspector.set_status(
+                        &result
+                            .err()
+                            .and_then(|err| err.as_string())
+                            .unwrap_or_default(),
+                    );
+                    runner_state.read().unwrap().update_inspector(&inspector);
+                },
+            )
+        };
+        inspector
+            .panel()
+            .add_event_listener_with_callback(
+                "change",
+                inspector_change_closure.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+
+        Ok(Runner {
+            draw_interval_closure,
+            draw_interval_token,
+            physics_interval_closure,
+            physics_interval_token,
+            keydown_closure,
+            keyup_closure,
+            mousedown_closure,
+            mousemove_closure,
+            mouseup_closure,
+            inspector_change_closure,
+            canvas,
+            document,
+            inspector,
+            state: runner_state,
+        })
+    }
+
+    // Adds a trail of a body-fixed point ([x, y, z]) with a color ([r, g, b])
+    // and returns its index
+    #[wasm_bindgen(js_name = addTrail)]
+    pub fn add_trail(
+        &self,
+        body: usize,
+        point: &[f32],
+        length: usize,
+        interval: u32,
+        color: &[f32],
+    ) -> Result<usize, wasm_bindgen::JsValue> {
+        let point = vector_from_slice(point)?;
+        let color = color_from_slice(color)?;
+
+        check_trail_length(length)?;
+
+        let mut state_locked = self.state.write().unwrap();
+        if body >= state_locked.simulation.bodies.len() {
+            return Err("Body index out of range".into());
+        }
+        state_locked
+            .trails
+            .push(trails::Trail::new(body, point, length, interval, color));
+        Ok(state_locked.trails.len() - 1)
+    }
+
+    // Changes the trail settings; changing the length discards its history
+    #[wasm_bindgen(js_name = configureTrail)]
+    pub fn configure_trail(
+        &self,
+        index: usize,
+        length: usize,
+        interval: u32,
+        color: &[f32],
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let color = color_from_slice(color)?;
+        check_trail_length(length)?;
+
+        let mut state_locked = self.state.write().unwrap();
+        let trail = state_locked
+            .trails
+            .get_mut(index)
+            .ok_or("Trail index out of range")?;
+        if trail.length() != length {
+            *trail = trails::Trail::new(trail.body, trail.point, length, interval, color);
+        } else {
+            trail.interval = interval.max(1);
+            trail.color = color;
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = removeTrail)]
+    pub fn remove_trail(&self, index: usize) -> Result<(), wasm_bindgen::JsValue> {
+        let mut state_locked = self.state.write().unwrap();
+        if index >= state_locked.trails.len() {
+            return Err("Trail index out of range".into());
+        }
+        state_locked.trails.remove(index);
+        Ok(())
+    }
+
+    // Force generators as a JSON array, in the scene's `forces` format
+    pub fn forces(&self) -> String {
+        serde_json::to_string(&self.state.read().unwrap().simulation.forces).unwrap()
+    }
+
+    // Replaces all force generators with a JSON array of them
+    #[wasm_bindgen(js_name = setForces)]
+    pub fn set_forces(&self, json: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let generators: Vec<forces::ForceGenerator> =
+            serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        let mut state_locked = self.state.write().unwrap();
+        let body_count = state_locked.simulation.bodies.len();
+        for (i, generator) in generators.iter().enumerate() {
+            generator
+                .validate(&format!("forces[{}]", i), body_count)
+                .map_err(|err| err.to_string())?;
+        }
+        let edit = history::Edit::set_forces(&state_locked.simulation, generators);
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Adds a force generator given as JSON and returns its index
+    #[wasm_bindgen(js_name = addForce)]
+    pub fn add_force(&self, json: &str) -> Result<usize, wasm_bindgen::JsValue> {
+        let generator = force_from_json(json)?;
+
+        let mut state_locked = self.state.write().unwrap();
+        generator
+            .validate("force", state_locked.simulation.bodies.len())
+            .map_err(|err| err.to_string())?;
+        let mut generators = state_locked.simulation.forces.clone();
+        generators.push(generator);
+        let edit = history::Edit::set_forces(&state_locked.simulation, generators);
+        state_locked.execute(edit);
+        Ok(state_locked.simulation.forces.len() - 1)
+    }
+
+    // Replaces a force generator with one given as JSON
+    #[wasm_bindgen(js_name = configureForce)]
+    pub fn configure_force(&self, index: usize, json: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let generator = force_from_json(json)?;
+
+        let mut state_locked = self.state.write().unwrap();
+        generator
+            .validate("force", state_locked.simulation.bodies.len())
+            .map_err(|err| err.to_string())?;
+        let mut generators = state_locked.simulation.forces.clone();
+        let slot = generators
+            .get_mut(index)
+            .ok_or("Force index out of range")?;
+        *slot = generator;
+        let edit = history::Edit::set_forces(&state_locked.simulation, generators);
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = removeForce)]
+    pub fn remove_force(&self, index: usize) -> Result<(), wasm_bindgen::JsValue> {
+        let mut state_locked = self.state.write().unwrap();
+        if index >= state_locked.simulation.forces.len() {
+            return Err("Force index out of range".into());
+        }
+        let mut generators = state_locked.simulation.forces.clone();
+        generators.remove(index);
+        let edit = history::Edit::set_forces(&state_locked.simulation, generators);
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Replaces the whole scene; the format (JSON or RON) is detected
+    #[wasm_bindgen(js_name = loadScene)]
+    pub fn load_scene(&self, text: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let scene = scene::Scene::parse(text).map_err(|err| err.to_string())?;
+        *self.state.write().unwrap() =
+            RunnerState::from_scene(&scene).map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    // Describes the current state as a scene, in JSON unless "ron" is given
+    #[wasm_bindgen(js_name = saveScene)]
+    pub fn save_scene(&self, format: Option<String>) -> Result<String, wasm_bindgen::JsValue> {
+        let format = match format {
+            Some(name) => scene::Format::from_name(&name)
+                .ok_or_else(|| format!("Unknown scene format: {}", name))?,
+            None => scene::Format::Json,
+        };
+        Ok(self.state.read().unwrap().capture_scene()?.to_text(format))
+    }
+
+    // Encodes the current scene and camera into `location.hash` and returns
+    // the full link
+    #[wasm_bindgen(js_name = shareScene)]
+    pub fn share_scene(&self) -> Result<String, wasm_bindgen::JsValue> {
+        let fragment = share::encode(&self.state.read().unwrap().capture_scene()?);
+        let location = web_sys::window().ok_or("No window")?.location();
+        location.set_hash(&fragment)?;
+        location.href()
+    }
+
+    // Captures the complete simulation state as an opaque blob
+    pub fn snapshot(&self) -> js_sys::Uint8Array {
+        let blob = snapshot::take(&self.state.read().unwrap().simulation);
+        js_sys::Uint8Array::from(blob.as_slice())
+    }
+
+    // Rolls the simulation back to a blob returned by `snapshot`
+    pub fn restore(&self, blob: &[u8]) -> Result<(), wasm_bindgen::JsValue> {
+        let simulation = snapshot::restore(blob)?;
+        self.state.write().unwrap().restore_simulation(simulation);
+        Ok(())
+    }
+
+    // Hex FNV-1a hash of the exact simulation state at the current tick
+    #[wasm_bindgen(js_name = stateHash)]
+    pub fn state_hash(&self) -> String {
+        format!(
+            "{:016x}",
+            self.state.read().unwrap().simulation.state_hash()
+        )
+    }
+
+    // Hash of a recent tick, for finding where two runs of a scene diverge
+    #[wasm_bindgen(js_name = stateHashAt)]
+    pub fn state_hash_at(&self, tick: u64) -> Option<String> {
+        let state_locked = self.state.read().unwrap();
+        if state_locked.simulation.counter == tick {
+            return Some(format!("{:016x}", state_locked.simulation.state_hash()));
+        }
+        let hash = state_locked
+            .state_hashes
+            .iter()
+            .find(|(counter, _)| *counter == tick)
+            .map(|(_, hash)| format!("{:016x}", hash));
+        hash
+    }
+
+    // Current physics tick
+    pub fn tick(&self) -> u64 {
+        self.state.read().unwrap().simulation.counter
+    }
+
+    // Starts recording every `decimation`-th physics tick, appending to the
+    // already recorded samples
+    #[wasm_bindgen(js_name = startRecording)]
+    pub fn start_recording(&self, decimation: u32) {
+        let state_locked = &mut *self.state.write().unwrap();
+        state_locked
+            .recorder
+            .start(decimation, &state_locked.simulation);
+    }
+
+    #[wasm_bindgen(js_name = stopRecording)]
+    pub fn stop_recording(&self) {
+        self.state.write().unwrap().recorder.stop();
+    }
+
+    #[wasm_bindgen(js_name = isRecording)]
+    pub fn is_recording(&self) -> bool {
+        self.state.read().unwrap().recorder.is_recording()
+    }
+
+    #[wasm_bindgen(js_name = clearRecording)]
+    pub fn clear_recording(&self) {
+        self.state.write().unwrap().recorder.clear();
+    }
+
+    // Recorded trajectory as "csv" or "jsonl" text
+    #[wasm_bindgen(js_name = exportTrajectory)]
+    pub fn export_trajectory(&self, format: &str) -> Result<String, wasm_bindgen::JsValue> {
+        let format = export_format_from_name(format)?;
+        Ok(self.state.read().unwrap().recorder.export(format))
+    }
+
+    #[wasm_bindgen(js_name = exportTrajectoryBytes)]
+    pub fn export_trajectory_bytes(
+        &self,
+        format: &str,
+    ) -> Result<js_sys::Uint8Array, wasm_bindgen::JsValue> {
+        let text = self.export_trajectory(format)?;
+        Ok(js_sys::Uint8Array::from(text.as_bytes()))
+    }
+
+    // Starts replaying what the recorder has captured so far
+    #[wasm_bindgen(js_name = replayRecording)]
+    pub fn replay_recording(&self) -> Result<(), wasm_bindgen::JsValue> {
+        let mut state_locked = self.state.write().unwrap();
+        state_locked.recorder.stop();
+        let trajectory = replay::Trajectory::from_samples(&state_locked.recorder.samples)?;
+        state_locked.playback = Some(replay::Playback::new(trajectory));
+        Ok(())
+    }
+
+    // Starts replaying an exported trajectory (CSV or JSON lines)
+    #[wasm_bindgen(js_name = importTrajectory)]
+    pub fn import_trajectory(&self, text: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let trajectory = replay::Trajectory::parse(text)?;
+        self.state.write().unwrap().playback = Some(replay::Playback::new(trajectory));
+        Ok(())
+    }
+
+    // Leaves replay mode, the simulation continues from where it was
+    #[wasm_bindgen(js_name = stopReplay)]
+    pub fn stop_replay(&self) {
+        self.state.write().unwrap().stop_playback();
+    }
+
+    // Control the replay if there is one, the live simulation otherwise
+    pub fn play(&self) -> Result<(), wasm_bindgen::JsValue> {
+        self.set_running(true);
+        Ok(())
+    }
+
+    pub fn pause(&self) -> Result<(), wasm_bindgen::JsValue> {
+        self.set_running(false);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = isPaused)]
+    pub fn is_paused(&self) -> bool {
+        let state_locked = self.state.read().unwrap();
+        match &state_locked.playback {
+            Some(playback) => !playback.playing,
+            None => state_locked.paused,
+        }
+    }
+
+    // Grid spacing and angle increment (degrees) gizmo edits snap to, zero
+    // turns snapping off
+    #[wasm_bindgen(js_name = setSnapping)]
+    pub fn set_snapping(&self, grid: f32, angle: f32) -> Result<(), wasm_bindgen::JsValue> {
+        if !(grid >= 0.0 && grid.is_finite() && angle >= 0.0 && angle.is_finite()) {
+            return Err("Snapping increments must be non-negative numbers".into());
+        }
+        self.state.write().unwrap().snapping = gizmo::Snapping {
+            grid,
+            angle: angle.to_radians(),
+        };
+        Ok(())
+    }
+
+    pub fn seek(&self, time: f64) -> Result<(), wasm_bindgen::JsValue> {
+        self.with_playback(|playback| playback.seek(time))
+    }
+
+    #[wasm_bindgen(js_name = setPlaybackSpeed)]
+    pub fn set_playback_speed(&self, speed: f64) -> Result<(), wasm_bindgen::JsValue> {
+        self.with_playback(|playback| playback.speed = speed)
+    }
+
+    #[wasm_bindgen(js_name = setLooping)]
+    pub fn set_looping(&self, looping: bool) -> Result<(), wasm_bindgen::JsValue> {
+        self.with_playback(|playback| playback.looping = looping)
+    }
+
+    #[wasm_bindgen(js_name = playbackTime)]
+    pub fn playback_time(&self) -> Option<f64> {
+        let state_locked = self.state.read().unwrap();
+        state_locked.playback.as_ref().map(|playback| playback.time)
+    }
+
+    // [start, end] of the replayed trajectory
+    #[wasm_bindgen(js_name = playbackRange)]
+    pub fn playback_range(&self) -> Option<Vec<f64>> {
+        let state_locked = self.state.read().unwrap();
+        state_locked.playback.as_ref().map(|playback| {
+            vec![
+                playback.trajectory.start_time(),
+                playback.trajectory.end_time(),
+            ]
+        })
+    }
+
+    // Lets the browser save the recorded trajectory as a file
+    #[wasm_bindgen(js_name = downloadTrajectory)]
+    pub fn download_trajectory(&self, format: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let format = export_format_from_name(format)?;
+        let text = self.state.read().unwrap().recorder.export(format);
+        download(
+            &format!("trajectory.{}", format.extension()),
+            &text,
+            format.mime_type(),
+        )
+    }
+
+    // Selects the body under the canvas point (x, y) in CSS pixels and returns
+    // the hit as JSON ({"body", "name", "point", "distance"}), or clears the
+    // selection and returns nothing
+    #[wasm_bindgen(js_name = pickAt)]
+    pub fn pick_at(&self, x: f32, y: f32) -> Result<Option<String>, wasm_bindgen::JsValue> {
+        let ndc = canvas_to_ndc(&find_canvas()?, x, y);
+        let mut state_locked = self.state.write().unwrap();
+        Ok(state_locked.pick(&ndc).map(|(hit, name)| {
+            serde_json::json!({
+                "body": hit.body,
+                "name": name,
+                "point": <[f32; 3]>::from(hit.point),
+                "distance": hit.distance,
+            })
+            .to_string()
+        }))
+    }
+
+    // "pull" drags the grabbed point along with a damped spring, "impulse"
+    // flicks it on release
+    #[wasm_bindgen(js_name = setDragMode)]
+    pub fn set_drag_mode(&self, mode: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let mode = drag::DragMode::from_name(mode)
+            .ok_or_else(|| format!("Unknown drag mode: {}", mode))?;
+        self.state.write().unwrap().drag_mode = mode;
+        Ok(())
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.state.read().unwrap().selected
+    }
+
+    pub fn select(&self, body: Option<usize>) -> Result<(), wasm_bindgen::JsValue> {
+        let mut state_locked = self.state.write().unwrap();
+        if body >= Some(state_locked.simulation.bodies.len()) {
+            return Err("Body index out of range".into());
+        }
+        state_locked.selected = body;
+        Ok(())
+    }
+
+    // Adds a body given as JSON, in the format of the scene's `bodies`, and
+    // returns its index
+    #[wasm_bindgen(js_name = addBody)]
+    pub fn add_body(&self, json: &str) -> Result<usize, wasm_bindgen::JsValue> {
+        let spec: scene::BodySpec = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        spec.validate("body").map_err(|err| err.to_string())?;
+        let body = spec.build("body").map_err(|err| err.to_string())?;
+
+        let mut state_locked = self.state.write().unwrap();
+        state_locked.execute(history::Edit::AddBody { body });
+        Ok(state_locked.simulation.bodies.len() - 1)
+    }
+
+    // Removes a body along with the forces, joints and springs acting on it.
+    // Later bodies move down an index.
+    #[wasm_bindgen(js_name = removeBody)]
+    pub fn remove_body(&self, index: usize) -> Result<(), wasm_bindgen::JsValue> {
+        let mut state_locked = self.state.write().unwrap();
+        if index >= state_locked.simulation.bodies.len() {
+            return Err("Body index out of range".into());
+        }
+        let edit =
+            history::Edit::remove_body(&state_locked.simulation, &state_locked.trails, index);
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Orientation as a quaternion [w, x, y, z]
+    #[wasm_bindgen(js_name = setPose)]
+    pub fn set_pose(
+        &self,
+        body: usize,
+        pos: &[f32],
+        orientation: &[f32],
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let pos = vector_from_slice(pos)?;
+        let orientation = quaternion_from_slice(orientation)?;
+
+        let mut state_locked = self.state.write().unwrap();
+        let changed = state_locked.changed_body(body, |spec| {
+            spec.pos = pos.into();
+            spec.orientation = orientation;
+        })?;
+        let edit =
+            history::Edit::set_pose(&state_locked.simulation, body, history::Pose::of(&changed));
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Linear velocity and world-frame angular momentum
+    #[wasm_bindgen(js_name = setVelocity)]
+    pub fn set_velocity(
+        &self,
+        body: usize,
+        lin_vel: &[f32],
+        ang_mom: &[f32],
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let lin_vel = vector_from_slice(lin_vel)?;
+        let ang_mom = vector_from_slice(ang_mom)?;
+
+        let mut state_locked = self.state.write().unwrap();
+        let changed = state_locked.changed_body(body, |spec| {
+            spec.lin_vel = lin_vel.into();
+            spec.ang_mom = ang_mom.into();
+        })?;
+        let edit = history::Edit::set_velocity(
+            &state_locked.simulation,
+            body,
+            history::Velocity::of(&changed),
+        );
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Inertia tensor as 9 numbers in row-major order, derived from the shape
+    // if left out
+    #[wasm_bindgen(js_name = setMassProperties)]
+    pub fn set_mass_properties(
+        &self,
+        body: usize,
+        mass: f32,
+        inertia: Option<Vec<f32>>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let inertia = match inertia.as_deref() {
+            Some([a, b, c, d, e, f, g, h, i]) => Some([[*a, *b, *c], [*d, *e, *f], [*g, *h, *i]]),
+            Some(_) => return Err("Expected an inertia tensor of 9 numbers".into()),
+            None => None,
+        };
+
+        let mut state_locked = self.state.write().unwrap();
+        let changed = state_locked.changed_body(body, |spec| {
+            spec.mass = mass;
+            spec.inertia = inertia;
+        })?;
+        let edit = history::Edit::set_mass_properties(
+            &state_locked.simulation,
+            body,
+            history::MassProperties::of(&changed),
+        );
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Material as JSON ({"restitution", "friction"})
+    #[wasm_bindgen(js_name = setMaterial)]
+    pub fn set_material(&self, body: usize, json: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let material: body::Material = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        let mut state_locked = self.state.write().unwrap();
+        let changed = state_locked.changed_body(body, |spec| spec.material = material)?;
+        let edit = history::Edit::set_material(&state_locked.simulation, body, changed.material);
+        state_locked.execute(edit);
+        Ok(())
+    }
+
+    // Shows or hides the panel with the selected body's state
+    #[wasm_bindgen(js_name = showInspector)]
+    pub fn show_inspector(&self, shown: bool) {
+        self.state.write().unwrap().overlays.inspector = shown;
+    }
+
+    // Whether there was anything to undo
+    pub fn undo(&self) -> bool {
+        self.state.write().unwrap().undo()
+    }
+
+    pub fn redo(&self) -> bool {
+        self.state.write().unwrap().redo()
+    }
+}
+
+fn export_format_from_name(name: &str) -> Result<recorder::ExportFormat, wasm_bindgen::JsValue> {
+    recorder::ExportFormat::from_name(name)
+        .ok_or_else(|| format!("Unknown trajectory format: {}", name).into())
+}
+
+fn download(filename: &str, text: &str, mime_type: &str) -> Result<(), wasm_bindgen::JsValue> {
+    let window = web_sys::window().ok_or("No window")?;
+    let document = window.document().ok_or("No document")?;
+
+    let parts = js_sys::Array::of1(&js_sys::JsString::from(text));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let anchor = document
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url)
+}
+
+fn find_canvas() -> Result<web_sys::HtmlCanvasElement, wasm_bindgen::JsValue> {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("physsim-viz-canvas"))
+        .ok_or("Canvas not found")?
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| "Canvas isn't canvas".into())
+}
+
+// CSS pixels relative to the canvas to normalized device coordinates
+fn canvas_to_ndc(canvas: &web_sys::HtmlCanvasElement, x: f32, y: f32) -> nalgebra::Vector2<f32> {
+    let width = (canvas.client_width() as f32).max(1.0);
+    let height = (canvas.client_height() as f32).max(1.0);
+    nalgebra::Vector2::new(2.0 * x / width - 1.0, 1.0 - 2.0 * y / height)
+}
+
+fn check_trail_length(length: usize) -> Result<(), wasm_bindgen::JsValue> {
+    if length > trails::MAX_LENGTH {
+        return Err(format!("Trail length must be at most {}", trails::MAX_LENGTH).into());
+    }
+    Ok(())
+}
+
+fn force_from_json(json: &str) -> Result<forces::ForceGenerator, wasm_bindgen::JsValue> {
+    serde_json::from_str(json).map_err(|err| err.to_string().into())
+}
+
+fn vector_from_slice(v: &[f32]) -> Result<nalgebra::Vector3<f32>, wasm_bindgen::JsValue> {
+    match v {
+        [x, y, z] => Ok(nalgebra::Vector3::new(*x, *y, *z)),
+        _ => Err("Expected a vector of 3 numbers".into()),
+    }
+}
+
+fn quaternion_from_slice(q: &[f32]) -> Result<[f32; 4], wasm_bindgen::JsValue> {
+    match q {
+        [w, x, y, z] => Ok([*w, *x, *y, *z]),
+        _ => Err("Expected a quaternion of 4 numbers".into()),
+    }
+}
+
+fn color_from_slice(c: &[f32]) -> Result<(f32, f32, f32), wasm_bindgen::JsValue> {
+    match c {
+        [r, g, b] => Ok((*r, *g, *b)),
+        _ => Err("Expected a color of 3 numbers".into()),
+    }
+}
+
+impl Runner {
+    fn set_running(&self, running: bool) {
+        let mut state_locked = self.state.write().unwrap();
+        match state_locked.playback.as_mut() {
+            Some(playback) => playback.playing = running,
+            None => state_locked.paused = !running,
+        }
+    }
+
+    fn with_playback(
+        &self,
+        f: impl FnOnce(&mut replay::Playback),
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let mut state_locked = self.state.write().unwrap();
+        let playback = state_locked.playback.as_mut().ok_or("Not in replay mode")?;
+        f(playback);
+        Ok(())
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        log::debug!("Dropping Runner");
+        match web_sys::window() {
+            Some(window) => {
+                window.clear_interval_with_handle(self.draw_interval_token);
+                window.clear_interval_with_handle(self.physics_interval_token);
+            }
+            _ => {}
+        }
+
+        // Otherwise they'd call the closures freed along with the runner
+        let listeners: [(&web_sys::EventTarget, &str, &js_sys::Function); 6] = [
+            (
+                &self.document,
+                "keydown",
+                self.keydown_closure.as_ref().unchecked_ref(),
+            ),
+            (
+                &self.document,
+                "keyup",
+                self.keyup_closure.as_ref().unchecked_ref(),
+            ),
+            (
+                &self.canvas,
+                "mousedown",
+                self.mousedown_closure.as_ref().unchecked_ref(),
+            ),
+            (
+                &self.canvas,
+                "mousemove",
+                self.mousemove_closure.as_ref().unchecked_ref(),
+            ),
+            (
+                &self.document,
+                "mouseup",
+                self.mouseup_closure.as_ref().unchecked_ref(),
+            ),
+            (
+                self.inspector.panel(),
+                "change",
+                self.inspector_change_closure.as_ref().unchecked_ref(),
+            ),
+        ];
+        for (target, event, listener) in listeners {
+            let _ = target.remove_event_listener_with_callback(event, listener);
+        }
+        self.inspector.panel().remove();
+    }
+}
+
+fn compile_shader(
+    ctx: &web_sys::WebGl2RenderingContext,
+    shader_type: u32,
+    source: &str,
+) -> Result<web_sys::WebGlShader, String> {
+    let shader = ctx
+        .create_shader(shader_type)
+        .ok_or_else(|| String::from("Couldn't create shader object"))?;
+    ctx.shader_source(&shader, source);
+    ctx.compile_shader(&shader);
+
+    if ctx
+        .get_shader_parameter(&shader, web_sys::WebGl2RenderingContext::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(shader)
+    } else {
+        Err(ctx
+            .get_shader_info_log(&shader)
+            .unwrap_or_else(|| String::from("Unknown error occurred when creating shader")))
+    }
+}
+
+fn link_program(
+    ctx: &web_sys::WebGl2RenderingContext,
+    vert_shader: &web_sys::WebGlShader,
+    frag_shader: &web_sys::WebGlShader,
+) -> Result<web_sys::WebGlProgram, String> {
+    let program = ctx
+        .create_program()
+        .ok_or_else(|| String::from("Couldn't create program object"))?;
+
+    ctx.attach_shader(&program, vert_shader);
+    ctx.attach_shader(&program, frag_shader);
+    ctx.link_program(&program);
+
+    if ctx
+        .get_program_parameter(&program, web_sys::WebGl2RenderingContext::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false)
+    {
+        Ok(program)
+    } else {
+        Err(ctx
+            .get_program_info_log(&program)
+            .unwrap_or_else(|| String::from("Unknown error occurred when creating program")))
+    }
+}
+
+fn draw(
+    ctx: &web_sys::WebGl2RenderingContext,
+    vbo: &web_sys::WebGlBuffer,
+    vao_plain: &web_sys::WebGlVertexArrayObject,
+    program_plain: &web_sys::WebGlProgram,
+    vao_colored: &web_sys::WebGlVertexArrayObject,
+    program_colored: &web_sys::WebGlProgram,
+    state: std::sync::Arc<std::sync::RwLock<RunnerState>>,
+) {
+    let totals = {
+        let mut state_locked = state.write().unwrap();
+        if state_locked.overlays.conserved {
+            Some(state_locked.readout_totals())
+        } else {
+            None
+        }
+    };
+
+    let state_locked = state.read().unwrap();
+    let bodies = state_locked.displayed_bodies();
+
+    //let vertices: [f32; 9] = [
+    //    -0.7,
+    //    -0.7,
+    //    0.0,
+    //    0.7,
+    //    -0.7,
+    //    0.0,
+    //    -0.8 + (state.counter as f32) * 0.002,
+    //    0.7,
+    //    0.0,
+    //];
+
+    let proj_mat =
+        camera::view_projection(&state_locked.camera_pos, &state_locked.camera_rot, ASPECT);
+
+    ctx.use_program(Some(program_plain));
+    ctx.bind_vertex_array(Some(&vao_plain));
+    //ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&vbo));
+
+    let plain_proj_uni_loc = ctx
+        .get_uniform_location(program_plain, "projection")
+        .expect("Uniform projection not found");
+    ctx.uniform_matrix4fv_with_f32_array(
+        Some(&plain_proj_uni_loc),
+        false,
+        &proj_mat.data.0.as_flattened(),
+    );
+    let plain_color_uni_loc = ctx
+        .get_uniform_location(program_plain, "color")
+        .expect("Uniform color not found");
+    ctx.uniform4f(Some(&plain_color_uni_loc), 1.0, 1.0, 1.0, 1.0);
+
+    let mut vertices_plain: Vec<f32> = Vec::new();
+    for body in bodies.iter() {
+        geometry::shape_to_vertices(
+            &mut vertices_plain,
+            &body.shape,
+            &body.rigid_body,
+            state_locked.wireframe,
+        );
+    }
+
+    //unsafe {
+    //    let vertices_view = js_sys::Float32Array::view(&vertices_plain);
+    //
+    //    ctx.buffer_data_with_array_buffer_view(
+    //        web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+    //        &vertices_view,
+    //        web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+    //    );
+    //}
+    let vertices_plain_f32_array =
+        js_sys::Float32Array::new_with_length(vertices_plain.len() as u32);
+    vertices_plain_f32_array.copy_from(&vertices_plain);
+    ctx.buffer_data_with_array_buffer_view(
+        web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+        &vertices_plain_f32_array,
+        web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+    );
+
+    let vert_count_plain = (vertices_plain.len() / 3) as i32;
+
+    ctx.clear_color(0.0, 0.0, 0.0, 1.0);
+    ctx.clear(web_sys::WebGl2RenderingContext::COLOR_BUFFER_BIT);
+
+    if state_locked.wireframe {
+        ctx.draw_arrays(web_sys::WebGl2RenderingContext::LINES, 0, vert_count_plain);
+    } else {
+        ctx.draw_arrays(
+            web_sys::WebGl2RenderingContext::TRIANGLES,
+            0,
+            vert_count_plain,
+        );
+    }
+
+    // Selection highlight
+    if let Some(body) = state_locked
+        .selected
+        .and_then(|selected| bodies.get(selected))
+    {
+        let mut vertices_selection: Vec<f32> = Vec::new();
+        geometry::shape_to_vertices(&mut vertices_selection, &body.shape, &body.rigid_body, true);
+        let vertices_selection_f32_array =
+            js_sys::Float32Array::new_with_length(vertices_selection.len() as u32);
+        vertices_selection_f32_array.copy_from(&vertices_selection);
+        ctx.buffer_data_with_array_buffer_view(
+            web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+            &vertices_selection_f32_array,
+            web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        ctx.uniform4f(
+            Some(&plain_color_uni_loc),
+            SELECTION_COLOR.0,
+            SELECTION_COLOR.1,
+            SELECTION_COLOR.2,
+            1.0,
+        );
+        ctx.draw_arrays(
+            web_sys::WebGl2RenderingContext::LINES,
+            0,
+            (vertices_selection.len() / 3) as i32,
+        );
+    }
+
+    let t = state_locked.displayed_time();
+    let mut readout = format!("t = {:.2} s", t);
+
+    // Analytic reference solution
+    if state_locked.overlays.reference {
+        let mut vertices_reference: Vec<f32> = Vec::new();
+        for (_, (body, reference)) in bodies
+            .iter()
+            .zip(state_locked.references.iter())
+            .enumerate()
+            .filter(|(i, _)| state_locked.is_shown(*i))
+        {
+            let reference_body = reference.rigid_body_at(t);
+            geometry::shape_to_vertices(
+                &mut vertices_reference,
+                &body.shape,
+                &reference_body,
+                true,
+            );
+
+            let error = reference.error(&body.rigid_body, t);
+            readout += &format!(
+                "\n{}: orientation error = {:.3e} rad, position error = {:.3e}",
+                body.name, error.orientation, error.position
+            );
+        }
+        let vertices_reference_f32_array =
+            js_sys::Float32Array::new_with_length(vertices_reference.len() as u32);
+        vertices_reference_f32_array.copy_from(&vertices_reference);
+        ctx.buffer_data_with_array_buffer_view(
+            web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+            &vertices_reference_f32_array,
+            web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+
+        ctx.enable(web_sys::WebGl2RenderingContext::BLEND);
+        ctx.blend_func(
+            web_sys::WebGl2RenderingContext::SRC_ALPHA,
+            web_sys::WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        ctx.uniform4f(Some(&plain_color_uni_loc), 0.3, 1.0, 0.3, 0.4);
+        ctx.draw_arrays(
+            web_sys::WebGl2RenderingContext::LINES,
+            0,
+            (vertices_reference.len() / 3) as i32,
+        );
+        ctx.disable(web_sys::WebGl2RenderingContext::BLEND);
+    }
+
+    if let Some(totals) = totals {
+        let initial = &state_locked.initial_totals;
+        readout += &format!(
+            "\nenergy = {:.6e} (drift {:.2e})",
+            totals.energy,
+            totals.energy - initial.energy
+        );
+        readout += &format!(
+            "\nmomentum = ({:.4e}, {:.4e}, {:.4e}) (drift {:.2e})",
+            totals.momentum.x,
+            totals.momentum.y,
+            totals.momentum.z,
+            (totals.momentum - initial.momentum).norm()
+        );
+        readout += &format!(
+            "\nangular momentum = ({:.4e}, {:.4e}, {:.4e}) (drift {:.2e})",
+            totals.ang_mom.x,
+            totals.ang_mom.y,
+            totals.ang_mom.z,
+            (totals.ang_mom - initial.ang_mom).norm()
+        );
+    }
+
+    if state_locked.overlays.reference || state_locked.overlays.conserved {
+        set_readout(&readout);
+    }
+
+    ctx.use_program(Some(program_colored));
+    ctx.bind_vertex_array(Some(&vao_colored));
+    //ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&vbo));
+
+    let colored_proj_uni_loc = ctx
+        .get_uniform_location(program_colored, "projection")
+        .expect("Uniform projection not found");
+    ctx.uniform_matrix4fv_with_f32_array(
+        Some(&colored_proj_uni_loc),
+        false,
+        &proj_mat.data.0.as_flattened(),
+    );
+
+    let mut vertices_colored: Vec<f32> = Vec::new();
+
+    if let Some(ground) = &state_locked.simulation.ground {
+        contact::ground_to_vertices(&mut vertices_colored, ground, &state_locked.camera_pos);
+    }
+
+    let coords_system_axes_sizes = 5.0;
+    geometry::vector_to_vertices(
+        &mut vertices_colored,
+        &nalgebra::Vector3::zeros(),
+        &nalgebra::Vector3::new(coords_system_axes_sizes, 0.0, 0.0),
+        Some((1.0, 0.0, 0.0)),
+        0.5,
+    );
+    geometry::vector_to_vertices(
+        &mut vertices_colored,
+        &nalgebra::Vector3::zeros(),
+        &nalgebra::Vector3::new(0.0, coords_system_axes_sizes, 0.0),
+        Some((0.0, 1.0, 0.0)),
+        0.5,
+    );
+    geometry::vector_to_vertices(
+        &mut vertices_colored,
+        &nalgebra::Vector3::zeros(),
+        &nalgebra::Vector3::new(0.0, 0.0, coords_system_axes_sizes),
+        Some((0.0, 0.0, 1.0)),
+        0.5,
+    );
+
+    for (_, body) in bodies
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| state_locked.is_shown(*i))
+    {
+        geometry::vector_to_vertices(
+            &mut vertices_colored,
+            &body.rigid_body.pos,
+            &body.rigid_body.lin_vel,
+            Some((1.0, 1.0, 0.0)),
+            0.1,
+        );
+        geometry::vector_to_vertices(
+            &mut vertices_colored,
+            &body.rigid_body.pos,
+            &body.rigid_body.ang_mom,
+            Some((0.0, 1.0, 1.0)),
+            0.1,
+        );
+        overlays::overlays_to_vertices(
+            &mut vertices_colored,
+            &body.rigid_body,
+            &state_locked.overlays,
+        );
+        overlays::poinsot_to_vertices(
+            &mut vertices_colored,
+            &body.rigid_body,
+            &state_locked.overlays,
+        );
+    }
+    forces::forces_to_vertices(
+        &mut vertices_colored,
+        &state_locked.simulation.forces,
+        &bodies,
+        &state_locked.overlays,
+    );
+    joints::joints_to_vertices(
+        &mut vertices_colored,
+        &state_locked.simulation.joints,
+        &bodies,
+        &state_locked.overlays.joints,
+    );
+    springs::springs_to_vertices(
+        &mut vertices_colored,
+        &state_locked.simulation.springs,
+        &bodies,
+        &state_locked.overlays.springs,
+    );
+    contact::contacts_to_vertices(
+        &mut vertices_colored,
+        &state_locked.contact_history,
+        &state_locked.overlays.contacts,
+    );
+    if let Some(drag) = &state_locked.drag {
+        drag::drag_to_vertices(&mut vertices_colored, drag, &bodies);
+    }
+    if let Some(selected) = state_locked.editable_selection() {
+        gizmo::gizmo_to_vertices(
+            &mut vertices_colored,
+            &bodies[selected],
+            state_locked
+                .manipulation
+                .as_ref()
+                .map(|manipulation| manipulation.handle),
+        );
+    }
+    let vert_count_lines = (vertices_colored.len() / 6) as i32;
+
+    let mut strips = Vec::new();
+    for (_, (body, traces)) in bodies
+        .iter()
+        .zip(state_locked.ang_vel_traces.iter())
+        .enumerate()
+        .filter(|(i, _)| state_locked.is_shown(*i))
+    {
+        strips.extend(overlays::traces_to_vertices(
+            &mut vertices_colored,
+            &body.rigid_body,
+            traces,
+            &state_locked.overlays,
+        ));
+    }
+    if state_locked.overlays.trails {
+        strips.extend(trails::trails_to_vertices(
+            &mut vertices_colored,
+            &state_locked.trails,
+        ));
+    }
+
+    //unsafe {
+    //    let vertices_view = js_sys::Float32Array::view(&vertices_colored);
+    //
+    //    ctx.buffer_data_with_array_buffer_view(
+    //        web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+    //        &vertices_view,
+    //        web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+    //    );
+    //}
+    let vertices_colored_f32_array =
+        js_sys::Float32Array::new_with_length(vertices_colored.len() as u32);
+    vertices_colored_f32_array.copy_from(&vertices_colored);
+    ctx.buffer_data_with_array_buffer_view(
+        web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+        &vertices_colored_f32_array,
+        web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+    );
+
+    ctx.draw_arrays(web_sys::WebGl2RenderingContext::LINES, 0, vert_count_lines);
+    for (first, count) in strips {
+        ctx.draw_arrays(web_sys::WebGl2RenderingContext::LINE_STRIP, first, count);
+    }
+}
+
+fn set_readout(text: &str) {
+    let readout = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("physsim-viz-readout"));
+    if let Some(readout) = readout {
+        readout.set_text_content(Some(text));
+    }
+}
+
+fn physics_step(state: std::sync::Arc<std::sync::RwLock<RunnerState>>) {
+    let state_locked = &mut *state.write().unwrap();
+
+    // Camera movement
+    let cam_linear_speed: f32 = 0.001 * PHYSICS_INTERVAL;
+    let cam_angular_sleep: f32 = 0.001 * PHYSICS_INTERVAL;
+    let keys = &state_locked.keys_pressed;
+    let moves = [
+        (keys.w, nalgebra::Vector3::new(0.0, 0.0, -cam_linear_speed)),
+        (keys.s, nalgebra::Vector3::new(0.0, 0.0, cam_linear_speed)),
+        (keys.a, nalgebra::Vector3::new(-cam_linear_speed, 0.0, 0.0)),
+        (keys.d, nalgebra::Vector3::new(cam_linear_speed, 0.0, 0.0)),
+        (keys.q, nalgebra::Vector3::new(0.0, -cam_linear_speed, 0.0)),
+        (keys.e, nalgebra::Vector3::new(0.0, cam_linear_speed, 0.0)),
+    ];
+    let turns = [
+        (keys.i, nalgebra::Vector3::new(cam_angular_sleep, 0.0, 0.0)),
+        (keys.k, nalgebra::Vector3::new(-cam_angular_sleep, 0.0, 0.0)),
+        (keys.j, nalgebra::Vector3::new(0.0, cam_angular_sleep, 0.0)),
+        (keys.l, nalgebra::Vector3::new(0.0, -cam_angular_sleep, 0.0)),
+        (keys.u, nalgebra::Vector3::new(0.0, 0.0, cam_angular_sleep)),
+        (keys.o, nalgebra::Vector3::new(0.0, 0.0, -cam_angular_sleep)),
+    ];
+    for (_, delta) in moves.iter().filter(|(pressed, _)| *pressed) {
+        camera::move_local(
+            &mut state_locked.camera_pos,
+            &state_locked.camera_rot,
+            delta,
+        );
+    }
+    // One after the other, rotations don't commute
+    for (_, axis_angle) in turns.iter().filter(|(pressed, _)| *pressed) {
+        camera::turn_local(&mut state_locked.camera_rot, axis_angle);
+    }
+
+    let now = js_sys::Date::now();
+    let elapsed = match state_locked.last_physics_time {
+        Some(last) => (now - last) / 1000.0,
+        None => PHYSICS_INTERVAL as f64 / 1000.0,
+    };
+    state_locked.last_physics_time = Some(now);
+
+    // Replay drives the view instead of the simulation
+    if let Some(playback) = state_locked.playback.as_mut() {
+        playback.advance(elapsed);
+        return;
+    }
+    if state_locked.paused {
+        return;
+    }
+
+    // Fixed steps for the real time that passed, the timer rate only decides
+    // how they are batched
+    let timestep = state_locked.simulation.timestep;
+    let steps = state_locked.clock.advance(elapsed, timestep);
+
+    for _ in 0..steps {
+        if let Some(drag) = &state_locked.drag {
+            drag.apply(&mut state_locked.simulation.bodies, timestep);
+        }
+        state_locked.simulation.step();
+        state_locked.recorder.record(&state_locked.simulation);
+        state_locked.state_hashes.push((
+            state_locked.simulation.counter,
+            state_locked.simulation.state_hash(),
+        ));
+        state_locked
+            .contact_history
+            .record(&state_locked.simulation.contacts);
+
+        let bodies = &state_locked.simulation.bodies;
+        for (body, traces) in bodies.iter().zip(state_locked.ang_vel_traces.iter_mut()) {
+            traces.record(&body.rigid_body);
+        }
+        for trail in state_locked.trails.iter_mut() {
+            trail.record(
+                state_locked.simulation.counter,
+                &bodies[trail.body].rigid_body,
+            );
+        }
+    }
+}